@@ -20,6 +20,7 @@ mod print;
 mod choice;
 mod section;
 mod summary;
+mod region_provider;
 
 pub use file_upload::*;
 pub use nova_form_wrapper::*;
@@ -42,4 +43,5 @@ pub use toolbar::*;
 pub use preview::*;
 pub use choice::*;
 pub use section::*;
-pub use summary::*;
\ No newline at end of file
+pub use summary::*;
+pub use region_provider::*;
\ No newline at end of file
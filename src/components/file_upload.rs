@@ -1,89 +1,675 @@
-use std::{fmt::Display, str::FromStr};
+use std::{fmt::Display, str::FromStr, sync::{Arc, Mutex}};
 
 use leptos::*;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use uuid::Uuid;
 
-use crate::{Group, GroupContext, Icon, QueryStringPart};
-use serde::de::Error;
-use server_fn::codec::{MultipartData, MultipartFormData};
-use web_sys::{wasm_bindgen::JsCast, FormData, HtmlInputElement};
+use crate::{use_translation, AppContext, Button, ButtonGroup, FormContext, FormData, Group, GroupContext, Icon, QueryStringPart};
+use serde::de::Error as _;
+use server_fn::{codec::{MultipartData, MultipartFormData}, error::ServerFnErrorSerde, ServerFn};
+use web_sys::{
+    wasm_bindgen::{closure::Closure, JsCast, JsValue},
+    FileList, FormData as WebFormData, HtmlInputElement, ProgressEvent, XmlHttpRequest,
+};
 
 
 // See this for reference: https://github.com/leptos-rs/leptos/blob/96e2b5cba10d2296f262820be19cac9b615b0d23/examples/server_fns_axum/src/app.rs
 
+/// The uploaded files of a `FileUpload`, in the order in which they are shown.
+type Files = Vec<(FileId, FileInfo)>;
+
+/// Collects the files of all `FileUpload`s while a form is rendered to PDF,
+/// so that they can be appended to the document, see `Attachments`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RenderedAttachments(Arc<Mutex<Files>>);
+
+impl RenderedAttachments {
+    /// Adds files that were not added before, keeping the order in which they are rendered.
+    pub(crate) fn add(&self, files: &[(FileId, FileInfo)]) {
+        let mut attachments = self.0.lock().unwrap();
+        for (file_id, file_info) in files {
+            if !attachments.iter().any(|(id, _)| id == file_id) {
+                attachments.push((*file_id, file_info.clone()));
+            }
+        }
+    }
+
+    #[cfg(feature = "ssr")]
+    pub(crate) fn take(&self) -> Files {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
 /// A component that allows users to upload files.
 /// The files are automatically uploaded to the server and stored in the `FileStore`.
+/// Previews of uploaded images and PDFs are loaded from `FileStore::serve_file`, which must be mounted under `FILES_PATH`.
+/// Files that were already uploaded are loaded from the `FileStore`,
+/// so the `FileStore` must be provided as a context when rendering the form on the server.
 #[component]
 pub fn FileUpload(
     /// The query string to bind to a list of `FileId`s.
-    #[prop(into)] bind: QueryStringPart
+    #[prop(into)] bind: QueryStringPart,
+    /// An optional label for the file upload.
+    #[prop(optional, into)] label: Option<TextProp>,
+    /// Restricts the number, size and type of the uploaded files.
+    /// The limits are checked in the browser and enforced by the server, see `UploadLimits`.
+    #[prop(optional)] limits: UploadLimits,
 ) -> impl IntoView {
-    let (file_info, set_file_info) = create_signal(Vec::new());
+    let (error, set_error) = create_signal(None::<UploadError>);
+    let accept = limits.accept();
+    let multiple = limits.max_files != Some(1);
+    let limits = store_value(limits);
 
-    let on_input = move |ev: web_sys::Event| {
-        let target = ev
-            .target()
-            .expect("target must exist")
-            .unchecked_into::<HtmlInputElement>();
+    view! {
+        <Group bind=bind>
+            {
+                let group = expect_context::<GroupContext>();
+                let qs = group.qs();
+                #[cfg(feature = "ssr")]
+                limits.with_value(|limits| register_upload_field(&qs, limits));
+                let nova_form_context = expect_context::<FormContext>();
+                let rendered_attachments = use_context::<RenderedAttachments>();
+                if let Some(label) = label.clone() {
+                    group.add_label(label);
+                }
 
-        if let Some(files) = target.files() {
-            let form_data: FormData = FormData::new().expect("can create form data");
+                // Load the files that are already bound to the form data, for example when rendering a submitted form.
+                let form_data = expect_context::<FormData>();
+                let initial_ids = (0..group.len().get_untracked().unwrap_or_default())
+                    .filter_map(|i| {
+                        form_data
+                            .get(qs.add_index(i))
+                            .get_untracked()?
+                            .as_input()?
+                            .raw()
+                            .parse::<FileId>()
+                            .ok()
+                    })
+                    .collect::<Vec<_>>();
+                let initial_files = create_resource(
+                    move || initial_ids.clone(),
+                    |file_ids| async move {
+                        if file_ids.is_empty() {
+                            Ok(Vec::new())
+                        } else {
+                            file_infos(file_ids).await
+                        }
+                    },
+                );
 
-            for i in 0..files.length() {
-                let file = files.get(i).expect("file must exist");
-                let file_name = file.name();
+                // `None` until the user changes the files, then the files are kept in this signal.
+                let (changed_files, set_changed_files) = create_signal(None::<Files>);
+                let files = Signal::derive(move || {
+                    changed_files
+                        .get()
+                        .or_else(|| initial_files.get().and_then(Result::ok))
+                        .unwrap_or_default()
+                });
+                let update_files = move |f: &dyn Fn(&mut Files)| {
+                    let mut new_files = files.get_untracked();
+                    f(&mut new_files);
+                    set_changed_files.set(Some(new_files));
+                };
 
-                form_data
-                    .append_with_blob_and_filename(&file_name, &file, &file_name)
-                    .expect("appending file to form data must be successful");
-            }
+                // Files that are being uploaded or whose upload failed, in the order in which they were added.
+                let (uploads, set_uploads) = create_signal(Vec::<PendingUpload>::new());
+                let next_upload_id = store_value(0usize);
+                let (dragging, set_dragging) = create_signal(false);
 
-            spawn_local(async move {
-                let mut new_file_infos = upload_file(form_data.into())
-                    .await
-                    .expect("couldn't upload file");
+                let start_upload = move |upload: PendingUpload| {
+                    upload.failed.set(false);
+                    upload.progress.set(0.0);
+                    nova_form_context.begin_upload();
 
-                set_file_info.update(|file_info| {
-                    file_info.append(&mut new_file_infos);
-                });
-            });
-        }
-    };
+                    let form_data: WebFormData = WebFormData::new().expect("can create form data");
 
-    view! {
-        <Group bind=bind>
-            {
-                let group = expect_context::<GroupContext>();
-                let qs = group.qs();
+                    // The field must be sent before the file, so that the server can count the files of the field.
+                    form_data
+                        .append_with_str("field", &qs.to_string())
+                        .expect("appending field to form data must be successful");
+
+                    let file_name = upload.file.name();
+                    form_data
+                        .append_with_blob_and_filename(&file_name, &upload.file, &file_name)
+                        .expect("appending file to form data must be successful");
+
+                    spawn_local(async move {
+                        let progress = upload.progress;
+                        let result = send_upload(form_data, move |p| progress.set(p)).await;
+                        nova_form_context.end_upload();
+
+                        match result {
+                            Ok(Ok(new_file_infos)) => {
+                                set_uploads.update(|uploads| uploads.retain(|u| u.id != upload.id));
+                                update_files(&|files| files.extend(new_file_infos.iter().cloned()));
+                            }
+                            // The server rejected the file, so retrying would not help.
+                            Ok(Err(err)) => {
+                                set_uploads.update(|uploads| uploads.retain(|u| u.id != upload.id));
+                                set_error.set(Some(err));
+                            }
+                            Err(err) => {
+                                logging::warn!("could not upload file {}: {}", file_name, err);
+                                upload.failed.set(true);
+                                set_error.set(Some(UploadError::UploadFailed { file_name }));
+                            }
+                        }
+                    });
+                };
+
+                let add_files = move |new_files: Vec<web_sys::File>| {
+                    if new_files.is_empty() {
+                        return;
+                    }
+
+                    // Check the limits before uploading, so that users get immediate feedback.
+                    let existing = files.get_untracked();
+                    let pending = uploads.get_untracked();
+                    let checked = limits.with_value(|limits| {
+                        limits.check_count(existing.len() + pending.len() + new_files.len())?;
+                        for file in &new_files {
+                            let content_type = Some(file.type_()).filter(|t| !t.is_empty());
+                            limits.check_file(&file.name(), content_type.as_deref(), file.size() as u64)?;
+                        }
+                        let total_size = existing.iter().filter_map(|(_, file_info)| file_info.size()).sum::<u64>()
+                            + pending.iter().map(|upload| upload.file.size() as u64).sum::<u64>()
+                            + new_files.iter().map(|file| file.size() as u64).sum::<u64>();
+                        limits.check_total_size(total_size)
+                    });
+
+                    if let Err(err) = checked {
+                        set_error.set(Some(err));
+                        return;
+                    }
+                    set_error.set(None);
+
+                    // Each file is uploaded on its own, so that the progress can be shown
+                    // and failed uploads can be retried individually.
+                    for file in new_files {
+                        let id = next_upload_id.get_value();
+                        next_upload_id.set_value(id + 1);
+
+                        let upload = PendingUpload {
+                            id,
+                            file,
+                            progress: create_rw_signal(0.0),
+                            failed: create_rw_signal(false),
+                        };
+                        set_uploads.update(|uploads| uploads.push(upload.clone()));
+                        start_upload(upload);
+                    }
+                };
+
+                let on_input = move |ev: web_sys::Event| {
+                    let target = ev
+                        .target()
+                        .expect("target must exist")
+                        .unchecked_into::<HtmlInputElement>();
+
+                    if let Some(file_list) = target.files() {
+                        add_files(file_list_to_vec(&file_list));
+                    }
+
+                    // Reset the input so that selecting the same file again triggers another input event.
+                    target.set_value("");
+                };
+
+                let on_drop = move |ev: web_sys::DragEvent| {
+                    ev.prevent_default();
+                    set_dragging.set(false);
+
+                    if let Some(file_list) = ev.data_transfer().and_then(|data_transfer| data_transfer.files()) {
+                        add_files(file_list_to_vec(&file_list));
+                    }
+                };
+
+                let retry = move |upload: PendingUpload| {
+                    set_error.set(None);
+                    start_upload(upload);
+                };
+
+                let cancel = move |id: usize| {
+                    set_error.set(None);
+                    set_uploads.update(|uploads| uploads.retain(|u| u.id != id));
+                };
+
+                let remove = move |file_id: FileId| {
+                    update_files(&|files| files.retain(|(id, _)| *id != file_id));
+                    // The file is not referenced by the form anymore, so it can be discarded right away.
+                    spawn_local(async move {
+                        if let Err(err) = discard_file(file_id).await {
+                            logging::warn!("could not discard file {}: {}", file_id, err);
+                        }
+                    });
+                };
+
+                let swap = move |i: usize, j: usize| {
+                    update_files(&|files| files.swap(i, j));
+                };
+
+                let render_label = label.clone();
 
                 view! {
-                    <label class="button icon-button" for=qs.to_string()>
-                        <input id=qs.to_string() type="file" class="sr-hidden" on:input=on_input disabled=cfg!(feature = "csr") />
-                        <Icon label="Upload" icon="upload" />
-                    </label>
-                    <ul>
-                        <For
-                            each=move || file_info.get().into_iter().enumerate()
-                            key=|(_, (file_id, _))| file_id.clone()
-                            // renders each item to a view
-                            children=move |(i, (file_id, file_info))| {
-                                let qs = qs.add_index(i);
-        
-                                view! {
-                                    <li>
-                                        {format!("{}", file_info.file_name)}
-                                        <input type="hidden" name=qs value=file_id.to_string() />
-                                    </li>
+                    <div
+                        class="field file-upload"
+                        class:error=move || error.get().is_some()
+                        class:ok=move || error.get().is_none()
+                    >
+                        <Suspense>
+                            {
+                                let label = render_label.clone();
+                                let rendered_attachments = rendered_attachments.clone();
+                                move || if nova_form_context.is_render_mode() {
+                                    let files = files.get();
+                                    if let Some(rendered_attachments) = &rendered_attachments {
+                                        rendered_attachments.add(&files);
+                                    }
+                                    view! {
+                                        <span class="label">{label.clone()}</span>
+                                        <ul class="value attachments">
+                                            {files.into_iter().map(|(_, file_info)| view! {
+                                                <li class="attachment">
+                                                    <span class="attachment-name">{file_info.file_name().to_owned()}</span>
+                                                    <span class="attachment-details">{attachment_details(&file_info)}</span>
+                                                </li>
+                                            }).collect_view()}
+                                        </ul>
+                                    }.into_view()
+                                } else {
+                                    View::default()
                                 }
                             }
-                        />
-                    </ul>
+                        </Suspense>
+                        <div
+                            class="file-upload-drop-zone"
+                            class:hidden=move || nova_form_context.is_render_mode()
+                            class:dragging=move || dragging.get()
+                            on:dragover=move |ev| {
+                                // Prevent the browser from opening the file, so that it can be dropped here.
+                                ev.prevent_default();
+                                set_dragging.set(true);
+                            }
+                            on:dragleave=move |_| set_dragging.set(false)
+                            on:drop=on_drop
+                        >
+                            {label.clone().map(|label| view! { <label for=qs.to_string()>{label}</label> })}
+                            <label class="button icon-button" for=qs.to_string()>
+                                <input
+                                    id=qs.to_string()
+                                    type="file"
+                                    class="sr-hidden"
+                                    accept=accept.clone()
+                                    multiple=multiple
+                                    on:input=on_input
+                                    disabled=cfg!(feature = "csr")
+                                />
+                                <Icon label="Upload" icon="upload" />
+                            </label>
+                            <ul class="file-upload-files">
+                                <For
+                                    each=move || files.get().into_iter().enumerate()
+                                    key=|(i, (file_id, _))| (*i, *file_id)
+                                    // renders each item to a view
+                                    children=move |(i, (file_id, file_info))| {
+                                        let qs = qs.add_index(i);
+                                        let is_last = Signal::derive(move || i + 1 >= files.get().len());
+
+                                        view! {
+                                            <li class="file-upload-file">
+                                                <FilePreview file_id=file_id file_info=file_info.clone() />
+                                                <span class="file-upload-file-name">{file_info.file_name().to_owned()}</span>
+                                                <input type="hidden" name=qs value=file_id.to_string() />
+                                                <ButtonGroup>
+                                                    <Button
+                                                        label="Move up"
+                                                        icon="arrow_upward"
+                                                        disabled=i == 0
+                                                        on:click=move |_| swap(i - 1, i)
+                                                    />
+                                                    <Button
+                                                        label="Move down"
+                                                        icon="arrow_downward"
+                                                        disabled=is_last
+                                                        on:click=move |_| swap(i, i + 1)
+                                                    />
+                                                    <Button
+                                                        label="Remove"
+                                                        icon="delete"
+                                                        on:click=move |_| remove(file_id)
+                                                    />
+                                                </ButtonGroup>
+                                            </li>
+                                        }
+                                    }
+                                />
+                                <For
+                                    each=move || uploads.get()
+                                    key=|upload| upload.id
+                                    children=move |upload| {
+                                        let id = upload.id;
+                                        let progress = upload.progress;
+                                        let failed = upload.failed;
+
+                                        view! {
+                                            <li class="file-upload-file pending" class:failed=move || failed.get()>
+                                                <span class="file-upload-file-name">{upload.file.name()}</span>
+                                                <progress class="file-upload-progress" max="1" value=move || progress.get() />
+                                                {move || {
+                                                    let upload = upload.clone();
+                                                    failed.get().then(|| view! {
+                                                        <ButtonGroup>
+                                                            <Button
+                                                                label="Retry"
+                                                                icon="refresh"
+                                                                on:click=move |_| retry(upload.clone())
+                                                            />
+                                                            <Button
+                                                                label="Remove"
+                                                                icon="delete"
+                                                                on:click=move |_| cancel(id)
+                                                            />
+                                                        </ButtonGroup>
+                                                    })
+                                                }}
+                                            </li>
+                                        }
+                                    }
+                                />
+                            </ul>
+                            {move || {
+                                if let Some(error) = error.get() {
+                                    view! { <span class="error-message">{use_translation(error)}</span> }
+                                        .into_view()
+                                } else {
+                                    View::default()
+                                }
+                            }}
+                        </div>
+                    </div>
                 }
             }
         </Group>
-        
+
+    }
+}
+
+/// Renders a thumbnail of an uploaded image or the first page of an uploaded PDF.
+/// The file is loaded from `FileStore::serve_file`, see `FILES_PATH`.
+#[component]
+fn FilePreview(file_id: FileId, file_info: FileInfo) -> impl IntoView {
+    let content_type = file_info.content_type().unwrap_or_default();
+    let url = expect_context::<AppContext>().file_url(file_id, true);
+
+    if content_type == "application/pdf" {
+        view! {
+            <object class="file-upload-thumbnail" data=format!("{url}#page=1&toolbar=0&navpanes=0") type="application/pdf"></object>
+        }.into_view()
+    } else if content_type.starts_with("image/") {
+        view! {
+            <img class="file-upload-thumbnail" src=url alt=file_info.file_name().to_owned() />
+        }.into_view()
+    } else {
+        View::default()
+    }
+}
+
+/// A file that is being uploaded or whose upload failed.
+#[derive(Clone)]
+struct PendingUpload {
+    id: usize,
+    file: web_sys::File,
+    /// The fraction of the file that was sent to the server, between 0 and 1.
+    progress: RwSignal<f64>,
+    failed: RwSignal<bool>,
+}
+
+fn file_list_to_vec(file_list: &FileList) -> Vec<web_sys::File> {
+    (0..file_list.length())
+        .filter_map(|i| file_list.get(i))
+        .collect()
+}
+
+/// Sends the form data to the `upload_file` server function.
+/// Unlike calling the server function directly, this reports the upload progress as a fraction between 0 and 1.
+async fn send_upload(
+    form_data: WebFormData,
+    on_progress: impl Fn(f64) + 'static,
+) -> Result<Result<Files, UploadError>, ServerFnError> {
+    let js_error = |err: JsValue| -> ServerFnError { ServerFnError::Request(format!("{err:?}")) };
+
+    let xhr = XmlHttpRequest::new().map_err(js_error)?;
+    xhr.open("POST", <UploadFile as ServerFn>::PATH).map_err(js_error)?;
+    // Without this, the server would treat the request as a plain form submission and redirect.
+    xhr.set_request_header("Accept", "application/json").map_err(js_error)?;
+
+    let on_progress = Closure::<dyn Fn(ProgressEvent)>::new(move |ev: ProgressEvent| {
+        if ev.length_computable() && ev.total() > 0.0 {
+            on_progress(ev.loaded() / ev.total());
+        }
+    });
+    xhr.upload()
+        .map_err(js_error)?
+        .set_onprogress(Some(on_progress.as_ref().unchecked_ref()));
+
+    // `loadend` is fired once the request completed, failed or was aborted.
+    let (done, on_done) = futures::channel::oneshot::channel::<()>();
+    let on_load_end = Closure::once(move || {
+        let _ = done.send(());
+    });
+    xhr.set_onloadend(Some(on_load_end.as_ref().unchecked_ref()));
+
+    xhr.send_with_opt_form_data(Some(&form_data)).map_err(js_error)?;
+    if on_done.await.is_err() {
+        return Err(ServerFnError::Request("the upload was interrupted".to_owned()));
+    }
+
+    let status = xhr.status().map_err(js_error)?;
+    let body = xhr.response_text().map_err(js_error)?.unwrap_or_default();
+
+    match status {
+        0 => Err(ServerFnError::Request("the server could not be reached".to_owned())),
+        200..=299 => serde_json::from_str(&body).map_err(|err| ServerFnError::Deserialization(err.to_string())),
+        _ => Err(<ServerFnError as ServerFnErrorSerde>::de(&body)),
+    }
+}
+
+/// Describes the type and size of an attachment, for example `PDF, 1.2 MB`.
+pub(crate) fn attachment_details(file_info: &FileInfo) -> String {
+    let file_type = file_info
+        .file_name()
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_uppercase())
+        .or_else(|| file_info.content_type().map(str::to_owned));
+
+    let size = file_info.size().map(|size| {
+        let size = size as f64;
+        if size >= 1_000_000.0 {
+            format!("{:.1} MB", size / 1_000_000.0)
+        } else if size >= 1_000.0 {
+            format!("{:.0} kB", size / 1_000.0)
+        } else {
+            format!("{size} B")
+        }
+    });
+
+    file_type.into_iter().chain(size).collect::<Vec<_>>().join(", ")
+}
+
+/// Restricts the number, size and type of files that can be uploaded with a `FileUpload`.
+///
+/// The limits of a `FileUpload` are checked in the browser, so that users get immediate feedback,
+/// and enforced by the server, which registers them when it renders the form.
+/// Uploads for fields that were not rendered by the server are rejected.
+/// You can additionally provide server-wide limits as a context to the server functions, which apply to every field.
+/// The limits count the files that were uploaded for the field but not submitted yet.
+/// The files are counted per owner, so limiting the number and total size of the files requires
+/// a `FileAccess` policy of the `FileStore` that identifies the uploader, for example `SessionAccess`;
+/// otherwise, uploads for fields with such limits are rejected.
+/// If neither the field nor the server-wide limits restrict the file size, files are limited to `UploadLimits::DEFAULT_MAX_FILE_SIZE`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadLimits {
+    max_files: Option<usize>,
+    max_file_size: Option<u64>,
+    max_total_size: Option<u64>,
+    #[serde(default)]
+    allowed_types: Vec<String>,
+}
+
+impl UploadLimits {
+    /// The maximum size of each file in bytes that the server accepts if no other limit is set.
+    pub const DEFAULT_MAX_FILE_SIZE: u64 = 25 * 1024 * 1024;
+
+    /// Creates limits that allow any number of files of any size and type.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of files.
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        self.max_files = Some(max_files);
+        self
+    }
+
+    /// Sets the maximum size of each file in bytes.
+    pub fn max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = Some(max_file_size);
+        self
+    }
+
+    /// Sets the maximum size of all files together in bytes.
+    pub fn max_total_size(mut self, max_total_size: u64) -> Self {
+        self.max_total_size = Some(max_total_size);
+        self
+    }
+
+    /// Allows a file type, either as a MIME type (`application/pdf`), a MIME type wildcard (`image/*`)
+    /// or a file extension (`.pdf`).
+    /// If no type is allowed explicitly, all types are allowed.
+    pub fn allow<S: Into<String>>(mut self, allowed_type: S) -> Self {
+        self.allowed_types.push(allowed_type.into());
+        self
+    }
+
+    /// The value of the `accept` attribute of the file input.
+    pub fn accept(&self) -> Option<String> {
+        if self.allowed_types.is_empty() {
+            None
+        } else {
+            Some(self.allowed_types.join(","))
+        }
+    }
+
+    /// Checks whether a file with the given name and content type is allowed.
+    pub fn allows_type(&self, file_name: &str, content_type: Option<&str>) -> bool {
+        if self.allowed_types.is_empty() {
+            return true;
+        }
+
+        let file_name = file_name.to_lowercase();
+        self.allowed_types.iter().any(|allowed_type| {
+            let allowed_type = allowed_type.trim().to_lowercase();
+            if allowed_type.starts_with('.') {
+                file_name.ends_with(&allowed_type)
+            } else if let Some(prefix) = allowed_type.strip_suffix("/*") {
+                content_type.is_some_and(|t| t.split_once('/').is_some_and(|(t, _)| t.eq_ignore_ascii_case(prefix)))
+            } else {
+                content_type.is_some_and(|t| t.eq_ignore_ascii_case(&allowed_type))
+            }
+        })
+    }
+
+    /// Checks the number of files.
+    pub fn check_count(&self, count: usize) -> Result<(), UploadError> {
+        match self.max_files {
+            Some(max) if count > max => Err(UploadError::TooManyFiles { max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the size of a single file.
+    pub fn check_file_size(&self, file_name: &str, size: u64) -> Result<(), UploadError> {
+        match self.max_file_size {
+            Some(max) if size > max => Err(UploadError::FileTooLarge { file_name: file_name.to_owned(), max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the size and type of a single file.
+    pub fn check_file(&self, file_name: &str, content_type: Option<&str>, size: u64) -> Result<(), UploadError> {
+        self.check_file_size(file_name, size)?;
+        if !self.allows_type(file_name, content_type) {
+            return Err(UploadError::TypeNotAllowed { file_name: file_name.to_owned() });
+        }
+        Ok(())
     }
+
+    /// Checks the size of all files together.
+    pub fn check_total_size(&self, size: u64) -> Result<(), UploadError> {
+        match self.max_total_size {
+            Some(max) if size > max => Err(UploadError::TotalSizeTooLarge { max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the limits restrict the number or the total size of the files,
+    /// which can only be enforced if the uploader is known.
+    #[cfg(feature = "ssr")]
+    fn limits_pending_uploads(&self) -> bool {
+        self.max_files.is_some() || self.max_total_size.is_some()
+    }
+}
+
+/// The limits of the `FileUpload`s that were rendered on the server, keyed by `field_key`.
+#[cfg(feature = "ssr")]
+static UPLOAD_FIELDS: Mutex<Option<std::collections::HashMap<String, UploadLimits>>> = Mutex::new(None);
+
+/// Registers the limits of a `FileUpload`, so that `upload_file` can enforce them.
+#[cfg(feature = "ssr")]
+fn register_upload_field(qs: &crate::QueryString, limits: &UploadLimits) {
+    UPLOAD_FIELDS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(Default::default)
+        .insert(field_key(qs), limits.clone());
+}
+
+/// The limits of the `FileUpload` bound to the given field, if it was rendered on the server.
+#[cfg(feature = "ssr")]
+fn registered_limits(field: &str) -> Option<UploadLimits> {
+    UPLOAD_FIELDS.lock().unwrap().as_ref()?.get(&field_key(&crate::QueryString::from(field))).cloned()
+}
+
+/// Identifies the `FileUpload` of a field regardless of the indices of the repeatable groups it is rendered in,
+/// as groups can be added in the browser, for example `items[][attachments]` for `items[2][attachments]`.
+#[cfg(feature = "ssr")]
+fn field_key(qs: &crate::QueryString) -> String {
+    qs.iter()
+        .map(|part| match part {
+            QueryStringPart::Index(_) => "[]".to_owned(),
+            QueryStringPart::Key(key) => format!("[{key}]"),
+        })
+        .collect()
+}
+
+/// The error type for the `FileUpload` component.
+/// This error is returned when an upload violates the `UploadLimits` and can be used to display an error message by providing a custom translation.
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum UploadError {
+    #[error("at most {max} files can be uploaded")]
+    TooManyFiles { max: usize },
+    #[error("{file_name} exceeds the maximum file size of {max} bytes")]
+    FileTooLarge { file_name: String, max: u64 },
+    #[error("the files exceed the maximum total size of {max} bytes")]
+    TotalSizeTooLarge { max: u64 },
+    #[error("the file type of {file_name} is not allowed")]
+    TypeNotAllowed { file_name: String },
+    #[error("the content of {file_name} does not match its file type")]
+    ContentMismatch { file_name: String },
+    #[error("{file_name} could not be uploaded, please try again")]
+    UploadFailed { file_name: String },
+    #[error("{file_name} was rejected because it may contain malware")]
+    Rejected { file_name: String },
 }
 
 /// A unique identifier for a file.
@@ -117,13 +703,21 @@ impl<'de> Deserialize<'de> for FileId {
     where
         D: serde::Deserializer<'de>,
     {
-        let mut value = String::deserialize(deserializer)?;
-        if !value.starts_with("file_id_") {
-            return Err(D::Error::custom("prefix mismatch"));
-        }
-        match Uuid::from_str(&value.split_off(8)) {
+        let value = String::deserialize(deserializer)?;
+        FileId::from_str(&value).map_err(D::Error::custom)
+    }
+}
+
+impl FromStr for FileId {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(uuid) = s.strip_prefix("file_id_") else {
+            return Err("prefix mismatch");
+        };
+        match Uuid::from_str(uuid) {
             Ok(uuid) => Ok(FileId(uuid)),
-            Err(_) => Err(D::Error::custom("invalid uuid")),
+            Err(_) => Err("invalid uuid"),
         }
     }
 }
@@ -133,6 +727,8 @@ impl<'de> Deserialize<'de> for FileId {
 pub struct FileInfo {
     file_name: String,
     content_type: Option<String>,
+    #[serde(default)]
+    size: Option<u64>,
 }
 
 impl FileInfo {
@@ -140,51 +736,240 @@ impl FileInfo {
         FileInfo {
             file_name,
             content_type,
+            size: None,
         }
     }
 
+    /// Sets the size of the file in bytes.
+    pub fn with_size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
     pub fn file_name(&self) -> &str {
         &self.file_name
     }
 
     pub fn content_type(&self) -> Option<&str> {
-        self.content_type.as_ref().map(|s| s.as_str())
+        self.content_type.as_deref()
+    }
+
+    /// The size of the file in bytes, if known.
+    pub fn size(&self) -> Option<u64> {
+        self.size
     }
 }
 
 #[server(input = MultipartFormData)]
-async fn upload_file(data: MultipartData) -> Result<Vec<(FileId, FileInfo)>, ServerFnError> {
-    use crate::FileStore;
+async fn upload_file(data: MultipartData) -> Result<Result<Vec<(FileId, FileInfo)>, UploadError>, ServerFnError> {
+    use crate::{verify_content_type, FileStore, FileStoreError};
 
     let mut data = data.into_inner().unwrap();
-    let mut file_infos = Vec::new();
 
-    while let Ok(Some(mut field)) = data.next_field().await {
-        let content_type = field.content_type().map(|mime| mime.to_string());
-        let file_name = field.file_name().expect("no filename on field").to_string();
-        let file_info = FileInfo::new(file_name, content_type);
+    // The limits sent by the browser are never trusted, only the limits that were registered when rendering the field.
+    let server_limits = use_context::<UploadLimits>().unwrap_or_default();
+
+    let file_store = expect_context::<FileStore>();
+    let owner = use_context::<http::request::Parts>().and_then(|request| file_store.access().owner(&request));
+    let mut field = None::<(String, [UploadLimits; 2])>;
+    let mut file_infos: Vec<(FileId, FileInfo)> = Vec::new();
+
+    while let Some(mut field_data) = data.next_field().await? {
+        let Some(file_name) = field_data.file_name().map(str::to_owned) else {
+            if field_data.name() == Some("field") && field.is_none() {
+                let name = field_data.text().await?;
+                let Some(mut limits) = registered_limits(&name) else {
+                    return Err(ServerFnError::ServerError(format!("no file upload was rendered for the field {name}")));
+                };
+                if limits.max_file_size.is_none() && server_limits.max_file_size.is_none() {
+                    limits.max_file_size = Some(UploadLimits::DEFAULT_MAX_FILE_SIZE);
+                }
+                if owner.is_none() && (limits.limits_pending_uploads() || server_limits.limits_pending_uploads()) {
+                    return Err(ServerFnError::ServerError(format!(
+                        "the number and total size of the files of the field {name} cannot be limited without knowing the uploader, \
+                        use a FileAccess policy such as SessionAccess"
+                    )));
+                }
+                field = Some((name, [limits, server_limits.clone()]));
+            }
+            continue;
+        };
+        let Some((field, limits)) = &field else {
+            return Err(ServerFnError::ServerError("the field must be sent before the files".to_owned()));
+        };
+        let declared_content_type = field_data.content_type().map(|mime| mime.to_string());
 
         let mut data = Vec::new();
 
-        while let Ok(Some(chunk)) = field.chunk().await {
+        while let Some(chunk) = field_data.chunk().await? {
             data.extend_from_slice(&chunk);
-            //let len = chunk.len();
-            //println!("[{file_name}]\t{len}");
-            //progress::add_chunk(&name, len).await;
-            // in a real server function, you'd do something like saving the file here
+            // Abort early instead of buffering oversized files.
+            if let Err(err) = limits.iter().try_for_each(|limits| limits.check_file_size(&file_name, data.len() as u64)) {
+                return discard_uploads(&file_store, file_infos, err).await;
+            }
         }
 
-        let file_store = expect_context::<FileStore>();
-        let file_id = file_store.insert(file_info.clone(), data).await?;
+        // Never trust the content type supplied by the browser, check the actual contents instead.
+        let content_type = match verify_content_type(&file_name, declared_content_type.as_deref(), &data) {
+            Ok(content_type) => content_type,
+            Err(err) => return discard_uploads(&file_store, file_infos, err).await,
+        };
 
-        println!(
-            "inserted file {} into database with id {}",
-            file_info.file_name(),
-            file_id
-        );
+        let file_info = FileInfo::new(file_name, content_type).with_size(data.len() as u64);
+        if let Err(err) = limits
+            .iter()
+            .try_for_each(|limits| limits.check_file(file_info.file_name(), file_info.content_type(), data.len() as u64))
+        {
+            return discard_uploads(&file_store, file_infos, err).await;
+        }
 
+        let file_id = match file_store.insert_upload(file_info.clone(), data, owner.clone(), Some(field.clone())).await {
+            Ok(file_id) => file_id,
+            Err(FileStoreError::Quarantined { reason, .. }) => {
+                leptos::logging::warn!("quarantined file {}: {}", file_info.file_name(), reason);
+                let err = UploadError::Rejected { file_name: file_info.file_name().to_owned() };
+                return discard_uploads(&file_store, file_infos, err).await;
+            }
+            Err(err) => return Err(err.into()),
+        };
         file_infos.push((file_id, file_info));
+
+        // Each file is uploaded with its own request, so the files that were uploaded for the field before are counted as well.
+        // They are counted after inserting the file, so that concurrent uploads cannot exceed the limits together.
+        // Without an owner, the field has no such limits, see above.
+        if let Some(owner) = &owner {
+            let (count, total_size) = file_store.pending_uploads(owner, field).await?;
+            if let Err(err) = limits
+                .iter()
+                .try_for_each(|limits| limits.check_count(count).and_then(|_| limits.check_total_size(total_size)))
+            {
+                return discard_uploads(&file_store, file_infos, err).await;
+            }
+        }
+    }
+
+    Ok(Ok(file_infos))
+}
+
+/// Discards the files of an upload that was rejected.
+#[cfg(feature = "ssr")]
+async fn discard_uploads(
+    file_store: &crate::FileStore,
+    file_infos: Vec<(FileId, FileInfo)>,
+    err: UploadError,
+) -> Result<Result<Vec<(FileId, FileInfo)>, UploadError>, ServerFnError> {
+    for (file_id, _) in file_infos {
+        file_store.discard(file_id).await?;
+    }
+    Ok(Err(err))
+}
+
+/// Loads the metadata of files that were already uploaded.
+/// Files that the `FileAccess` policy of the `FileStore` does not allow to read are skipped,
+/// unless the form is rendered as a PDF on the server.
+#[server]
+async fn file_infos(file_ids: Vec<FileId>) -> Result<Vec<(FileId, FileInfo)>, ServerFnError> {
+    use crate::FileStore;
+
+    let file_store = expect_context::<FileStore>();
+    // Only `PdfGen` renders forms without a request, and it collects the rendered attachments.
+    let request = use_context::<http::request::Parts>();
+    if request.is_none() && use_context::<RenderedAttachments>().is_none() {
+        return Ok(Vec::new());
+    }
+    let mut file_infos = Vec::new();
+
+    for file_id in file_ids {
+        if let Some(request) = &request {
+            if !file_store.can_read(request, file_id).await? {
+                continue;
+            }
+        }
+        if let Some(file_info) = file_store.info(file_id).await? {
+            file_infos.push((file_id, file_info));
+        }
     }
 
     Ok(file_infos)
 }
+
+/// Discards a file that was removed from the form before it was submitted.
+/// Only the owner of the file may discard it, see `FileAccess`.
+#[server]
+async fn discard_file(file_id: FileId) -> Result<(), ServerFnError> {
+    use crate::FileStore;
+
+    let file_store = expect_context::<FileStore>();
+    let Some(request) = use_context::<http::request::Parts>() else {
+        return Ok(());
+    };
+    if !file_store.is_owner(&request, file_id).await? {
+        return Ok(());
+    }
+    file_store.discard(file_id).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_details() {
+        let file_info = FileInfo::new("scan.pdf".to_owned(), Some("application/pdf".to_owned()));
+        assert_eq!(attachment_details(&file_info), "PDF");
+        assert_eq!(attachment_details(&file_info.clone().with_size(1_234_567)), "PDF, 1.2 MB");
+        assert_eq!(attachment_details(&file_info.with_size(2_400)), "PDF, 2 kB");
+
+        let file_info = FileInfo::new("photo".to_owned(), Some("image/jpeg".to_owned())).with_size(512);
+        assert_eq!(attachment_details(&file_info), "image/jpeg, 512 B");
+    }
+
+    #[test]
+    fn test_upload_limits_types() {
+        let limits = UploadLimits::new().allow("image/*").allow(".pdf");
+
+        assert!(limits.allows_type("scan.PDF", None));
+        assert!(limits.allows_type("photo.jpg", Some("image/jpeg")));
+        assert!(!limits.allows_type("photo.jpg", None));
+        assert!(!limits.allows_type("notes.txt", Some("text/plain")));
+        assert!(UploadLimits::new().allows_type("notes.txt", None));
+        assert_eq!(limits.accept(), Some("image/*,.pdf".to_owned()));
+    }
+
+    #[test]
+    fn test_upload_limits_sizes() {
+        let limits = UploadLimits::new().max_files(2).max_file_size(10).max_total_size(15);
+
+        assert_eq!(limits.check_count(2), Ok(()));
+        assert_eq!(limits.check_count(3), Err(UploadError::TooManyFiles { max: 2 }));
+        assert_eq!(limits.check_file("a.txt", None, 10), Ok(()));
+        assert_eq!(
+            limits.check_file("a.txt", None, 11),
+            Err(UploadError::FileTooLarge { file_name: "a.txt".to_owned(), max: 10 })
+        );
+        assert_eq!(limits.check_total_size(16), Err(UploadError::TotalSizeTooLarge { max: 15 }));
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_registered_limits() {
+        let limits = UploadLimits::new().max_files(1).allow("image/*");
+        register_upload_field(&"registry_test[items][0][photos]".into(), &limits);
+
+        // Groups that were added in the browser use the limits of the rendered group.
+        assert_eq!(registered_limits("registry_test[items][3][photos]"), Some(limits));
+        assert_eq!(registered_limits("registry_test[items][3][other]"), None);
+        assert_eq!(registered_limits("registry_test[photos]"), None);
+    }
+
+    #[test]
+    fn test_upload_limits_serialization() {
+        let limits = UploadLimits::new().max_files(2).allow("image/*").allow(".pdf");
+        let encoded = serde_qs::to_string(&limits).unwrap();
+
+        assert_eq!(serde_qs::from_str::<UploadLimits>(&encoded).unwrap(), limits);
+        assert_eq!(serde_qs::from_str::<UploadLimits>("").unwrap(), UploadLimits::new());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Datatype, QueryStringPart, FieldWiring};
+use crate::{use_region, with_region, Datatype, QueryStringPart, FieldWiring};
 use leptos::*;
 
 /// A component that renders an input field.
@@ -23,6 +23,7 @@ where
 {    
     let FieldWiring {
         qs,
+        value: parsed_value,
         raw_value,
         error,
         set_raw_value,
@@ -41,11 +42,15 @@ where
         }
     });
 
+    // Datatypes such as `Decimal` submit a normalized value in a hidden input,
+    // so that the server can deserialize it without knowing the region.
+    let submitted_value = move || parsed_value.get().ok().and_then(|value| value.submit_value());
+
     let input_elem = T::attributes()
         .into_iter()
         .fold(html::input(), |el, (name, value)| el.attr(name, value))
         .attr("id", qs.to_string())
-        .attr("name", qs.to_string())
+        .attr("name", move || submitted_value().is_none().then(|| qs.to_string()))
         .attr("placeholder", placeholder.as_ref().map(T::to_string))
         .prop("value", move || raw_value.get())
         .prop("disabled", move || disabled.get())
@@ -54,6 +59,16 @@ where
             set_raw_value.call(event_target_value(&ev));
         });
 
+    // Datatypes such as `Money` are shown formatted for the region in render mode.
+    let region = use_region();
+    let rendered_value = move || {
+        parsed_value
+            .get()
+            .ok()
+            .and_then(|value| with_region(region.get(), || value.render()))
+            .unwrap_or_else(|| raw_value.get())
+    };
+
     view! {
         <div
             class="field"
@@ -64,12 +79,15 @@ where
                 if render_mode.get() {
                     view!{
                         <span class="label">{label.clone()}</span>
-                        <span class="value">{raw_value.get()}</span>
+                        <span class="value">{rendered_value()}</span>
                     }.into_view()
                 } else {
                     view! {
                         <label for=qs.to_string()>{label.clone()}</label>
                         {input_elem.clone()}
+                        {move || submitted_value().map(|value| view! {
+                            <input type="hidden" name=qs.to_string() value=value />
+                        })}
                         {move || {
                             if let Some(error) = error.get() {
                                 view! { <span class="error-message">{error}</span> }
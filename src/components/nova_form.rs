@@ -10,11 +10,11 @@ use server_fn::{
 use strum::Display;
 use time::UtcOffset;
 use ustr::Ustr;
-use std::{fmt::Debug, marker::PhantomData, path::{Path, PathBuf}, str::FromStr};
+use std::{fmt::Debug, marker::PhantomData, path::{Path, PathBuf}, str::FromStr, sync::{Arc, Mutex}};
 use thiserror::Error;
 
 use crate::{
-    local_utc_offset, qs, use_translation, BaseGroupContext, Data, DialogKind, FormData, Group, Modal, QueryString, QueryStringPart, APP_CSS, PRINT_CSS, VARIABLES_CSS
+    local_utc_offset, qs, use_translation, BaseGroupContext, Country, Data, DialogKind, FileId, FormData, Group, Modal, QueryString, QueryStringPart, RegionContext, APP_CSS, PRINT_CSS, VARIABLES_CSS
 };
 
 /// Can be used to provide custom translations.
@@ -35,6 +35,8 @@ pub enum SubmitError {
     ValidationError,
     #[error("the form contains errors")]
     ParseError,
+    #[error("files are still being uploaded")]
+    UploadPending,
     #[error("a server error occurred: {0}")]
     ServerError(ServerFnError),
 }
@@ -76,6 +78,26 @@ impl RenderContext {
     pub fn meta_data(&self) -> &MetaData {
         &self.meta_data
     }
+
+    /// Provides this render context to the form.
+    /// When rendering to PDF, this also identifies the rendered form for the `PdfCache`.
+    pub fn provide(self) {
+        if let Some(rendered_context) = use_context::<RenderedContext>() {
+            *rendered_context.0.lock().unwrap() = Some(self.clone());
+        }
+        provide_context(self);
+    }
+}
+
+/// Collects the `RenderContext` while a form is rendered to PDF, see `PdfCache`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RenderedContext(Arc<Mutex<Option<RenderContext>>>);
+
+impl RenderedContext {
+    #[cfg(feature = "ssr")]
+    pub(crate) fn take(&self) -> Option<RenderContext> {
+        self.0.lock().unwrap().take()
+    }
 }
 
 /// The base context provides general information about the environment.
@@ -105,8 +127,53 @@ impl AppContext {
             format!("{}", self.base_url.join(path).display())
         }
     }
+
+    /// The URL of an uploaded file served by `FileStore::serve_file`, see `FILES_PATH`.
+    /// Images and PDFs are shown in the browser instead of being downloaded if `inline` is set.
+    pub fn file_url(&self, file_id: FileId, inline: bool) -> String {
+        let url = self.resolve_path(format!("{FILES_PATH}/{file_id}"));
+        if inline {
+            format!("{url}?inline")
+        } else {
+            url
+        }
+    }
+
+    /// The URL of the paged.js polyfill used by the preview.
+    /// Served from the site root, see `install_assets`, unless the `cdn` feature is enabled.
+    pub fn paged_js_url(&self) -> String {
+        if cfg!(feature = "cdn") {
+            PAGED_JS_CDN_URL.to_owned()
+        } else {
+            self.resolve_path(PAGED_JS_PATH)
+        }
+    }
+
+    /// The URL of the stylesheet that loads the icon font.
+    /// Served from the site root, see `install_assets`, unless the `cdn` feature is enabled.
+    pub fn icons_css_url(&self) -> String {
+        if cfg!(feature = "cdn") {
+            ICONS_CSS_CDN_URL.to_owned()
+        } else {
+            self.resolve_path(ICONS_CSS_PATH)
+        }
+    }
 }
 
+/// The path under which `FileStore::serve_file` must be mounted, followed by the `FileId`, for example `/files/:id`.
+/// `FileUpload` loads the previews of uploaded files from there.
+pub const FILES_PATH: &str = "files";
+
+/// The path of the paged.js polyfill, relative to the site root.
+pub const PAGED_JS_PATH: &str = "pkg/nova-forms/paged.polyfill.js";
+/// The path of the icon font stylesheet, relative to the site root.
+pub const ICONS_CSS_PATH: &str = "pkg/nova-forms/material-symbols-rounded.css";
+/// The path of the icon font, relative to the site root.
+pub const ICONS_FONT_PATH: &str = "pkg/nova-forms/material-symbols-rounded.woff2";
+
+const PAGED_JS_CDN_URL: &str = "https://unpkg.com/pagedjs/dist/paged.polyfill.js";
+const ICONS_CSS_CDN_URL: &str = "https://fonts.googleapis.com/css2?family=Material+Symbols+Rounded:opsz,wght,FILL,GRAD@24,400,1,0";
+
 #[test]
 fn test_base_context_resolve_path() {
     let base_context = AppContext::new(PathBuf::from("/"));
@@ -135,6 +202,7 @@ impl From<PathBuf> for SiteRoot {
 pub struct FormContext {
     form_id: Ustr,
     preview: RwSignal<bool>,
+    pending_uploads: RwSignal<usize>,
 }
 
 impl FormContext {
@@ -142,6 +210,7 @@ impl FormContext {
         Self {
             form_id: Ustr::from(form_id),
             preview: create_rw_signal(false),
+            pending_uploads: create_rw_signal(0),
         }
     }
 
@@ -168,6 +237,20 @@ impl FormContext {
     pub fn form_id(&self) -> &str {
         self.form_id.as_str()
     }
+
+    /// Registers an upload that has to complete before the form can be submitted.
+    pub fn begin_upload(&self) {
+        self.pending_uploads.update(|pending| *pending += 1);
+    }
+
+    /// Marks an upload registered with `begin_upload` as completed, whether it succeeded or not.
+    pub fn end_upload(&self) {
+        self.pending_uploads.update(|pending| *pending = pending.saturating_sub(1));
+    }
+
+    pub fn has_pending_uploads(&self) -> bool {
+        self.pending_uploads.get() > 0
+    }
 }
 
 /// Creates a new nova form.
@@ -219,10 +302,14 @@ where
     });
 
     provide_context(form_data);
+    // Validates regional datatypes for the region of the locale, see `RegionProvider`.
+    provide_context(RegionContext(Signal::derive(move || {
+        Country::from_locale(i18n.get_locale().as_ref())
+    })));
 
     let preview = create_rw_signal(false);
     let form_id = Ustr::from("nova-form");
-    let nova_form_context = FormContext { preview, form_id };
+    let nova_form_context = FormContext { preview, form_id, pending_uploads: create_rw_signal(0) };
     provide_context(nova_form_context);
 
     let (submit_state, set_submit_state) = create_signal(SubmitState::Initial);
@@ -281,6 +368,11 @@ where
                 return;
             }
 
+            if nova_form_context.has_pending_uploads() {
+                set_submit_state.set(SubmitState::Error(SubmitError::UploadPending));
+                return;
+            }
+
 
 
             match ServFn::from_event(&ev) {
@@ -373,6 +465,13 @@ pub struct MetaData {
     pub local_utc_offset: UtcOffset,
 }
 
+impl MetaData {
+    /// The region of the locale, which regional datatypes are validated for by default, see `validate_in_region`.
+    pub fn region(&self) -> Option<Country> {
+        Country::from_locale(&self.locale)
+    }
+}
+
 /// Initializes the Nova Forms `AppContextProvider` and `RenderContextProvider`.
 #[macro_export]
 macro_rules! init_nova_forms {
@@ -415,16 +514,9 @@ macro_rules! init_nova_forms {
                 // id=leptos means cargo-leptos will hot-reload this stylesheet.
                 // Preload the stylesheet to make sure it is loaded before the page is rendered.
                 <Link rel="preload" as_="style" href=base_context.resolve_path("pkg/app.css") />
-                <Link
-                    rel="preload"
-                    as_="style"
-                    href="https://fonts.googleapis.com/css2?family=Material+Symbols+Rounded:opsz,wght,FILL,GRAD@24,400,1,0"
-                />
+                <Link rel="preload" as_="style" href=base_context.icons_css_url() />
                 <Stylesheet id="leptos" href=base_context.resolve_path("pkg/app.css") />
-                <Link
-                    rel="stylesheet"
-                    href="https://fonts.googleapis.com/css2?family=Material+Symbols+Rounded:opsz,wght,FILL,GRAD@24,400,1,0"
-                />
+                <Link rel="stylesheet" href=base_context.icons_css_url() />
             }
         }
 
@@ -444,7 +536,10 @@ macro_rules! init_nova_forms {
             let locale = meta_data.locale.clone();
 
             // Adds the render context.
-            provide_context($crate::RenderContext::new(&form_data, meta_data));
+            $crate::RenderContext::new(&form_data, meta_data).provide();
+            $crate::update_document_metadata(|document_metadata| {
+                document_metadata.language.get_or_insert_with(|| locale.clone());
+            });
                         
             view! {
                 <AppContextProvider>
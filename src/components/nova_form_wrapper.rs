@@ -1,7 +1,7 @@
 use leptos::*;
 use leptos_meta::*;
 
-use crate::AppContext;
+use crate::{update_document_metadata, AppContext};
 
 /// A container for a form.
 /// Adds a header with a logo, title, and subtitle, as well as a footer with the title.
@@ -20,6 +20,9 @@ pub fn NovaFormWrapper(
     children: Children,
 ) -> impl IntoView {
     let base_context = expect_context::<AppContext>();
+    update_document_metadata(|document_metadata| {
+        document_metadata.title.get_or_insert_with(|| title.get().to_string());
+    });
 
     view! {
         <Title text={title.clone()} />
@@ -26,6 +26,7 @@ pub fn stop_preview(_form_id: &str) {
 #[component]
 pub fn Preview() -> impl IntoView {
     let nova_form_context = expect_context::<FormContext>();
+    let base_context = expect_context::<AppContext>();
 
     view! {
         <Script>r#"
@@ -90,7 +91,7 @@ pub fn Preview() -> impl IntoView {
             
             window.addEventListener("resize", resizePreview);
         "#</Script>
-        <Script src="https://unpkg.com/pagedjs/dist/paged.polyfill.js"></Script>
+        <Script src=base_context.paged_js_url()></Script>
 
         <div
             id="preview-wrapper"
@@ -1,4 +1,5 @@
 use leptos::*;
+use leptos_meta::Style;
 
 /// Only renders the children when printing to PDF.
 #[component]
@@ -26,4 +27,161 @@ where
             {children()}
         </div>
     }
-}
\ No newline at end of file
+}
+/// The horizontal position of a `PrintHeader` or `PrintFooter` in the page margin.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrintAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl PrintAlign {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PrintAlign::Left => "left",
+            PrintAlign::Center => "center",
+            PrintAlign::Right => "right",
+        }
+    }
+}
+
+/// Repeats the children in the top margin of every printed page,
+/// for example the title of the form or the reference of the submission.
+#[component]
+pub fn PrintHeader(
+    /// The position in the top margin, defaults to the left.
+    #[prop(optional)] align: PrintAlign,
+    children: Children,
+) -> impl IntoView {
+    running_element("top", align, children)
+}
+
+/// Repeats the children in the bottom margin of every printed page,
+/// for example `"Page "<PageNumber/>" of "<PageCount/>`.
+#[component]
+pub fn PrintFooter(
+    /// The position in the bottom margin, defaults to the left.
+    #[prop(optional)] align: PrintAlign,
+    children: Children,
+) -> impl IntoView {
+    running_element("bottom", align, children)
+}
+
+/// Places the children into a margin box of the paged.js layout,
+/// replacing the default content of that margin box.
+fn running_element(side: &str, align: PrintAlign, children: Children) -> impl IntoView {
+    let name = format!("{side}-{}", align.as_str());
+    let css = format!(
+        ".print-running-{name} {{ position: running({name}); }} @page {{ @{name} {{ content: element({name}); }} }}"
+    );
+
+    view! {
+        <Style>{css}</Style>
+        <div class=format!("print-running print-running-{name}")>
+            {children()}
+        </div>
+    }
+}
+
+/// The number of the current page, when used in a `PrintHeader` or `PrintFooter`.
+#[component]
+pub fn PageNumber() -> impl IntoView {
+    view! { <span class="page-number"></span> }
+}
+
+/// The total number of pages, when used in a `PrintHeader` or `PrintFooter`.
+#[component]
+pub fn PageCount() -> impl IntoView {
+    view! { <span class="page-count"></span> }
+}
+
+/// Stamps a diagonal text on every printed page, for example `DRAFT`.
+#[component]
+pub fn Watermark(
+    #[prop(into)] text: String,
+    /// The color of the text, defaults to a light gray.
+    #[prop(optional, into)] color: Option<String>,
+) -> impl IntoView {
+    let color = color.unwrap_or_else(|| "rgba(0, 0, 0, 0.12)".to_owned());
+    view! { <Style>{watermark_css(&text, &color)}</Style> }
+}
+
+/// Creates the page background with the watermark as SVG image.
+fn watermark_css(text: &str, color: &str) -> String {
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+    let escape = |value: &str| value.replace('&', "&amp;").replace('<', "&lt;").replace('"', "&quot;");
+    // Shrinks long texts so that they fit on the diagonal of an A4 page.
+    let font_size = (400.0 / text.chars().count().max(1) as f64).min(50.0);
+    let svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 210 297"><text x="105" y="148.5" transform="rotate(-45 105 148.5)" text-anchor="middle" dominant-baseline="middle" font-family="sans-serif" font-weight="bold" font-size="{font_size:.1}" fill="{}">{}</text></svg>"#,
+        escape(color),
+        escape(text)
+    );
+
+    format!(
+        r#"@page {{ background-image: url("data:image/svg+xml,{}"); background-repeat: no-repeat; background-position: center; background-size: contain; }}"#,
+        utf8_percent_encode(&svg, NON_ALPHANUMERIC)
+    )
+}
+
+/// Metadata of the document that is embedded into generated PDFs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Vec<String>,
+    /// The language of the document, for example `de-CH`.
+    pub language: Option<String>,
+}
+
+/// Updates the metadata of the document while it is rendered to PDF.
+/// Does nothing when the form is rendered in the browser.
+pub fn update_document_metadata<F: FnOnce(&mut DocumentMetadata)>(f: F) {
+    if let Some(document_metadata) = use_context::<RwSignal<DocumentMetadata>>() {
+        document_metadata.update(f);
+    }
+}
+
+/// Sets metadata that is embedded into generated PDFs.
+/// By default, the title is taken from the `NovaFormWrapper` and the language from the `MetaData`.
+#[component]
+pub fn DocumentMeta(
+    #[prop(into, optional)] title: Option<String>,
+    #[prop(into, optional)] author: Option<String>,
+    #[prop(into, optional)] subject: Option<String>,
+    #[prop(into, optional)] keywords: Vec<String>,
+) -> impl IntoView {
+    update_document_metadata(|document_metadata| {
+        if title.is_some() {
+            document_metadata.title = title;
+        }
+        if author.is_some() {
+            document_metadata.author = author;
+        }
+        if subject.is_some() {
+            document_metadata.subject = subject;
+        }
+        if !keywords.is_empty() {
+            document_metadata.keywords = keywords;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watermark_css() {
+        let css = watermark_css("<DRAFT>", "red");
+
+        assert!(css.starts_with(r#"@page { background-image: url("data:image/svg+xml,%3Csvg"#));
+        assert!(css.contains("%26lt%3BDRAFT%3E%3C%2Ftext%3E"));
+        assert!(css.contains("font%2Dsize%3D%2250%2E0%22"));
+        assert!(!css[css.find(',').unwrap()..css.find("\")").unwrap()].contains(['<', '"', '#']));
+    }
+}
@@ -0,0 +1,52 @@
+use leptos::*;
+
+use crate::{Country, FormData, GroupContext, QueryStringPart};
+
+/// The region for which the regional datatypes of the inputs are validated.
+#[derive(Clone, Copy)]
+pub(crate) struct RegionContext(pub(crate) Signal<Option<Country>>);
+
+/// Returns the region of the closest `RegionProvider`.
+/// Within a `NovaForm`, this defaults to the region of the locale, if the locale has one.
+pub fn use_region() -> Signal<Option<Country>> {
+    match use_context::<RegionContext>() {
+        Some(region) => region.0,
+        None => Signal::derive(|| None),
+    }
+}
+
+/// Sets the region for which datatypes such as `RegionalField` and `Phone` are validated
+/// in the inputs within, for example the postal code and phone number of an address.
+/// The region is either given by `country` or taken from the input bound with `bind`,
+/// relative to the current group, for example a `Select` of country codes.
+/// Falls back to the region of the surrounding `RegionProvider` or of the form's locale.
+/// Server functions do not know the region, use `validate_in_region` to check the submitted values there.
+#[component]
+pub fn RegionProvider(
+    /// An explicit region.
+    #[prop(optional, into)] country: MaybeProp<Country>,
+    /// The sibling input that contains the ISO 3166 code of the region.
+    #[prop(optional, into)] bind: Option<QueryStringPart>,
+    children: Children,
+) -> impl IntoView {
+    let parent = use_region();
+    let bound = bind.map(|bind| {
+        let qs = expect_context::<GroupContext>().qs().add(bind);
+        let form_data = expect_context::<FormData>();
+        Signal::derive(move || {
+            form_data
+                .get(qs)
+                .get()
+                .and_then(|data| data.as_input()?.raw().parse::<Country>().ok())
+        })
+    });
+
+    provide_context(RegionContext(Signal::derive(move || {
+        country
+            .get()
+            .or_else(|| bound.and_then(|bound| bound.get()))
+            .or_else(|| parent.get())
+    })));
+
+    children()
+}
@@ -1,4 +1,5 @@
 mod email;
+mod country;
 mod non_empty_string;
 mod phone;
 mod date_time;
@@ -9,8 +10,13 @@ mod iban;
 mod postal_code;
 mod field;
 mod regional_field;
+mod national_id;
+mod tax_number;
+mod decimal;
+mod money;
 
 pub use email::*;
+pub use country::*;
 pub use non_empty_string::*;
 pub use phone::*;
 pub use date_time::*;
@@ -21,6 +27,10 @@ pub use iban::*;
 pub use postal_code::*;
 pub use field::*;
 pub use regional_field::*;
+pub use national_id::*;
+pub use tax_number::*;
+pub use decimal::*;
+pub use money::*;
 
 use num_bigint::BigInt;
 use num_rational::BigRational;
@@ -184,6 +194,19 @@ pub trait Datatype: Clone + Display + Debug + Default + FromStr<Err = Self::Erro
 
     /// Return the HTML attributes for the datatype that should be added to an input field.
     fn attributes() -> Vec<(&'static str, Attribute)>;
+
+    /// Return the text that is shown in render mode, or `None` to show the input as it was entered.
+    /// The region of the input is set while rendering, see `with_region`.
+    fn render(&self) -> Option<String> {
+        None
+    }
+
+    /// Return the value that is submitted with the form, or `None` to submit the input as it was entered.
+    /// Datatypes whose input depends on the region submit a format that can be deserialized without a region,
+    /// because server functions do not know the region of the form.
+    fn submit_value(&self) -> Option<String> {
+        None
+    }
 }
 
 // Defines custom translations for a type `T`.
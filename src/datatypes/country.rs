@@ -0,0 +1,163 @@
+use std::{cell::Cell, fmt::{self, Display}, str::FromStr};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+/// A country with built-in rules for regional datatypes, identified by its ISO 3166 code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Country {
+    AT,
+    CH,
+    DE,
+    FR,
+    GB,
+    IT,
+    LI,
+    US,
+}
+
+/// The error returned when parsing an unsupported country code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("unknown country")]
+pub struct UnknownCountry;
+
+impl Country {
+    pub const ALL: &'static [Country] = &[
+        Country::AT,
+        Country::CH,
+        Country::DE,
+        Country::FR,
+        Country::GB,
+        Country::IT,
+        Country::LI,
+        Country::US,
+    ];
+
+    /// The ISO 3166 alpha-2 code, for example `CH`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Country::AT => "AT",
+            Country::CH => "CH",
+            Country::DE => "DE",
+            Country::FR => "FR",
+            Country::GB => "GB",
+            Country::IT => "IT",
+            Country::LI => "LI",
+            Country::US => "US",
+        }
+    }
+
+    /// The character that separates the integer and the fractional part of numbers.
+    pub fn decimal_separator(&self) -> char {
+        match self {
+            Country::CH | Country::GB | Country::LI | Country::US => '.',
+            Country::AT | Country::DE | Country::FR | Country::IT => ',',
+        }
+    }
+
+    /// The character that groups the digits of large numbers by thousands.
+    pub fn group_separator(&self) -> char {
+        match self {
+            Country::CH | Country::LI => '\'',
+            Country::AT | Country::DE | Country::IT => '.',
+            // A narrow no-break space.
+            Country::FR => '\u{202f}',
+            Country::GB | Country::US => ',',
+        }
+    }
+
+    /// The country of a locale such as `de-CH`, if it has a region.
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        locale
+            .split(['-', '_'])
+            .skip(1)
+            .find_map(|part| Country::from_str(part).ok())
+    }
+}
+
+impl Display for Country {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl FromStr for Country {
+    type Err = UnknownCountry;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Country::ALL
+            .iter()
+            .find(|country| country.code().eq_ignore_ascii_case(s))
+            .copied()
+            // The ISO code of the United Kingdom is GB, but UK is widely used.
+            .or_else(|| s.eq_ignore_ascii_case("UK").then_some(Country::GB))
+            .ok_or(UnknownCountry)
+    }
+}
+
+/// A region that is fixed at compile time, used as default region of datatypes such as `Phone`.
+pub trait Region: 'static {
+    const COUNTRY: Country;
+}
+
+macro_rules! regions {
+    ( $( $name:ident => $country:ident ),* $(,)? ) => {
+        $(
+            #[doc = concat!("The region `", stringify!($country), "`.")]
+            pub struct $name;
+
+            impl Region for $name {
+                const COUNTRY: Country = Country::$country;
+            }
+        )*
+    };
+}
+
+regions! {
+    RegionAT => AT,
+    RegionCH => CH,
+    RegionDE => DE,
+    RegionFR => FR,
+    RegionGB => GB,
+    RegionIT => IT,
+    RegionLI => LI,
+    RegionUS => US,
+}
+
+thread_local! {
+    static CURRENT_REGION: Cell<Option<Country>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with the given region, which regional datatypes such as `RegionalField` and `Phone` validate against.
+/// Inputs set the region from the `RegionProvider` automatically,
+/// on the server, see `validate_in_region`.
+pub fn with_region<F: FnOnce() -> T, T>(region: Option<Country>, f: F) -> T {
+    let previous = CURRENT_REGION.with(|current| current.replace(region));
+    // Restores the previous region even if `f` panics.
+    struct Restore(Option<Country>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT_REGION.with(|current| current.set(self.0));
+        }
+    }
+    let _restore = Restore(previous);
+    f()
+}
+
+/// Validates the regional datatypes of an already deserialized value, such as `RegionalField` and `Phone`, for a region.
+/// Server functions deserialize their arguments without a region, so these datatypes accept the formats of all countries.
+/// On the server, use this to check the submitted data for the region the user selected,
+/// for example the region of the locale, see `MetaData::region`, or the value of the field bound by a `RegionProvider`:
+///
+/// ```ignore
+/// let address = validate_in_region(&form_data.address, Some(form_data.address.country))?;
+/// ```
+pub fn validate_in_region<T: Serialize + DeserializeOwned>(value: &T, region: Option<Country>) -> Result<T, serde_json::Error> {
+    let value = serde_json::to_value(value)?;
+    with_region(region, || serde_json::from_value(value))
+}
+
+/// The region set by `with_region`, if any.
+pub fn current_region() -> Option<Country> {
+    CURRENT_REGION.with(Cell::get)
+}
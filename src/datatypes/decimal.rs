@@ -0,0 +1,513 @@
+use std::{convert::Infallible, fmt::{self, Debug, Display}, marker::PhantomData, str::FromStr};
+
+use leptos::{Attribute, IntoAttribute};
+use rust_decimal::Decimal as Exact;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{current_region, Country, Datatype};
+
+/// The bounds of numeric datatypes such as `Decimal`, `Percentage` and `Money`.
+pub trait Bounds: 'static {
+    /// The smallest allowed value.
+    const MIN: Option<i64> = None;
+    /// The largest allowed value.
+    const MAX: Option<i64> = None;
+    /// The maximum number of decimal places.
+    /// For `Money`, the minor units of the currency apply as well.
+    const SCALE: Option<u32> = None;
+}
+
+/// Allows any value.
+pub struct Unbounded;
+
+impl Bounds for Unbounded {}
+
+/// Allows zero and positive values.
+pub struct NonNegative;
+
+impl Bounds for NonNegative {
+    const MIN: Option<i64> = Some(0);
+}
+
+/// The error type for the `Decimal` and `Percentage` datatypes.
+/// Can be used to display an error message by providing a custom translation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum DecimalError {
+    #[error("invalid format")]
+    InvalidFormat,
+    #[error("too small")]
+    TooSmall,
+    #[error("too large")]
+    TooLarge,
+    #[error("too many decimal places, at most {max} are allowed")]
+    TooManyDecimals { max: u32 },
+}
+
+impl From<Infallible> for DecimalError {
+    fn from(_: Infallible) -> Self {
+        unreachable!()
+    }
+}
+
+/// Parses a number as it is written in the given region, for example `1'234.50` or `1.234,50`.
+/// Spaces and apostrophes are always treated as group separators.
+/// A single `.` or `,` is the decimal separator, unless it is followed by exactly three digits
+/// and is the group separator of the region, as in `1.234` in Germany.
+pub(crate) fn parse_decimal(input: &str, region: Option<Country>) -> Result<Exact, DecimalError> {
+    let input = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '\'' | '’'))
+        .map(|c| if c == '−' { '-' } else { c })
+        .collect::<String>();
+    let (negative, digits) = match input.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, input.strip_prefix('+').unwrap_or(&input)),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit() || c == '.' || c == ',') {
+        return Err(DecimalError::InvalidFormat);
+    }
+
+    let decimal_separator = match (digits.rfind('.'), digits.rfind(',')) {
+        (Some(dot), Some(comma)) => Some(if dot > comma { '.' } else { ',' }),
+        (Some(index), None) | (None, Some(index)) => {
+            let separator = digits.as_bytes()[index] as char;
+            let grouping = digits.matches(separator).count() > 1
+                || (digits.len() - index - 1 == 3 && region.is_some_and(|region| region.group_separator() == separator));
+            (!grouping).then_some(separator)
+        }
+        (None, None) => None,
+    };
+
+    let (integer, fraction) = match decimal_separator {
+        Some(separator) => digits.rsplit_once(separator).unwrap(),
+        None => (digits, ""),
+    };
+    if decimal_separator.is_some_and(|separator| fraction.is_empty() || integer.contains(separator))
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(DecimalError::InvalidFormat);
+    }
+
+    // Digits are grouped by thousands.
+    let groups = integer.split(['.', ',']).collect::<Vec<_>>();
+    if groups.len() > 1
+        && (!(1..=3).contains(&groups[0].len()) || groups[1..].iter().any(|group| group.len() != 3))
+    {
+        return Err(DecimalError::InvalidFormat);
+    }
+
+    let integer = groups.concat();
+    let canonical = match (integer.as_str(), fraction) {
+        ("", "") => return Err(DecimalError::InvalidFormat),
+        ("", fraction) => format!("0.{fraction}"),
+        (integer, "") => integer.to_owned(),
+        (integer, fraction) => format!("{integer}.{fraction}"),
+    };
+    let value = Exact::from_str_exact(&canonical).map_err(|_| DecimalError::InvalidFormat)?;
+    Ok(if negative && !value.is_zero() { -value } else { value })
+}
+
+/// Parses a number in the canonical format, which is independent of the region,
+/// with an optional `-`, no grouping and `.` as decimal separator, for example `-1234.50`.
+pub(crate) fn parse_canonical(input: &str) -> Result<Exact, DecimalError> {
+    let digits = input.strip_prefix('-').unwrap_or(input);
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, "0"));
+    if integer.is_empty()
+        || fraction.is_empty()
+        || !integer.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(DecimalError::InvalidFormat);
+    }
+    Exact::from_str_exact(input).map_err(|_| DecimalError::InvalidFormat)
+}
+
+/// Formats a number as it is written in the given region, or without grouping if there is none.
+pub(crate) fn format_decimal(value: Exact, region: Option<Country>) -> String {
+    let Some(region) = region else {
+        return value.to_string();
+    };
+
+    let plain = value.abs().to_string();
+    let (integer, fraction) = plain.split_once('.').unwrap_or((&plain, ""));
+    let mut result = String::new();
+    if value.is_sign_negative() && !value.is_zero() {
+        result.push('-');
+    }
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            result.push(region.group_separator());
+        }
+        result.push(digit);
+    }
+    if !fraction.is_empty() {
+        result.push(region.decimal_separator());
+        result.push_str(fraction);
+    }
+    result
+}
+
+/// Checks a value against the bounds, allowing at most `scale` decimal places.
+pub(crate) fn check_bounds<B: Bounds>(value: Exact, scale: Option<u32>) -> Result<(), DecimalError> {
+    if B::MIN.is_some_and(|min| value < Exact::from(min)) {
+        return Err(DecimalError::TooSmall);
+    }
+    if B::MAX.is_some_and(|max| value > Exact::from(max)) {
+        return Err(DecimalError::TooLarge);
+    }
+    let max = match (B::SCALE, scale) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    if let Some(max) = max {
+        // Trailing zeros such as in `1.50` do not count.
+        if value.normalize().scale() > max {
+            return Err(DecimalError::TooManyDecimals { max });
+        }
+    }
+    Ok(())
+}
+
+/// The value closest to zero that lies within the bounds.
+pub(crate) fn default_value<B: Bounds>() -> Exact {
+    Exact::from(0.clamp(B::MIN.unwrap_or(i64::MIN), B::MAX.unwrap_or(i64::MAX)))
+}
+
+/// A datatype representing an exact decimal number, for example `1'234.50`.
+/// The input is parsed as it is written in the current region, see `RegionProvider`,
+/// and displayed in the same way.
+/// The number is submitted, serialized and deserialized in the canonical format, which is independent of the region,
+/// without grouping and with `.` as decimal separator, for example `1234.50`.
+pub struct Decimal<B: Bounds = Unbounded> {
+    value: Exact,
+    region: Option<Country>,
+    _bounds: PhantomData<B>,
+}
+
+impl<B: Bounds> Decimal<B> {
+    /// The exact value.
+    pub fn value(&self) -> Exact {
+        self.value
+    }
+
+    /// Parses a number in the canonical format, such as `1234.50`, independent of the region.
+    pub fn from_canonical(input: &str) -> Result<Self, DecimalError> {
+        let value = parse_canonical(input)?;
+        check_bounds::<B>(value, None)?;
+        Ok(Decimal {
+            value,
+            region: None,
+            _bounds: PhantomData,
+        })
+    }
+
+    fn format(&self, region: Option<Country>) -> String {
+        format_decimal(self.value, region)
+    }
+}
+
+impl<B: Bounds> Datatype for Decimal<B> {
+    type Inner = String;
+    type Error = DecimalError;
+
+    fn validate(input: String) -> Result<Self, DecimalError> {
+        let region = current_region();
+        let value = parse_decimal(&input, region)?;
+        check_bounds::<B>(value, None)?;
+        Ok(Decimal {
+            value,
+            region,
+            _bounds: PhantomData,
+        })
+    }
+
+    fn attributes() -> Vec<(&'static str, Attribute)> {
+        vec![
+            ("type", "text".into_attribute()),
+            ("inputmode", "decimal".into_attribute()),
+            ("required", Attribute::Bool(true)),
+        ]
+    }
+
+    fn render(&self) -> Option<String> {
+        Some(self.format(current_region().or(self.region)))
+    }
+
+    fn submit_value(&self) -> Option<String> {
+        Some(self.value.to_string())
+    }
+}
+
+impl<B: Bounds> Display for Decimal<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format(self.region))
+    }
+}
+
+impl<B: Bounds> Debug for Decimal<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Decimal({})", self.value)
+    }
+}
+
+impl<B: Bounds> Clone for Decimal<B> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value,
+            region: self.region,
+            _bounds: PhantomData,
+        }
+    }
+}
+
+impl<B: Bounds> PartialEq for Decimal<B> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<B: Bounds> Eq for Decimal<B> {}
+
+impl<B: Bounds> From<Decimal<B>> for String {
+    fn from(decimal: Decimal<B>) -> Self {
+        decimal.value.to_string()
+    }
+}
+
+impl<B: Bounds> Default for Decimal<B> {
+    fn default() -> Self {
+        Self {
+            value: default_value::<B>(),
+            region: None,
+            _bounds: PhantomData,
+        }
+    }
+}
+
+impl<B: Bounds> FromStr for Decimal<B> {
+    type Err = DecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::validate(s.to_owned())
+    }
+}
+
+impl<B: Bounds> Serialize for Decimal<B> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.value.to_string().serialize(serializer)
+    }
+}
+
+impl<'de, B: Bounds> Deserialize<'de> for Decimal<B> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        Self::from_canonical(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A datatype representing a percentage, for example `12.5 %`.
+/// The `%` sign is optional when entering the value.
+/// The value is stored in percent, use `Percentage::fraction` to get the fraction of one.
+pub struct Percentage<B: Bounds = Unbounded>(Decimal<B>);
+
+pub type PercentageError = DecimalError;
+
+impl<B: Bounds> Percentage<B> {
+    /// The value in percent, for example `12.5`.
+    pub fn value(&self) -> Exact {
+        self.0.value
+    }
+
+    /// The value as a fraction of one, for example `0.125`.
+    pub fn fraction(&self) -> Exact {
+        self.0.value / Exact::ONE_HUNDRED
+    }
+
+    /// Parses a percentage in the canonical format, such as `12.5`, independent of the region.
+    pub fn from_canonical(input: &str) -> Result<Self, DecimalError> {
+        Decimal::from_canonical(input).map(Percentage)
+    }
+
+    fn format(&self, region: Option<Country>) -> String {
+        match region {
+            Some(Country::GB | Country::US) | None => format!("{}%", self.0.format(region)),
+            // Most European regions separate the sign with a no-break space.
+            Some(_) => format!("{}\u{a0}%", self.0.format(region)),
+        }
+    }
+}
+
+impl<B: Bounds> Datatype for Percentage<B> {
+    type Inner = String;
+    type Error = DecimalError;
+
+    fn validate(input: String) -> Result<Self, DecimalError> {
+        let input = input.trim();
+        let input = input.strip_suffix('%').unwrap_or(input);
+        Decimal::validate(input.to_owned()).map(Percentage)
+    }
+
+    fn attributes() -> Vec<(&'static str, Attribute)> {
+        Decimal::<B>::attributes()
+    }
+
+    fn render(&self) -> Option<String> {
+        Some(self.format(current_region().or(self.0.region)))
+    }
+
+    fn submit_value(&self) -> Option<String> {
+        self.0.submit_value()
+    }
+}
+
+impl<B: Bounds> Display for Percentage<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format(self.0.region))
+    }
+}
+
+impl<B: Bounds> Debug for Percentage<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Percentage({})", self.0.value)
+    }
+}
+
+impl<B: Bounds> Clone for Percentage<B> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<B: Bounds> PartialEq for Percentage<B> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<B: Bounds> Eq for Percentage<B> {}
+
+impl<B: Bounds> From<Percentage<B>> for String {
+    fn from(percentage: Percentage<B>) -> Self {
+        percentage.0.into()
+    }
+}
+
+impl<B: Bounds> Default for Percentage<B> {
+    fn default() -> Self {
+        Self(Decimal::default())
+    }
+}
+
+impl<B: Bounds> FromStr for Percentage<B> {
+    type Err = DecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::validate(s.to_owned())
+    }
+}
+
+impl<B: Bounds> Serialize for Percentage<B> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, B: Bounds> Deserialize<'de> for Percentage<B> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Decimal::deserialize(deserializer).map(Percentage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::with_region;
+    use std::str::FromStr;
+
+    fn parse(input: &str, region: Option<Country>) -> Result<String, DecimalError> {
+        parse_decimal(input, region).map(|value| value.to_string())
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse("1'234.50", Some(Country::CH)), Ok("1234.50".to_owned()));
+        assert_eq!(parse("1.234,50", Some(Country::DE)), Ok("1234.50".to_owned()));
+        assert_eq!(parse("1 234,5", Some(Country::FR)), Ok("1234.5".to_owned()));
+        assert_eq!(parse("-1,234,567.89", Some(Country::US)), Ok("-1234567.89".to_owned()));
+        assert_eq!(parse(",5", None), Ok("0.5".to_owned()));
+
+        // A single separator followed by three digits depends on the region.
+        assert_eq!(parse("1.234", Some(Country::DE)), Ok("1234".to_owned()));
+        assert_eq!(parse("1.234", Some(Country::CH)), Ok("1.234".to_owned()));
+        assert_eq!(parse("1,234", Some(Country::DE)), Ok("1.234".to_owned()));
+        assert_eq!(parse("1.234", None), Ok("1.234".to_owned()));
+
+        for input in ["", "-", "1.2.3", "12.34,5", "1,234.5.6", "1.", "1e5", "12'34x"] {
+            assert_eq!(parse(input, Some(Country::DE)), Err(DecimalError::InvalidFormat), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_decimal() {
+        let decimal = with_region(Some(Country::CH), || Decimal::<Unbounded>::from_str("1234567.5")).unwrap();
+        assert_eq!(decimal.to_string(), "1'234'567.5");
+        assert_eq!(String::from(decimal), "1234567.5");
+
+        let decimal = with_region(Some(Country::DE), || Decimal::<Unbounded>::from_str("-1.234,50")).unwrap();
+        assert_eq!(decimal.to_string(), "-1.234,50");
+        assert_eq!(serde_json::to_string(&decimal).unwrap(), "\"-1234.50\"");
+        let deserialized: Decimal = serde_json::from_str("\"-1234.50\"").unwrap();
+        assert_eq!(deserialized, decimal);
+
+        assert_eq!(Decimal::<NonNegative>::from_str("-1"), Err(DecimalError::TooSmall));
+    }
+
+    #[test]
+    fn test_decimal_submission() {
+        // The value typed in a German form is submitted in the canonical format,
+        // which the server deserializes without a region.
+        let typed = with_region(Some(Country::DE), || Decimal::<Unbounded>::from_str("1.000")).unwrap();
+        let submitted = typed.submit_value().unwrap();
+        assert_eq!(submitted, "1000");
+        let deserialized: Decimal = serde_json::from_value(serde_json::Value::String(submitted)).unwrap();
+        assert_eq!(deserialized.value(), Exact::from(1000));
+
+        // Deserializing does not depend on the region and only accepts the canonical format.
+        let deserialized: Decimal = with_region(Some(Country::DE), || serde_json::from_str("\"1.234\"")).unwrap();
+        assert_eq!(deserialized.value().to_string(), "1.234");
+        for input in ["\"1'234.50\"", "\"1.234,5\"", "\"1_000\"", "\"+1\"", "\".5\""] {
+            assert!(serde_json::from_str::<Decimal>(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_percentage() {
+        struct Rate;
+
+        impl Bounds for Rate {
+            const MIN: Option<i64> = Some(0);
+            const MAX: Option<i64> = Some(100);
+            const SCALE: Option<u32> = Some(1);
+        }
+
+        let rate = with_region(Some(Country::FR), || Percentage::<Rate>::from_str("7,7 %")).unwrap();
+        assert_eq!(rate.fraction().to_string(), "0.077");
+        assert_eq!(rate.to_string(), "7,7\u{a0}%");
+        assert_eq!(Percentage::<Rate>::from_str("12.50%").unwrap().value().to_string(), "12.50");
+        assert_eq!(Percentage::<Rate>::from_str("101"), Err(DecimalError::TooLarge));
+        assert_eq!(Percentage::<Rate>::from_str("7.75"), Err(DecimalError::TooManyDecimals { max: 1 }));
+    }
+}
@@ -1,12 +1,13 @@
-use std::cell::LazyCell;
-use regex::Regex;
-use super::{Field, FieldError, Rule};
+use super::{Constraints, Field, FieldError, Rule};
 
 pub struct EmailRule;
 
 impl Rule for EmailRule {
-    const REGEX: LazyCell<Regex> =
-        LazyCell::new(|| Regex::new(r"^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$").unwrap());
+    const CONSTRAINTS: Constraints = Constraints::new()
+        .pattern(r"^[a-zA-Z0-9._%+\-]+@[a-zA-Z0-9.\-]+\.[a-zA-Z]{2,}$")
+        .max_length(254)
+        .input_mode("email")
+        .autocomplete("email");
     const DEFAULT: &'static str = "test@example.com";
     const ATTRIBUTES: &'static [(&'static str, &'static str)] = &[("type", "email")];
 }
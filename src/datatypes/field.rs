@@ -1,17 +1,250 @@
+use std::{cell::RefCell, collections::HashMap, convert::Infallible, fmt::{self, Display, Debug}, marker::PhantomData, ops::Deref, str::FromStr};
 
-use std::{cell::LazyCell, convert::Infallible, fmt::{self, Display, Debug}, marker::PhantomData, ops::Deref, str::FromStr};
-
-use leptos::IntoAttribute;
+use leptos::{Attribute, IntoAttribute};
 use regex::Regex;
 
 use super::Datatype;
 
 pub trait Rule: 'static {
-    const REGEX: LazyCell<Regex>;
+    /// The constraints of valid values, which are checked when validating
+    /// and added to the input field as HTML attributes.
+    const CONSTRAINTS: Constraints;
     const DEFAULT: &'static str;
+    /// Additional HTML attributes of the input field, which take precedence over the attributes
+    /// derived from the constraints.
     const ATTRIBUTES: &'static [(&'static str, &'static str)] = &[("type", "text")];
 }
 
+/// Describes the values that a `Rule` accepts.
+/// The same definition is used to validate the values and to create the attributes of the input field,
+/// so that browsers can check the values before they are submitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Constraints {
+    pattern: Option<&'static str>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    required: bool,
+    input_mode: Option<&'static str>,
+    autocomplete: Option<&'static str>,
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Constraints {
+    /// Creates constraints that accept any non-empty value.
+    pub const fn new() -> Self {
+        Self {
+            pattern: None,
+            min_length: None,
+            max_length: None,
+            required: true,
+            input_mode: None,
+            autocomplete: None,
+        }
+    }
+
+    /// The regular expression that values must match, which is also used as `pattern` attribute.
+    /// It should be anchored with `^` and `$` and only use syntax that is also supported by JavaScript.
+    /// Browsers compile the `pattern` attribute with the `v` flag, so within character classes,
+    /// `-` must be escaped unless it forms a range, the characters `( ) [ ] { } / |` must be escaped
+    /// and punctuation must not be doubled, as in `[a-z.\-]`.
+    /// Outside of character classes, only syntax characters such as `.` or `+` may be escaped.
+    /// Otherwise, browsers silently ignore the pattern, which is checked in debug builds.
+    pub const fn pattern(mut self, pattern: &'static str) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    /// The minimum number of characters.
+    pub const fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = Some(min_length);
+        self
+    }
+
+    /// The maximum number of characters.
+    pub const fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Whether empty values are rejected, which is the default.
+    pub const fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// The `inputmode` attribute, which selects the virtual keyboard, for example `numeric`.
+    pub const fn input_mode(mut self, input_mode: &'static str) -> Self {
+        self.input_mode = Some(input_mode);
+        self
+    }
+
+    /// The `autocomplete` attribute, for example `email` or `postal-code`.
+    pub const fn autocomplete(mut self, autocomplete: &'static str) -> Self {
+        self.autocomplete = Some(autocomplete);
+        self
+    }
+
+    /// Checks a value against the constraints.
+    pub fn check<R: Rule>(&self, value: &str) -> Result<(), FieldError<R>> {
+        if value.is_empty() {
+            return if self.required { Err(FieldError::Required(PhantomData)) } else { Ok(()) };
+        }
+
+        let length = value.chars().count();
+        if self.min_length.is_some_and(|min_length| length < min_length) {
+            return Err(FieldError::TooShort(PhantomData));
+        }
+        if self.max_length.is_some_and(|max_length| length > max_length) {
+            return Err(FieldError::TooLong(PhantomData));
+        }
+        if self.pattern.is_some_and(|pattern| !is_match(pattern, value)) {
+            return Err(FieldError::InvalidFormat(PhantomData));
+        }
+
+        Ok(())
+    }
+
+    /// The HTML attributes that describe the constraints.
+    pub fn attributes(&self) -> Vec<(&'static str, Attribute)> {
+        let mut attributes = Vec::new();
+        if let Some(pattern) = self.pattern {
+            debug_assert!(
+                v_mode_error(pattern).is_none(),
+                "the pattern {pattern} is ignored by browsers: {}",
+                v_mode_error(pattern).unwrap_or_default()
+            );
+            attributes.push(("pattern", pattern.into_attribute()));
+        }
+        if let Some(min_length) = self.min_length {
+            attributes.push(("minlength", min_length.to_string().into_attribute()));
+        }
+        if let Some(max_length) = self.max_length {
+            attributes.push(("maxlength", max_length.to_string().into_attribute()));
+        }
+        if self.required {
+            attributes.push(("required", Attribute::Bool(true)));
+        }
+        if let Some(input_mode) = self.input_mode {
+            attributes.push(("inputmode", input_mode.into_attribute()));
+        }
+        if let Some(autocomplete) = self.autocomplete {
+            attributes.push(("autocomplete", autocomplete.into_attribute()));
+        }
+        attributes
+    }
+}
+
+thread_local! {
+    static REGEXES: RefCell<HashMap<&'static str, Regex>> = RefCell::new(HashMap::new());
+}
+
+/// Matches a value against a pattern, compiling each pattern only once per thread.
+pub(crate) fn is_match(pattern: &'static str, value: &str) -> bool {
+    REGEXES.with(|regexes| {
+        regexes
+            .borrow_mut()
+            .entry(pattern)
+            .or_insert_with(|| Regex::new(pattern).unwrap())
+            .is_match(value)
+    })
+}
+
+/// Checks that the escapes and character classes of a pattern are valid with the `v` flag of JavaScript,
+/// which browsers use for the `pattern` attribute, and returns the reason if they are not.
+pub(crate) fn v_mode_error(pattern: &str) -> Option<&'static str> {
+    #[derive(PartialEq)]
+    enum Previous {
+        None,
+        Character,
+        Range,
+    }
+
+    const SYNTAX_CHARACTERS: &str = "^$\\.*+?()[]{}|/";
+    // Punctuation that may only be escaped within character classes.
+    const CLASS_PUNCTUATORS: &str = "&-!#%,:;<=>@`~";
+
+    let mut chars = pattern.chars().peekable();
+    let mut in_class = false;
+    let mut previous = Previous::None;
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let escaped = chars.peek().copied().unwrap_or_default();
+            let allowed = escaped.is_ascii_alphanumeric()
+                || SYNTAX_CHARACTERS.contains(escaped)
+                || (in_class && CLASS_PUNCTUATORS.contains(escaped));
+            if !allowed {
+                return Some("unnecessary escape");
+            }
+        }
+
+        if !in_class {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '[' => {
+                    in_class = true;
+                    previous = Previous::None;
+                    chars.next_if_eq(&'^');
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '\\' => {
+                chars.next();
+                previous = Previous::Character;
+            }
+            ']' => in_class = false,
+            '-' => {
+                let next = chars.peek().copied();
+                if previous != Previous::Character || matches!(next, None | Some(']' | '-')) {
+                    return Some("unescaped `-` outside of a range");
+                }
+                if next == Some('\\') {
+                    chars.next();
+                }
+                chars.next();
+                previous = Previous::Range;
+            }
+            '(' | ')' | '[' | '{' | '}' | '/' | '|' => return Some("unescaped syntax character in a character class"),
+            _ => {
+                if "&!#$%*+,.:;<=>?@^`~".contains(c) && chars.peek() == Some(&c) {
+                    return Some("doubled punctuation in a character class");
+                }
+                previous = Previous::Character;
+            }
+        }
+    }
+
+    None
+}
+
+/// Combines the attributes of a rule with the attributes derived from its constraints.
+pub(crate) fn rule_attributes(
+    attributes: &'static [(&'static str, &'static str)],
+    derived: Vec<(&'static str, Attribute)>,
+) -> Vec<(&'static str, Attribute)> {
+    let mut result = attributes
+        .iter()
+        .map(|(name, value)| (*name, value.into_attribute()))
+        .collect::<Vec<_>>();
+    for (name, value) in derived {
+        if !attributes.iter().any(|(existing, _)| *existing == name) {
+            result.push((name, value));
+        }
+    }
+    result
+}
+
 pub struct Field<R: Rule> {
     value: String,
     _rule: PhantomData<R>,
@@ -19,11 +252,19 @@ pub struct Field<R: Rule> {
 
 pub enum FieldError<R: Rule> {
     InvalidFormat(PhantomData<R>),
+    Required(PhantomData<R>),
+    TooShort(PhantomData<R>),
+    TooLong(PhantomData<R>),
 }
 
 impl<R: Rule> Debug for FieldError<R> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "FieldError({:?})", self)
+        match self {
+            Self::InvalidFormat(_) => write!(f, "InvalidFormat"),
+            Self::Required(_) => write!(f, "Required"),
+            Self::TooShort(_) => write!(f, "TooShort"),
+            Self::TooLong(_) => write!(f, "TooLong"),
+        }
     }
 }
 
@@ -31,6 +272,9 @@ impl<R: Rule> Display for FieldError<R> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::InvalidFormat(_) => write!(f, "invalid format"),
+            Self::Required(_) => write!(f, "required"),
+            Self::TooShort(_) => write!(f, "too short"),
+            Self::TooLong(_) => write!(f, "too long"),
         }
     }
 }
@@ -39,9 +283,7 @@ impl<R: Rule> std::error::Error for FieldError<R> {}
 
 impl<R: Rule> Clone for FieldError<R> {
     fn clone(&self) -> Self {
-        match self {
-            Self::InvalidFormat(_) => Self::InvalidFormat(PhantomData),
-        }
+        *self
     }
 }
 
@@ -49,7 +291,7 @@ impl<R: Rule> Copy for FieldError<R> {}
 
 impl<R: Rule> PartialEq for FieldError<R> {
     fn eq(&self, other: &Self) -> bool {
-        matches!(self, Self::InvalidFormat(_)) && matches!(other, Self::InvalidFormat(_))
+        std::mem::discriminant(self) == std::mem::discriminant(other)
     }
 }
 
@@ -66,18 +308,15 @@ impl<R: Rule> Datatype for Field<R> {
     type Error = FieldError<R>;
 
     fn validate(input: String) -> Result<Field<R>, FieldError<R>> {
-        if !R::REGEX.is_match(&input) {
-            Err(FieldError::InvalidFormat(PhantomData))
-        } else {
-            Ok(Field {
-                value: input,
-                _rule: PhantomData,
-            })
-        }
+        R::CONSTRAINTS.check(&input)?;
+        Ok(Field {
+            value: input,
+            _rule: PhantomData,
+        })
     }
 
     fn attributes() -> Vec<(&'static str, leptos::Attribute)> {
-        vec![("type", "text".into_attribute())]
+        rule_attributes(R::ATTRIBUTES, R::CONSTRAINTS.attributes())
     }
 }
 
@@ -124,9 +363,9 @@ impl<R: Rule> Deref for Field<R> {
     }
 }
 
-impl<R: Rule> Into<String> for Field<R> {
-    fn into(self) -> String {
-        self.value
+impl<R: Rule> From<Field<R>> for String {
+    fn from(field: Field<R>) -> Self {
+        field.value
     }
 }
 
@@ -155,4 +394,77 @@ impl<R: Rule> serde::Serialize for Field<R> {
     {
         self.value.serialize(serializer)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CodeRule;
+
+    impl Rule for CodeRule {
+        const CONSTRAINTS: Constraints = Constraints::new()
+            .pattern(r"^[A-Z0-9]+$")
+            .min_length(3)
+            .max_length(5)
+            .input_mode("text")
+            .autocomplete("off");
+        const DEFAULT: &'static str = "ABC";
+        const ATTRIBUTES: &'static [(&'static str, &'static str)] = &[("type", "text"), ("autocomplete", "one-time-code")];
+    }
+
+    type Code = Field<CodeRule>;
+
+    #[test]
+    fn test_constraints() {
+        assert!(Code::from_str("AB12").is_ok());
+        assert_eq!(Code::from_str(""), Err(FieldError::Required(PhantomData)));
+        assert_eq!(Code::from_str("AB"), Err(FieldError::TooShort(PhantomData)));
+        assert_eq!(Code::from_str("ABCDEF"), Err(FieldError::TooLong(PhantomData)));
+        assert_eq!(Code::from_str("ab12"), Err(FieldError::InvalidFormat(PhantomData)));
+    }
+
+    #[test]
+    fn test_v_mode() {
+        assert_eq!(v_mode_error(r"^[a-zA-Z0-9._%+\-]+@[0-9]{4}$"), None);
+        assert_eq!(v_mode_error(r"^[^\]\-]$"), None);
+        assert!(v_mode_error(r"^[a-z.-]$").is_some());
+        assert!(v_mode_error(r"^[-a]$").is_some());
+        assert!(v_mode_error(r"^[a-c-e]$").is_some());
+        assert!(v_mode_error(r"^[(]$").is_some());
+        assert!(v_mode_error(r"^[a..]$").is_some());
+        assert!(v_mode_error(r"^[A-Z]{2}\-[0-9]$").is_some());
+        assert!(v_mode_error(r"^[\_]$").is_some());
+
+        // The patterns of the built-in rules must be usable as `pattern` attribute.
+        for pattern in [
+            crate::EmailRule::CONSTRAINTS.pattern,
+            crate::PostalCodeCHRule::CONSTRAINTS.pattern,
+        ] {
+            let pattern = pattern.unwrap();
+            assert_eq!(v_mode_error(pattern), None, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn test_attributes() {
+        let attributes = Code::attributes()
+            .into_iter()
+            .map(|(name, value)| (name, value.as_nameless_value_string().map(|value| value.to_string())))
+            .collect::<Vec<_>>();
+
+        // The attributes of the rule take precedence.
+        assert_eq!(
+            attributes,
+            [
+                ("type", Some("text".to_owned())),
+                ("autocomplete", Some("one-time-code".to_owned())),
+                ("pattern", Some("^[A-Z0-9]+$".to_owned())),
+                ("minlength", Some("3".to_owned())),
+                ("maxlength", Some("5".to_owned())),
+                ("required", Some(String::new())),
+                ("inputmode", Some("text".to_owned())),
+            ]
+        );
+    }
+}
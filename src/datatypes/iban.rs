@@ -1,15 +1,319 @@
-use std::cell::LazyCell;
-use regex::Regex;
-use super::{Field, FieldError, Rule};
+use std::{convert::Infallible, fmt::{Display, self}, ops::Deref};
 
-pub struct IbanRule;
+use leptos::IntoAttribute;
+use serde::Serialize;
+use thiserror::Error;
 
-impl Rule for IbanRule {
-    const REGEX: LazyCell<Regex> =
-        LazyCell::new(|| Regex::new(r"^[A-Z]{2}[0-9]{2}[a-zA-Z0-9]{11,30}$").unwrap());
-    const DEFAULT: &'static str = "CH9300762011623852957";
-    const ATTRIBUTES: &'static [(&'static str, &'static str)] = &[("type", "text")];
+use crate::impl_datatype;
+
+use super::Datatype;
+
+/// The structure of the BBAN per country, as in the IBAN registry of SWIFT.
+/// `n` are digits, `a` are upper case letters and `c` are letters or digits.
+const COUNTRIES: &[(&str, &str)] = &[
+    ("AD", "4n4n12c"),
+    ("AE", "3n16n"),
+    ("AL", "8n16c"),
+    ("AT", "5n11n"),
+    ("AZ", "4a20c"),
+    ("BA", "3n3n8n2n"),
+    ("BE", "3n7n2n"),
+    ("BG", "4a4n2n8c"),
+    ("BH", "4a14c"),
+    ("BI", "5n5n11n2n"),
+    ("BR", "8n5n10n1a1c"),
+    ("BY", "4c4n16c"),
+    ("CH", "5n12c"),
+    ("CR", "4n14n"),
+    ("CY", "3n5n16c"),
+    ("CZ", "4n6n10n"),
+    ("DE", "8n10n"),
+    ("DJ", "5n5n11n2n"),
+    ("DK", "4n9n1n"),
+    ("DO", "4c20n"),
+    ("EE", "2n2n11n1n"),
+    ("EG", "4n4n17n"),
+    ("ES", "4n4n1n1n10n"),
+    ("FI", "3n11n"),
+    ("FK", "2a12n"),
+    ("FO", "4n9n1n"),
+    ("FR", "5n5n11c2n"),
+    ("GB", "4a6n8n"),
+    ("GE", "2a16n"),
+    ("GI", "4a15c"),
+    ("GL", "4n9n1n"),
+    ("GR", "3n4n16c"),
+    ("GT", "4c20c"),
+    ("HN", "4a20n"),
+    ("HR", "7n10n"),
+    ("HU", "3n4n1n15n1n"),
+    ("IE", "4a6n8n"),
+    ("IL", "3n3n13n"),
+    ("IQ", "4a3n12n"),
+    ("IS", "4n2n6n10n"),
+    ("IT", "1a5n5n12c"),
+    ("JO", "4a4n18c"),
+    ("KW", "4a22c"),
+    ("KZ", "3n13c"),
+    ("LB", "4n20c"),
+    ("LC", "4a24c"),
+    ("LI", "5n12c"),
+    ("LT", "5n11n"),
+    ("LU", "3n13c"),
+    ("LV", "4a13c"),
+    ("LY", "3n3n15n"),
+    ("MC", "5n5n11c2n"),
+    ("MD", "2c18c"),
+    ("ME", "3n13n2n"),
+    ("MK", "3n10c2n"),
+    ("MN", "4n12n"),
+    ("MR", "5n5n11n2n"),
+    ("MT", "4a5n18c"),
+    ("MU", "4a2n2n12n3n3a"),
+    ("NI", "4a20n"),
+    ("NL", "4a10n"),
+    ("NO", "4n6n1n"),
+    ("OM", "3n16c"),
+    ("PK", "4a16c"),
+    ("PL", "8n16n"),
+    ("PS", "4a21c"),
+    ("PT", "4n4n11n2n"),
+    ("QA", "4a21c"),
+    ("RO", "4a16c"),
+    ("RS", "3n13n2n"),
+    ("RU", "9n5n15c"),
+    ("SA", "2n18c"),
+    ("SC", "4a2n2n16n3a"),
+    ("SD", "2n12n"),
+    ("SE", "3n16n1n"),
+    ("SI", "5n8n2n"),
+    ("SK", "4n6n10n"),
+    ("SM", "1a5n5n12c"),
+    ("SO", "4n3n12n"),
+    ("ST", "4n4n11n2n"),
+    ("SV", "4a20n"),
+    ("TL", "3n14n2n"),
+    ("TN", "2n3n13n2n"),
+    ("TR", "5n1n16c"),
+    ("UA", "6n19c"),
+    ("VA", "3n15n"),
+    ("VG", "4a16n"),
+    ("XK", "4n10n2n"),
+    ("YE", "4a4n18c"),
+];
+
+/// A datatype representing an International Bank Account Number (IBAN).
+/// Whitespace is removed and letters are converted to upper case,
+/// so that IBANs can be entered as they are printed.
+/// The checksum and the length and structure of the country are validated.
+/// The IBAN is stored and serialized without spaces, but displayed in groups of four characters.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Iban(String);
+
+/// The error type for the `Iban` datatype.
+/// Can be used to display an error message by providing a custom translation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum IbanError {
+    #[error("invalid format")]
+    InvalidFormat,
+    #[error("unknown country")]
+    UnknownCountry,
+    #[error("invalid length, expected {expected} characters")]
+    InvalidLength { expected: usize },
+    #[error("invalid checksum")]
+    InvalidChecksum,
+}
+
+impl From<Infallible> for IbanError {
+    fn from(_: Infallible) -> Self {
+        unreachable!()
+    }
+}
+
+impl Iban {
+    /// The ISO 3166 country code, for example `CH`.
+    pub fn country_code(&self) -> &str {
+        &self.0[..2]
+    }
+
+    /// The Basic Bank Account Number, which is the IBAN without country code and checksum.
+    pub fn bban(&self) -> &str {
+        &self.0[4..]
+    }
+
+    /// The bank clearing number, which is the IID in Switzerland and Liechtenstein
+    /// and the Bankleitzahl in Germany and Austria.
+    /// Returns `None` for other countries.
+    pub fn bank_clearing_number(&self) -> Option<&str> {
+        let length = match self.country_code() {
+            "CH" | "LI" | "AT" => 5,
+            "DE" => 8,
+            _ => return None,
+        };
+        Some(&self.bban()[..length])
+    }
+}
+
+impl Datatype for Iban {
+    type Inner = String;
+    type Error = IbanError;
+
+    fn validate(input: String) -> Result<Self, IbanError> {
+        let iban = input
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_uppercase();
+
+        let bytes = iban.as_bytes();
+        if bytes.len() < 4
+            || !bytes[..2].iter().all(u8::is_ascii_uppercase)
+            || !bytes[2..4].iter().all(u8::is_ascii_digit)
+            || !bytes.iter().all(u8::is_ascii_alphanumeric)
+        {
+            return Err(IbanError::InvalidFormat);
+        }
+
+        let Some((_, structure)) = COUNTRIES.iter().find(|(country, _)| *country == &iban[..2]) else {
+            return Err(IbanError::UnknownCountry);
+        };
+        let structure = parse_structure(structure);
+        let expected = 4 + structure.iter().map(|(count, _)| count).sum::<usize>();
+        if iban.len() != expected {
+            return Err(IbanError::InvalidLength { expected });
+        }
+
+        let mut bban = bytes[4..].iter();
+        for (count, class) in structure {
+            let valid = bban.by_ref().take(count).all(|c| match class {
+                'n' => c.is_ascii_digit(),
+                'a' => c.is_ascii_uppercase(),
+                _ => true,
+            });
+            if !valid {
+                return Err(IbanError::InvalidFormat);
+            }
+        }
+
+        if checksum(&iban) != 1 {
+            return Err(IbanError::InvalidChecksum);
+        }
+
+        Ok(Iban(iban))
+    }
+
+    fn attributes() -> Vec<(&'static str, leptos::Attribute)> {
+        vec![
+            ("type", "text".into_attribute()),
+            ("autocomplete", "off".into_attribute()),
+        ]
+    }
 }
 
-pub type Iban = Field<IbanRule>;
-pub type IbanError = FieldError<IbanRule>;
\ No newline at end of file
+/// Parses a BBAN structure such as `5n12c` into its parts.
+fn parse_structure(structure: &str) -> Vec<(usize, char)> {
+    let mut parts = Vec::new();
+    let mut count = 0;
+    for c in structure.chars() {
+        match c.to_digit(10) {
+            Some(digit) => count = count * 10 + digit as usize,
+            None => {
+                parts.push((count, c));
+                count = 0;
+            }
+        }
+    }
+    parts
+}
+
+/// Computes the ISO 13616 checksum, which is 1 for valid IBANs.
+/// The first four characters are moved to the end and letters are replaced by two digits,
+/// `A` being 10, before taking the remainder of the division by 97.
+fn checksum(iban: &str) -> u32 {
+    iban[4..]
+        .chars()
+        .chain(iban[..4].chars())
+        .fold(0, |remainder, c| {
+            let value = c.to_digit(36).unwrap();
+            if value < 10 {
+                (remainder * 10 + value) % 97
+            } else {
+                (remainder * 100 + value) % 97
+            }
+        })
+}
+
+impl Display for Iban {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, chunk) in self.0.as_bytes().chunks(4).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", std::str::from_utf8(chunk).unwrap())?;
+        }
+        Ok(())
+    }
+}
+
+impl Deref for Iban {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Iban> for String {
+    fn from(iban: Iban) -> Self {
+        iban.0
+    }
+}
+
+impl Default for Iban {
+    fn default() -> Self {
+        Self::validate("CH9300762011623852957".into()).unwrap()
+    }
+}
+
+impl_datatype!(Iban);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_iban() {
+        let iban = Iban::from_str(" ch93 0076 2011 6238 5295 7").unwrap();
+        assert_eq!(&*iban, "CH9300762011623852957");
+        assert_eq!(iban.to_string(), "CH93 0076 2011 6238 5295 7");
+        assert_eq!(iban.country_code(), "CH");
+        assert_eq!(iban.bank_clearing_number(), Some("00762"));
+
+        let iban = Iban::from_str("DE89 3704 0044 0532 0130 00").unwrap();
+        assert_eq!(iban.bank_clearing_number(), Some("37040044"));
+        assert_eq!(Iban::from_str("GB29 NWBK 6016 1331 9268 19").unwrap().bank_clearing_number(), None);
+    }
+
+    #[test]
+    fn test_iban_errors() {
+        assert_eq!(Iban::from_str("CH93-0076"), Err(IbanError::InvalidFormat));
+        assert_eq!(Iban::from_str("ZZ9300762011623852957"), Err(IbanError::UnknownCountry));
+        assert_eq!(Iban::from_str("CH930076201162385295"), Err(IbanError::InvalidLength { expected: 21 }));
+        assert_eq!(Iban::from_str("CH9400762011623852957"), Err(IbanError::InvalidChecksum));
+        // German account numbers are numeric.
+        assert_eq!(Iban::from_str("DE89370400440532013A00"), Err(IbanError::InvalidFormat));
+    }
+
+    #[test]
+    fn test_country_lengths() {
+        let length = |country: &str| {
+            let (_, structure) = COUNTRIES.iter().find(|(c, _)| *c == country).unwrap();
+            4 + parse_structure(structure).iter().map(|(count, _)| count).sum::<usize>()
+        };
+        assert_eq!(length("NO"), 15);
+        assert_eq!(length("FR"), 27);
+        assert_eq!(length("LC"), 32);
+        assert_eq!(length("RU"), 33);
+    }
+}
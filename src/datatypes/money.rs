@@ -0,0 +1,416 @@
+use std::{convert::Infallible, fmt::{self, Debug, Display}, marker::PhantomData, str::FromStr};
+
+use leptos::{Attribute, IntoAttribute};
+use rust_decimal::Decimal as Exact;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{check_bounds, current_region, default_value, format_decimal, parse_canonical, parse_decimal, Bounds, Country, Datatype, DecimalError, Unbounded};
+
+/// A currency, identified by its ISO 4217 code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Currency {
+    CHF,
+    EUR,
+    GBP,
+    JPY,
+    USD,
+}
+
+/// The error returned when parsing an unsupported currency code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("unknown currency")]
+pub struct UnknownCurrency;
+
+impl Currency {
+    pub const ALL: &'static [Currency] = &[
+        Currency::CHF,
+        Currency::EUR,
+        Currency::GBP,
+        Currency::JPY,
+        Currency::USD,
+    ];
+
+    /// The ISO 4217 code, for example `CHF`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::CHF => "CHF",
+            Currency::EUR => "EUR",
+            Currency::GBP => "GBP",
+            Currency::JPY => "JPY",
+            Currency::USD => "USD",
+        }
+    }
+
+    /// The number of decimal places of the minor unit, for example 2 for cents.
+    pub fn minor_units(&self) -> u32 {
+        match self {
+            Currency::JPY => 0,
+            _ => 2,
+        }
+    }
+
+    /// The symbol used when displaying amounts, for example `€`.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Currency::CHF => "CHF",
+            Currency::EUR => "€",
+            Currency::GBP => "£",
+            Currency::JPY => "¥",
+            Currency::USD => "$",
+        }
+    }
+
+    /// The codes and symbols that are accepted when parsing amounts.
+    fn tokens(&self) -> &'static [&'static str] {
+        match self {
+            Currency::CHF => &["CHF", "SFr.", "Fr."],
+            Currency::EUR => &["EUR", "€"],
+            Currency::GBP => &["GBP", "£"],
+            Currency::JPY => &["JPY", "¥"],
+            Currency::USD => &["USD", "US$", "$"],
+        }
+    }
+
+    /// Splits a leading or trailing currency code or symbol from an amount.
+    fn split(input: &str) -> Option<(Currency, &str)> {
+        Currency::ALL.iter().find_map(|currency| {
+            currency.tokens().iter().find_map(|token| {
+                let prefix = input
+                    .get(..token.len())
+                    .filter(|prefix| prefix.eq_ignore_ascii_case(token))
+                    .map(|_| &input[token.len()..]);
+                let suffix = input
+                    .len()
+                    .checked_sub(token.len())
+                    .filter(|index| input.get(*index..).is_some_and(|suffix| suffix.eq_ignore_ascii_case(token)))
+                    .map(|index| &input[..index]);
+                prefix.or(suffix).map(|amount| (*currency, amount.trim()))
+            })
+        })
+    }
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = UnknownCurrency;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Currency::ALL
+            .iter()
+            .find(|currency| currency.code().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or(UnknownCurrency)
+    }
+}
+
+/// A currency that is fixed at compile time, used as default currency of `Money`.
+pub trait CurrencyUnit: 'static {
+    const CURRENCY: Currency;
+}
+
+macro_rules! currency_units {
+    ( $( $name:ident => $currency:ident ),* $(,)? ) => {
+        $(
+            #[doc = concat!("The currency `", stringify!($currency), "`.")]
+            pub struct $name;
+
+            impl CurrencyUnit for $name {
+                const CURRENCY: Currency = Currency::$currency;
+            }
+        )*
+    };
+}
+
+currency_units! {
+    CurrencyCHF => CHF,
+    CurrencyEUR => EUR,
+    CurrencyGBP => GBP,
+    CurrencyJPY => JPY,
+    CurrencyUSD => USD,
+}
+
+/// A datatype representing an amount of money in a currency.
+/// Accepts amounts such as `1'234.50`, `CHF 1'234.50`, `1.234,50 €` or `Fr. 5.–`,
+/// which are parsed as they are written in the current region, see `RegionProvider`.
+/// Amounts without currency are in the currency `C`.
+/// The amount may have at most as many decimal places as the minor unit of the currency,
+/// and must lie within the bounds `B`.
+/// It is displayed with the currency symbol, and submitted, serialized and deserialized losslessly
+/// in the canonical format, which is independent of the region, for example `1234.50 CHF`.
+pub struct Money<C: CurrencyUnit = CurrencyCHF, B: Bounds = Unbounded> {
+    amount: Exact,
+    currency: Currency,
+    region: Option<Country>,
+    _marker: PhantomData<(C, B)>,
+}
+
+/// The error type for the `Money` datatype.
+/// Can be used to display an error message by providing a custom translation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum MoneyError {
+    #[error("invalid format")]
+    InvalidFormat,
+    #[error("unknown currency")]
+    UnknownCurrency,
+    #[error("too small")]
+    TooSmall,
+    #[error("too large")]
+    TooLarge,
+    #[error("too many decimal places, at most {max} are allowed")]
+    TooManyDecimals { max: u32 },
+}
+
+impl From<DecimalError> for MoneyError {
+    fn from(error: DecimalError) -> Self {
+        match error {
+            DecimalError::InvalidFormat => MoneyError::InvalidFormat,
+            DecimalError::TooSmall => MoneyError::TooSmall,
+            DecimalError::TooLarge => MoneyError::TooLarge,
+            DecimalError::TooManyDecimals { max } => MoneyError::TooManyDecimals { max },
+        }
+    }
+}
+
+impl From<Infallible> for MoneyError {
+    fn from(_: Infallible) -> Self {
+        unreachable!()
+    }
+}
+
+impl<C: CurrencyUnit, B: Bounds> Money<C, B> {
+    /// The amount in the currency, with as many decimal places as the minor unit.
+    pub fn amount(&self) -> Exact {
+        self.amount
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// The amount in the minor unit of the currency, for example in cents,
+    /// as expected by most payment providers.
+    pub fn in_minor_units(&self) -> i128 {
+        self.amount.mantissa()
+    }
+
+    /// Parses an amount in the canonical format, such as `1234.50 CHF`, independent of the region.
+    pub fn from_canonical(input: &str) -> Result<Self, MoneyError> {
+        let (amount, code) = input.split_once(' ').ok_or(MoneyError::InvalidFormat)?;
+        let currency = Currency::from_str(code).map_err(|_| MoneyError::UnknownCurrency)?;
+        Self::new_checked(parse_canonical(amount)?, currency, None)
+    }
+
+    fn new_checked(mut amount: Exact, currency: Currency, region: Option<Country>) -> Result<Self, MoneyError> {
+        check_bounds::<B>(amount, Some(currency.minor_units()))?;
+        amount.rescale(currency.minor_units());
+
+        Ok(Money {
+            amount,
+            currency,
+            region,
+            _marker: PhantomData,
+        })
+    }
+
+    fn format(&self, region: Option<Country>) -> String {
+        let sign = if self.amount.is_sign_negative() && !self.amount.is_zero() { "-" } else { "" };
+        let amount = format_decimal(self.amount.abs(), region);
+        let symbol = self.currency.symbol();
+        match region {
+            // Regions with a decimal comma write the symbol after the amount.
+            Some(region) if region.decimal_separator() == ',' => format!("{sign}{amount}\u{a0}{symbol}"),
+            _ if symbol.chars().count() == 1 => format!("{sign}{symbol}{amount}"),
+            _ => format!("{sign}{symbol}\u{a0}{amount}"),
+        }
+    }
+}
+
+impl<C: CurrencyUnit, B: Bounds> Datatype for Money<C, B> {
+    type Inner = String;
+    type Error = MoneyError;
+
+    fn validate(input: String) -> Result<Self, MoneyError> {
+        let input = input.trim();
+        // The sign can precede the currency, as in `-CHF 5.00`.
+        let (negative, input) = match input.strip_prefix(['-', '−']) {
+            Some(input) => (true, input.trim_start()),
+            None => (false, input),
+        };
+        let (currency, amount) = Currency::split(input).unwrap_or((C::CURRENCY, input));
+        if negative && amount.starts_with(['-', '−', '+']) {
+            return Err(MoneyError::InvalidFormat);
+        }
+        // Whole amounts are often written as `5.–` in Switzerland.
+        let amount = [".-", ".–", ",-", ",–"]
+            .iter()
+            .find_map(|suffix| amount.strip_suffix(suffix))
+            .unwrap_or(amount);
+
+        let region = current_region();
+        let amount = parse_decimal(amount, region).map_err(|error| {
+            if amount.chars().any(char::is_alphabetic) {
+                MoneyError::UnknownCurrency
+            } else {
+                error.into()
+            }
+        })?;
+        let amount = if negative { -amount } else { amount };
+        Self::new_checked(amount, currency, region)
+    }
+
+    fn attributes() -> Vec<(&'static str, Attribute)> {
+        vec![
+            ("type", "text".into_attribute()),
+            ("inputmode", "decimal".into_attribute()),
+            ("autocomplete", "transaction-amount".into_attribute()),
+            ("required", Attribute::Bool(true)),
+        ]
+    }
+
+    fn render(&self) -> Option<String> {
+        Some(self.format(current_region().or(self.region)))
+    }
+
+    fn submit_value(&self) -> Option<String> {
+        Some(self.clone().into())
+    }
+}
+
+impl<C: CurrencyUnit, B: Bounds> Display for Money<C, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format(self.region))
+    }
+}
+
+impl<C: CurrencyUnit, B: Bounds> Debug for Money<C, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Money({} {})", self.amount, self.currency)
+    }
+}
+
+impl<C: CurrencyUnit, B: Bounds> Clone for Money<C, B> {
+    fn clone(&self) -> Self {
+        Self {
+            amount: self.amount,
+            currency: self.currency,
+            region: self.region,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: CurrencyUnit, B: Bounds> PartialEq for Money<C, B> {
+    fn eq(&self, other: &Self) -> bool {
+        self.amount == other.amount && self.currency == other.currency
+    }
+}
+
+impl<C: CurrencyUnit, B: Bounds> Eq for Money<C, B> {}
+
+impl<C: CurrencyUnit, B: Bounds> From<Money<C, B>> for String {
+    fn from(money: Money<C, B>) -> Self {
+        format!("{} {}", money.amount, money.currency)
+    }
+}
+
+impl<C: CurrencyUnit, B: Bounds> Default for Money<C, B> {
+    fn default() -> Self {
+        let mut amount = default_value::<B>();
+        amount.rescale(C::CURRENCY.minor_units());
+        Self {
+            amount,
+            currency: C::CURRENCY,
+            region: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: CurrencyUnit, B: Bounds> FromStr for Money<C, B> {
+    type Err = <Self as Datatype>::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as Datatype>::validate(<Self as Datatype>::Inner::from_str(s).map_err(<Self as Datatype>::Error::from)?)
+    }
+}
+
+impl<'de, C: CurrencyUnit, B: Bounds> Deserialize<'de> for Money<C, B> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        Self::from_canonical(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+impl<C: CurrencyUnit, B: Bounds> Serialize for Money<C, B> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        String::from(self.clone()).serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{with_region, NonNegative};
+
+    #[test]
+    fn test_money() {
+        let money = with_region(Some(Country::CH), || Money::<CurrencyCHF>::from_str("1'234.5")).unwrap();
+        assert_eq!(money.amount().to_string(), "1234.50");
+        assert_eq!(money.currency(), Currency::CHF);
+        assert_eq!(money.in_minor_units(), 123450);
+        assert_eq!(money.to_string(), "CHF\u{a0}1'234.50");
+
+        let money = with_region(Some(Country::DE), || Money::<CurrencyCHF>::from_str("1.234,50 €")).unwrap();
+        assert_eq!(money.currency(), Currency::EUR);
+        assert_eq!(money.to_string(), "1.234,50\u{a0}€");
+
+        let money = with_region(Some(Country::US), || Money::<CurrencyUSD>::from_str("-$1,000")).unwrap();
+        assert_eq!(money.to_string(), "-$1,000.00");
+        assert_eq!(Money::<CurrencyCHF>::from_str("Fr. 5.–").unwrap().amount().to_string(), "5.00");
+        assert_eq!(Money::<CurrencyJPY>::from_str("1500").unwrap().to_string(), "¥1500");
+    }
+
+    #[test]
+    fn test_money_serialization() {
+        let money = with_region(Some(Country::DE), || Money::<CurrencyEUR>::from_str("1.234,5")).unwrap();
+        let json = serde_json::to_string(&money).unwrap();
+        assert_eq!(json, "\"1234.50 EUR\"");
+        let deserialized: Money<CurrencyEUR> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, money);
+
+        // The amount typed in a German form is submitted in the canonical format,
+        // which the server deserializes without a region.
+        let typed = with_region(Some(Country::DE), || Money::<CurrencyCHF>::from_str("1.000 €")).unwrap();
+        let submitted = typed.submit_value().unwrap();
+        assert_eq!(submitted, "1000.00 EUR");
+        let deserialized: Money = serde_json::from_value(serde_json::Value::String(submitted)).unwrap();
+        assert_eq!(deserialized.amount(), Exact::from(1000));
+        assert_eq!(deserialized.currency(), Currency::EUR);
+
+        for input in ["\"1.000 €\"", "\"1'000.00 CHF\"", "\"1000.00\"", "\"1000.00 XYZ\""] {
+            assert!(serde_json::from_str::<Money>(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_money_errors() {
+        assert_eq!(Money::<CurrencyCHF>::from_str("1.005"), Err(MoneyError::TooManyDecimals { max: 2 }));
+        assert_eq!(Money::<CurrencyJPY>::from_str("1.5"), Err(MoneyError::TooManyDecimals { max: 0 }));
+        assert_eq!(Money::<CurrencyCHF, NonNegative>::from_str("-5"), Err(MoneyError::TooSmall));
+        assert_eq!(Money::<CurrencyCHF>::from_str("5 XYZ"), Err(MoneyError::UnknownCurrency));
+        assert_eq!(Money::<CurrencyCHF>::from_str("5..0"), Err(MoneyError::InvalidFormat));
+        assert_eq!(Money::<CurrencyCHF>::from_str("-CHF -5"), Err(MoneyError::InvalidFormat));
+    }
+}
@@ -0,0 +1,31 @@
+use super::{Country, RegionalField, RegionalFieldError, RegionalRule};
+
+/// National identification numbers of the current region, see `RegionalField`.
+/// These are the social security number in Switzerland (AHV), Austria, France (NIR), the UK (NINO) and the US (SSN),
+/// the identity card number in Germany and the fiscal code in Italy.
+/// Liechtenstein is not supported.
+pub struct NationalIdRule;
+
+impl RegionalRule for NationalIdRule {
+    fn pattern(country: Country) -> Option<&'static str> {
+        match country {
+            Country::AT => Some(r"^[0-9]{4} ?[0-9]{6}$"),
+            Country::CH => Some(r"^756\.?[0-9]{4}\.?[0-9]{4}\.?[0-9]{2}$"),
+            Country::DE => Some(r"^[CFGHJKLMNPRTVWXYZ0-9]{9}$"),
+            Country::FR => Some(r"^[12] ?[0-9]{2} ?[0-9]{2} ?(2[AB]|[0-9]{2}) ?[0-9]{3} ?[0-9]{3} ?[0-9]{2}$"),
+            Country::GB => Some(r"^[A-CEGHJ-PR-TW-Z][A-CEGHJ-NPR-TW-Z] ?[0-9]{2} ?[0-9]{2} ?[0-9]{2} ?[A-D]$"),
+            Country::IT => Some(r"^[A-Z]{6}[0-9LMNPQRSTUV]{2}[ABCDEHLMPRST][0-9LMNPQRSTUV]{2}[A-Z][0-9LMNPQRSTUV]{3}[A-Z]$"),
+            Country::US => Some(r"^[0-8][0-9]{2}-?[0-9]{2}-?[0-9]{4}$"),
+            Country::LI => None,
+        }
+    }
+
+    fn normalize(input: &str) -> String {
+        input.trim().to_uppercase()
+    }
+
+    const DEFAULT: &'static str = "756.1234.5678.97";
+}
+
+pub type NationalId = RegionalField<NationalIdRule>;
+pub type NationalIdError = RegionalFieldError<NationalIdRule>;
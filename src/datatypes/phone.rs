@@ -1,15 +1,416 @@
-use std::cell::LazyCell;
-use regex::Regex;
-use super::{Field, FieldError, Rule};
+use std::{convert::Infallible, fmt::{self, Debug, Display}, marker::PhantomData, ops::{Deref, RangeInclusive}};
 
-pub struct PhoneRule;
+use leptos::IntoAttribute;
+use serde::Serialize;
+use thiserror::Error;
 
-impl Rule for PhoneRule {
-    const REGEX: LazyCell<Regex> =
-        LazyCell::new(|| Regex::new(r"^\+?[0-9]{1,3}[0-9]{3,14}$").unwrap());
-    const DEFAULT: &'static str = "+41791234567";
-    const ATTRIBUTES: &'static [(&'static str, &'static str)] = &[("type", "phone")];
+use crate::impl_datatype;
+
+use super::{current_region, Country, Datatype, Region, RegionCH};
+
+/// The numbering plan of a country.
+struct NumberingPlan {
+    country: Country,
+    calling_code: &'static str,
+    /// Whether other countries share the calling code, as in the North American numbering plan.
+    shared_calling_code: bool,
+    /// The prefix of national numbers that is dropped in the international format.
+    trunk_prefix: Option<&'static str>,
+    /// The lengths of the national significant number.
+    lengths: RangeInclusive<usize>,
+    mobile: &'static [&'static str],
+    fixed_line: &'static [&'static str],
+    /// The sizes of the digit groups when formatting, the remaining digits form the last group.
+    mobile_groups: &'static [usize],
+    fixed_line_groups: &'static [usize],
+}
+
+const NUMBERING_PLANS: &[NumberingPlan] = &[
+    NumberingPlan {
+        country: Country::AT,
+        calling_code: "43",
+        shared_calling_code: false,
+        trunk_prefix: Some("0"),
+        lengths: 4..=13,
+        mobile: &["65", "66", "67", "68", "69"],
+        fixed_line: &["1", "2", "3", "4", "5", "7"],
+        mobile_groups: &[3],
+        fixed_line_groups: &[],
+    },
+    NumberingPlan {
+        country: Country::CH,
+        calling_code: "41",
+        shared_calling_code: false,
+        trunk_prefix: Some("0"),
+        lengths: 9..=9,
+        mobile: &["75", "76", "77", "78", "79"],
+        fixed_line: &["2", "3", "4", "5", "6", "81"],
+        mobile_groups: &[2, 3, 2],
+        fixed_line_groups: &[2, 3, 2],
+    },
+    NumberingPlan {
+        country: Country::DE,
+        calling_code: "49",
+        shared_calling_code: false,
+        trunk_prefix: Some("0"),
+        lengths: 6..=11,
+        mobile: &["15", "16", "17"],
+        fixed_line: &["2", "3", "4", "5", "6", "7", "8", "9"],
+        mobile_groups: &[3],
+        fixed_line_groups: &[],
+    },
+    NumberingPlan {
+        country: Country::FR,
+        calling_code: "33",
+        shared_calling_code: false,
+        trunk_prefix: Some("0"),
+        lengths: 9..=9,
+        mobile: &["6", "7"],
+        fixed_line: &["1", "2", "3", "4", "5", "9"],
+        mobile_groups: &[1, 2, 2, 2],
+        fixed_line_groups: &[1, 2, 2, 2],
+    },
+    NumberingPlan {
+        country: Country::GB,
+        calling_code: "44",
+        shared_calling_code: false,
+        trunk_prefix: Some("0"),
+        lengths: 9..=10,
+        mobile: &["71", "72", "73", "74", "75", "77", "78", "79"],
+        fixed_line: &["1", "2"],
+        mobile_groups: &[4],
+        fixed_line_groups: &[],
+    },
+    NumberingPlan {
+        country: Country::IT,
+        calling_code: "39",
+        shared_calling_code: false,
+        // The leading zero of fixed line numbers is kept in the international format.
+        trunk_prefix: None,
+        lengths: 6..=11,
+        mobile: &["3"],
+        fixed_line: &["0"],
+        mobile_groups: &[3, 3],
+        fixed_line_groups: &[],
+    },
+    NumberingPlan {
+        country: Country::LI,
+        calling_code: "423",
+        shared_calling_code: false,
+        trunk_prefix: None,
+        lengths: 7..=9,
+        mobile: &["6", "7"],
+        fixed_line: &["2", "3"],
+        mobile_groups: &[3, 2],
+        fixed_line_groups: &[3, 2],
+    },
+    NumberingPlan {
+        country: Country::US,
+        calling_code: "1",
+        shared_calling_code: true,
+        trunk_prefix: Some("1"),
+        lengths: 10..=10,
+        // Mobile and fixed line numbers cannot be told apart in the North American numbering plan.
+        mobile: &[],
+        fixed_line: &[],
+        mobile_groups: &[3, 3],
+        fixed_line_groups: &[3, 3],
+    },
+];
+
+impl NumberingPlan {
+    fn of(country: Country) -> &'static NumberingPlan {
+        NUMBERING_PLANS
+            .iter()
+            .find(|plan| plan.country == country)
+            .expect("all countries have a numbering plan")
+    }
+
+    fn phone_type(&self, number: &str) -> PhoneType {
+        if self.mobile.iter().any(|prefix| number.starts_with(prefix)) {
+            PhoneType::Mobile
+        } else if self.fixed_line.iter().any(|prefix| number.starts_with(prefix)) {
+            PhoneType::FixedLine
+        } else {
+            PhoneType::Unknown
+        }
+    }
+}
+
+/// Whether a phone number belongs to a mobile or a fixed line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PhoneType {
+    Mobile,
+    FixedLine,
+    /// The type cannot be determined, for example in the US or for service numbers.
+    Unknown,
+}
+
+/// A datatype representing a phone number.
+/// Accepts international numbers such as `+41 79 123 45 67` or `0041 79 123 45 67`,
+/// and national numbers such as `079 123 45 67`, which are interpreted in the current region,
+/// see `RegionProvider`, or in the region `R` if there is none.
+/// The number is stored and serialized in the E.164 format, for example `+41791234567`,
+/// and displayed in the international format, for example `+41 79 123 45 67`.
+/// International numbers of countries without a built-in numbering plan are only checked for their length.
+pub struct Phone<R: Region = RegionCH> {
+    number: String,
+    plan: Option<&'static NumberingPlan>,
+    _region: PhantomData<R>,
+}
+
+/// The error type for the `Phone` datatype.
+/// Can be used to display an error message by providing a custom translation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum PhoneError {
+    #[error("invalid format")]
+    InvalidFormat,
+    #[error("invalid length")]
+    InvalidLength,
+    #[error("invalid number")]
+    InvalidNumber,
+}
+
+impl From<Infallible> for PhoneError {
+    fn from(_: Infallible) -> Self {
+        unreachable!()
+    }
+}
+
+impl<R: Region> Phone<R> {
+    /// The country of the number, if it has a built-in numbering plan.
+    /// Returns `None` for calling codes that several countries share, such as `+1`
+    /// for the US, Canada and the Caribbean, because the country cannot be told from the number.
+    pub fn country(&self) -> Option<Country> {
+        self.plan
+            .filter(|plan| !plan.shared_calling_code)
+            .map(|plan| plan.country)
+    }
+
+    /// The international calling code without `+`, if the country has a built-in numbering plan.
+    pub fn calling_code(&self) -> Option<&'static str> {
+        self.plan.map(|plan| plan.calling_code)
+    }
+
+    /// The national significant number, which is the number without calling code and trunk prefix.
+    pub fn national_number(&self) -> &str {
+        &self.number[1 + self.calling_code().map_or(0, str::len)..]
+    }
+
+    pub fn phone_type(&self) -> PhoneType {
+        match self.plan {
+            Some(plan) => plan.phone_type(self.national_number()),
+            None => PhoneType::Unknown,
+        }
+    }
+
+    pub fn is_mobile(&self) -> bool {
+        self.phone_type() == PhoneType::Mobile
+    }
+}
+
+impl<R: Region> Datatype for Phone<R> {
+    type Inner = String;
+    type Error = PhoneError;
+
+    fn validate(input: String) -> Result<Self, PhoneError> {
+        // Removes separators and the optional trunk prefix in numbers such as `+41 (0)79 123 45 67`.
+        let input = input.replace("(0)", "");
+        let mut digits = input
+            .chars()
+            .filter(|c| !c.is_whitespace() && !matches!(c, '-' | '.' | '/' | '(' | ')'))
+            .collect::<String>();
+        if let Some(rest) = digits.strip_prefix("00") {
+            digits = format!("+{rest}");
+        }
+
+        let (international, digits) = match digits.strip_prefix('+') {
+            Some(digits) => (true, digits),
+            None => (false, digits.as_str()),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(PhoneError::InvalidFormat);
+        }
+
+        // Calling codes never start with zero.
+        if international && digits.starts_with('0') {
+            return Err(PhoneError::InvalidNumber);
+        }
+
+        let (plan, national_number) = if international {
+            let Some(plan) = NUMBERING_PLANS.iter().find(|plan| digits.starts_with(plan.calling_code)) else {
+                // E.164 numbers have at most 15 digits.
+                if !(8..=15).contains(&digits.len()) {
+                    return Err(PhoneError::InvalidLength);
+                }
+                return Ok(Phone { number: format!("+{digits}"), plan: None, _region: PhantomData });
+            };
+            let national_number = &digits[plan.calling_code.len()..];
+            // The trunk prefix is sometimes written in international numbers by mistake.
+            let national_number = match plan.trunk_prefix {
+                Some("0") => national_number.strip_prefix('0').unwrap_or(national_number),
+                _ => national_number,
+            };
+            (plan, national_number)
+        } else {
+            let plan = NumberingPlan::of(current_region().unwrap_or(R::COUNTRY));
+            let national_number = match plan.trunk_prefix {
+                Some(trunk_prefix) => digits.strip_prefix(trunk_prefix).unwrap_or(digits),
+                None => digits,
+            };
+            (plan, national_number)
+        };
+
+        if !plan.lengths.contains(&national_number.len()) {
+            return Err(PhoneError::InvalidLength);
+        }
+        let invalid_start = match plan.trunk_prefix {
+            Some(trunk_prefix) => national_number.starts_with(trunk_prefix) || national_number.starts_with('0'),
+            None => false,
+        };
+        if invalid_start {
+            return Err(PhoneError::InvalidNumber);
+        }
+
+        Ok(Phone {
+            number: format!("+{}{national_number}", plan.calling_code),
+            plan: Some(plan),
+            _region: PhantomData,
+        })
+    }
+
+    fn attributes() -> Vec<(&'static str, leptos::Attribute)> {
+        vec![
+            ("type", "tel".into_attribute()),
+            ("autocomplete", "tel".into_attribute()),
+        ]
+    }
 }
 
-pub type Phone = Field<PhoneRule>;
-pub type PhoneError = FieldError<PhoneRule>;
\ No newline at end of file
+impl<R: Region> Display for Phone<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(plan) = self.plan else {
+            return write!(f, "{}", self.number);
+        };
+
+        write!(f, "+{}", plan.calling_code)?;
+        let groups = match self.phone_type() {
+            PhoneType::Mobile => plan.mobile_groups,
+            _ => plan.fixed_line_groups,
+        };
+        let mut rest = self.national_number();
+        for size in groups {
+            if rest.len() <= *size {
+                break;
+            }
+            let (group, remaining) = rest.split_at(*size);
+            write!(f, " {group}")?;
+            rest = remaining;
+        }
+        write!(f, " {rest}")
+    }
+}
+
+impl<R: Region> Debug for Phone<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Phone({:?})", self.number)
+    }
+}
+
+impl<R: Region> Clone for Phone<R> {
+    fn clone(&self) -> Self {
+        Self {
+            number: self.number.clone(),
+            plan: self.plan,
+            _region: PhantomData,
+        }
+    }
+}
+
+impl<R: Region> PartialEq for Phone<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.number == other.number
+    }
+}
+
+impl<R: Region> Eq for Phone<R> {}
+
+impl<R: Region> Deref for Phone<R> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.number
+    }
+}
+
+impl<R: Region> From<Phone<R>> for String {
+    fn from(phone: Phone<R>) -> Self {
+        phone.number
+    }
+}
+
+impl<R: Region> Default for Phone<R> {
+    fn default() -> Self {
+        Self::validate("+41791234567".into()).unwrap()
+    }
+}
+
+impl<R: Region> Serialize for Phone<R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.number.serialize(serializer)
+    }
+}
+
+impl_datatype!(Phone<R> where R: Region);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RegionDE, RegionUS};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_phone() {
+        let phone = Phone::<RegionCH>::from_str("079 123 45 67").unwrap();
+        assert_eq!(&*phone, "+41791234567");
+        assert_eq!(phone.to_string(), "+41 79 123 45 67");
+        assert_eq!(phone.country(), Some(Country::CH));
+        assert_eq!(phone.phone_type(), PhoneType::Mobile);
+
+        // International numbers do not depend on the region.
+        for input in ["+41 79 123 45 67", "0041791234567", "+41 (0)79 123-45-67", "+41 079 123 45 67"] {
+            assert_eq!(&*Phone::<RegionDE>::from_str(input).unwrap(), "+41791234567");
+        }
+
+        let phone = Phone::<RegionCH>::from_str("044 668 18 00").unwrap();
+        assert_eq!(phone.phone_type(), PhoneType::FixedLine);
+
+        let phone = Phone::<RegionDE>::from_str("0151 23456789").unwrap();
+        assert_eq!(phone.to_string(), "+49 151 23456789");
+        assert!(phone.is_mobile());
+
+        let phone = Phone::<RegionUS>::from_str("1 (202) 555-0123").unwrap();
+        assert_eq!(phone.to_string(), "+1 202 555 0123");
+        assert_eq!(phone.phone_type(), PhoneType::Unknown);
+        // The calling code +1 is shared with Canada and the Caribbean.
+        assert_eq!(phone.country(), None);
+        assert_eq!(phone.calling_code(), Some("1"));
+
+        // Countries without numbering plan are kept as they are.
+        let phone = Phone::<RegionCH>::from_str("+81 3 1234 5678").unwrap();
+        assert_eq!(phone.to_string(), "+81312345678");
+        assert_eq!(phone.country(), None);
+    }
+
+    #[test]
+    fn test_phone_errors() {
+        assert_eq!(Phone::<RegionCH>::from_str("079 123 45 6x"), Err(PhoneError::InvalidFormat));
+        assert_eq!(Phone::<RegionCH>::from_str(""), Err(PhoneError::InvalidFormat));
+        assert_eq!(Phone::<RegionCH>::from_str("079 123 45 678"), Err(PhoneError::InvalidLength));
+        assert_eq!(Phone::<RegionCH>::from_str("+81 3"), Err(PhoneError::InvalidLength));
+        assert_eq!(Phone::<RegionCH>::from_str("000 123 45 67"), Err(PhoneError::InvalidNumber));
+    }
+}
@@ -1,15 +1,42 @@
-use std::cell::LazyCell;
-use regex::Regex;
-use super::{Field, FieldError, Rule};
+use super::{Constraints, Country, Field, FieldError, RegionalField, RegionalFieldError, RegionalRule, Rule};
 
 pub struct PostalCodeCHRule;
 
 impl Rule for PostalCodeCHRule {
-    const REGEX: LazyCell<Regex> =
-        LazyCell::new(|| Regex::new(r"^[0-9]{4}$").unwrap());
+    const CONSTRAINTS: Constraints = Constraints::new()
+        .pattern(r"^[0-9]{4}$")
+        .min_length(4)
+        .max_length(4)
+        .input_mode("numeric")
+        .autocomplete("postal-code");
     const DEFAULT: &'static str = "6210";
     const ATTRIBUTES: &'static [(&'static str, &'static str)] = &[("type", "text")];
 }
 
 pub type PostalCodeCH = Field<PostalCodeCHRule>;
-pub type PostalCodeCHError = FieldError<PostalCodeCHRule>;
\ No newline at end of file
+pub type PostalCodeCHError = FieldError<PostalCodeCHRule>;
+
+/// Postal codes of the current region, see `RegionalField`.
+pub struct PostalCodeRule;
+
+impl RegionalRule for PostalCodeRule {
+    fn pattern(country: Country) -> Option<&'static str> {
+        Some(match country {
+            Country::AT | Country::CH => r"^[0-9]{4}$",
+            Country::LI => r"^94(8[5-9]|9[0-8])$",
+            Country::DE | Country::FR | Country::IT => r"^[0-9]{5}$",
+            Country::GB => r"^[A-Z]{1,2}[0-9][A-Z0-9]? ?[0-9][A-Z]{2}$",
+            Country::US => r"^[0-9]{5}(-[0-9]{4})?$",
+        })
+    }
+
+    fn normalize(input: &str) -> String {
+        input.trim().to_uppercase()
+    }
+
+    const DEFAULT: &'static str = "6210";
+    const ATTRIBUTES: &'static [(&'static str, &'static str)] = &[("type", "text"), ("autocomplete", "postal-code")];
+}
+
+pub type PostalCode = RegionalField<PostalCodeRule>;
+pub type PostalCodeError = RegionalFieldError<PostalCodeRule>;
@@ -1,28 +1,67 @@
+use std::{convert::Infallible, fmt::{self, Display, Debug}, marker::PhantomData, ops::Deref, str::FromStr};
 
-use std::{cell::LazyCell, convert::Infallible, fmt::{self, Display, Debug}, marker::PhantomData, ops::Deref, str::FromStr};
+use leptos::Attribute;
 
-use leptos::IntoAttribute;
-use regex::Regex;
-
-use super::Datatype;
+use super::{current_region, field::{is_match, rule_attributes}, Country, Datatype};
 
+/// A rule for values whose format depends on the region, such as postal codes or tax numbers.
 pub trait RegionalRule: 'static {
-    const REGEX: LazyCell<Regex>;
+    /// The regular expression that values of the given country must match,
+    /// or `None` if the country is not supported.
+    fn pattern(country: Country) -> Option<&'static str>;
+
+    /// Normalizes the input before it is validated, by default by trimming whitespace.
+    fn normalize(input: &str) -> String {
+        input.trim().to_owned()
+    }
+
     const DEFAULT: &'static str;
     const ATTRIBUTES: &'static [(&'static str, &'static str)] = &[("type", "text")];
 }
 
+/// A text field that is validated for the current region, see `RegionProvider`.
+/// Without a region, for example when server functions deserialize their arguments,
+/// values that are valid in any supported country are accepted.
+/// On the server, use `validate_in_region` to validate the submitted data for the region the user selected,
+/// or `RegionalField::check_region` to validate a single value for a specific country.
 pub struct RegionalField<R: RegionalRule> {
     value: String,
+    country: Option<Country>,
     _rule: PhantomData<R>,
 }
+
 pub enum RegionalFieldError<R: RegionalRule> {
     InvalidFormat(PhantomData<R>),
+    UnsupportedRegion(PhantomData<R>),
+}
+
+impl<R: RegionalRule> RegionalField<R> {
+    /// The country for which the value was validated, if it was validated for a region.
+    pub fn country(&self) -> Option<Country> {
+        self.country
+    }
+
+    /// Checks that the value is valid in the given country.
+    pub fn check_region(&self, country: Country) -> Result<(), RegionalFieldError<R>> {
+        validate_for::<R>(&self.value, country)
+    }
+}
+
+fn validate_for<R: RegionalRule>(value: &str, country: Country) -> Result<(), RegionalFieldError<R>> {
+    let pattern = R::pattern(country).ok_or(RegionalFieldError::UnsupportedRegion(PhantomData))?;
+    if is_match(pattern, value) {
+        Ok(())
+    } else {
+        Err(RegionalFieldError::InvalidFormat(PhantomData))
+    }
 }
 
 impl<R: RegionalRule> Debug for RegionalFieldError<R> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "FieldError({:?})", self)
+        match self {
+            Self::InvalidFormat(_) => write!(f, "InvalidFormat"),
+            Self::UnsupportedRegion(_) => write!(f, "UnsupportedRegion"),
+        }
     }
 }
 
@@ -30,6 +69,7 @@ impl<R: RegionalRule> Display for RegionalFieldError<R> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::InvalidFormat(_) => write!(f, "invalid format"),
+            Self::UnsupportedRegion(_) => write!(f, "unsupported region"),
         }
     }
 }
@@ -38,9 +78,7 @@ impl<R: RegionalRule> std::error::Error for RegionalFieldError<R> {}
 
 impl<R: RegionalRule> Clone for RegionalFieldError<R> {
     fn clone(&self) -> Self {
-        match self {
-            Self::InvalidFormat(_) => Self::InvalidFormat(PhantomData),
-        }
+        *self
     }
 }
 
@@ -48,7 +86,10 @@ impl<R: RegionalRule> Copy for RegionalFieldError<R> {}
 
 impl<R: RegionalRule> PartialEq for RegionalFieldError<R> {
     fn eq(&self, other: &Self) -> bool {
-        matches!(self, Self::InvalidFormat(_)) && matches!(other, Self::InvalidFormat(_))
+        matches!(
+            (self, other),
+            (Self::InvalidFormat(_), Self::InvalidFormat(_)) | (Self::UnsupportedRegion(_), Self::UnsupportedRegion(_))
+        )
     }
 }
 
@@ -65,18 +106,32 @@ impl<R: RegionalRule> Datatype for RegionalField<R> {
     type Error = RegionalFieldError<R>;
 
     fn validate(input: String) -> Result<RegionalField<R>, RegionalFieldError<R>> {
-        if !R::REGEX.is_match(&input) {
-            Err(RegionalFieldError::InvalidFormat(PhantomData))
-        } else {
-            Ok(RegionalField {
-                value: input,
-                _rule: PhantomData,
-            })
-        }
+        let value = R::normalize(&input);
+
+        let country = match current_region() {
+            Some(country) => {
+                validate_for::<R>(&value, country)?;
+                Some(country)
+            }
+            None => {
+                if !Country::ALL.iter().any(|country| validate_for::<R>(&value, *country).is_ok()) {
+                    return Err(RegionalFieldError::InvalidFormat(PhantomData));
+                }
+                None
+            }
+        };
+
+        Ok(RegionalField {
+            value,
+            country,
+            _rule: PhantomData,
+        })
     }
 
     fn attributes() -> Vec<(&'static str, leptos::Attribute)> {
-        vec![("type", "text".into_attribute())]
+        // The pattern depends on the region, which can change while the input is shown,
+        // so it is only checked when validating.
+        rule_attributes(R::ATTRIBUTES, vec![("required", Attribute::Bool(true))])
     }
 }
 
@@ -84,6 +139,7 @@ impl<R: RegionalRule> Clone for RegionalField<R> {
     fn clone(&self) -> Self {
         Self {
             value: self.value.clone(),
+            country: self.country,
             _rule: PhantomData,
         }
     }
@@ -99,7 +155,12 @@ impl<R: RegionalRule> Eq for RegionalField<R> {}
 
 impl<R: RegionalRule> Default for RegionalField<R> {
     fn default() -> Self {
-        Self::validate(R::DEFAULT.into()).unwrap()
+        // The default value is not valid in every region, so it is not validated for the current one.
+        Self {
+            value: R::normalize(R::DEFAULT),
+            country: None,
+            _rule: PhantomData,
+        }
     }
 }
 
@@ -111,7 +172,7 @@ impl<R: RegionalRule> Display for RegionalField<R> {
 
 impl<R: RegionalRule> Debug for RegionalField<R> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Field({:?})", self.value)
+        write!(f, "RegionalField({:?})", self.value)
     }
 }
 
@@ -123,9 +184,9 @@ impl<R: RegionalRule> Deref for RegionalField<R> {
     }
 }
 
-impl<R: RegionalRule> Into<String> for RegionalField<R> {
-    fn into(self) -> String {
-        self.value
+impl<R: RegionalRule> From<RegionalField<R>> for String {
+    fn from(field: RegionalField<R>) -> Self {
+        field.value
     }
 }
 
@@ -145,4 +206,62 @@ impl<'de, R: RegionalRule> serde::Deserialize<'de> for RegionalField<R> {
         let value = <Self as Datatype>::Inner::deserialize(deserializer)?;
         Self::validate(value).map_err(serde::de::Error::custom)
     }
-}
\ No newline at end of file
+}
+
+impl<R: RegionalRule> serde::Serialize for RegionalField<R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.value.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{validate_in_region, with_region, NationalId, NationalIdError, PostalCode, PostalCodeError};
+
+    #[test]
+    fn test_regional_field() {
+        let postal_code = with_region(Some(Country::CH), || PostalCode::from_str("8001")).unwrap();
+        assert_eq!(postal_code.country(), Some(Country::CH));
+        assert_eq!(with_region(Some(Country::DE), || PostalCode::from_str("8001")), Err(PostalCodeError::InvalidFormat(PhantomData)));
+        assert_eq!(&*with_region(Some(Country::GB), || PostalCode::from_str(" sw1a 1aa")).unwrap(), "SW1A 1AA");
+        assert_eq!(
+            with_region(Some(Country::LI), || NationalId::from_str("756.1234.5678.97")),
+            Err(NationalIdError::UnsupportedRegion(PhantomData))
+        );
+
+        // Without a region, values of any country are accepted.
+        let postal_code = PostalCode::from_str("10115").unwrap();
+        assert_eq!(postal_code.country(), None);
+        assert!(postal_code.check_region(Country::DE).is_ok());
+        assert!(postal_code.check_region(Country::CH).is_err());
+        assert!(PostalCode::from_str("ABC").is_err());
+    }
+
+    #[test]
+    fn test_validate_in_region() {
+        // Server functions deserialize without a region.
+        let postal_code: PostalCode = serde_json::from_str("\"10115\"").unwrap();
+        assert!(validate_in_region(&postal_code, Some(Country::CH)).is_err());
+        let postal_code = validate_in_region(&postal_code, Some(Country::DE)).unwrap();
+        assert_eq!(postal_code.country(), Some(Country::DE));
+    }
+
+    #[test]
+    fn test_default() {
+        // The default value is not valid in Germany, but must not panic there.
+        let national_id = with_region(Some(Country::DE), NationalId::default);
+        assert_eq!(&*national_id, "756.1234.5678.97");
+        assert_eq!(national_id.country(), None);
+    }
+
+    #[test]
+    fn test_country_from_locale() {
+        assert_eq!(Country::from_locale("de-CH"), Some(Country::CH));
+        assert_eq!(Country::from_locale("en_UK"), Some(Country::GB));
+        assert_eq!(Country::from_locale("de"), None);
+    }
+}
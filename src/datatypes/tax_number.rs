@@ -0,0 +1,32 @@
+use super::{Country, RegionalField, RegionalFieldError, RegionalRule};
+
+/// Tax numbers of the current region, see `RegionalField`.
+/// These are the enterprise identification number (UID) in Switzerland, the tax identification number in Germany,
+/// the tax number in Austria, the fiscal number in France, the fiscal code or VAT number in Italy,
+/// the unique taxpayer reference in the UK and the employer identification number or SSN in the US.
+/// Liechtenstein is not supported.
+pub struct TaxNumberRule;
+
+impl RegionalRule for TaxNumberRule {
+    fn pattern(country: Country) -> Option<&'static str> {
+        match country {
+            Country::AT => Some(r"^[0-9]{2}-?[0-9]{3}/?[0-9]{4}$"),
+            Country::CH => Some(r"^CHE-?[0-9]{3}\.?[0-9]{3}\.?[0-9]{3}( ?(MWST|TVA|IVA))?$"),
+            Country::DE => Some(r"^[1-9][0-9]{10}$"),
+            Country::FR => Some(r"^[0-3][0-9]{12}$"),
+            Country::GB => Some(r"^[0-9]{10}$"),
+            Country::IT => Some(r"^([0-9]{11}|[A-Z]{6}[0-9LMNPQRSTUV]{2}[ABCDEHLMPRST][0-9LMNPQRSTUV]{2}[A-Z][0-9LMNPQRSTUV]{3}[A-Z])$"),
+            Country::US => Some(r"^([0-9]{2}-?[0-9]{7}|[0-8][0-9]{2}-?[0-9]{2}-?[0-9]{4})$"),
+            Country::LI => None,
+        }
+    }
+
+    fn normalize(input: &str) -> String {
+        input.trim().to_uppercase()
+    }
+
+    const DEFAULT: &'static str = "CHE-123.456.789";
+}
+
+pub type TaxNumber = RegionalField<TaxNumberRule>;
+pub type TaxNumberError = RegionalFieldError<TaxNumberRule>;
@@ -1,4 +1,4 @@
-use crate::{Data, FormData, QueryString, QueryStringPart};
+use crate::{use_region, with_region, Data, FormData, QueryString, QueryStringPart, RenderContext};
 use leptos::*;
 use serde::{de::DeserializeOwned, Serialize};
 use std::{collections::HashMap, ops::Deref, str::FromStr, sync::atomic::AtomicU64};
@@ -150,10 +150,18 @@ impl InputContext {
         expect_context::<FormData>().set(qs, Data::new_input(value.to_string()));
     }
 
+    /// The parsed value, validated for the region of the closest `RegionProvider`.
+    /// The form data of a `RenderContext` was serialized on the server, so it is parsed without a region.
     pub fn value<T: FromStr>(&self) -> Signal<Result<T, T::Err>> {
         let self_signal = self.raw_value();
+        let region = if use_context::<RenderContext>().is_some() {
+            Signal::derive(|| None)
+        } else {
+            use_region()
+        };
         Signal::derive(move || {
-            T::from_str(&self_signal.get())
+            let raw_value = self_signal.get();
+            with_region(region.get(), || T::from_str(&raw_value))
         })
     }
 
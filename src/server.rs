@@ -1,5 +1,9 @@
 mod file_store;
 mod pdf_gen;
+mod content_sniffing;
+mod assets;
 
 pub use file_store::*;
 pub use pdf_gen::*;
+pub use assets::*;
+pub(crate) use content_sniffing::*;
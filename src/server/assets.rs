@@ -0,0 +1,90 @@
+use std::{io, path::Path};
+
+use crate::{ICONS_CSS_PATH, ICONS_FONT_PATH, PAGED_JS_PATH};
+
+/// The location of the paged.js polyfill in the `pagedjs` npm package.
+const PAGED_JS_SOURCE: &str = "pagedjs/dist/paged.polyfill.js";
+/// The location of the icon font in the `material-symbols` npm package.
+const ICONS_FONT_SOURCE: &str = "material-symbols/material-symbols-rounded.woff2";
+
+/// Loads the icon font with the same settings as the Google Fonts stylesheet used before.
+const ICONS_CSS: &str = r#"@font-face {
+  font-family: 'Material Symbols Rounded';
+  font-style: normal;
+  font-weight: 400;
+  font-display: block;
+  src: url(material-symbols-rounded.woff2) format('woff2');
+}
+
+.material-symbols-rounded {
+  font-family: 'Material Symbols Rounded';
+  font-weight: normal;
+  font-style: normal;
+  font-size: 24px;
+  line-height: 1;
+  letter-spacing: normal;
+  text-transform: none;
+  display: inline-block;
+  white-space: nowrap;
+  word-wrap: normal;
+  direction: ltr;
+  font-feature-settings: 'liga';
+  font-variation-settings: 'FILL' 1, 'wght' 400, 'GRAD' 0, 'opsz' 24;
+  -webkit-font-smoothing: antialiased;
+}
+"#;
+
+/// Copies the paged.js polyfill and the icon font into the site root,
+/// so that the preview and the PDF generation work without internet access.
+/// The files are taken from the `pagedjs` and `material-symbols` npm packages in `node_modules`,
+/// for example installed with `npm install pagedjs material-symbols`.
+/// Call this when the server starts, before serving the site root,
+/// or at build time if the server is deployed without `node_modules`.
+/// Enable the `cdn` feature to load the assets from public CDNs instead.
+pub async fn install_assets<P: AsRef<Path>, Q: AsRef<Path>>(site_root: P, node_modules: Q) -> io::Result<()> {
+    let site_root = site_root.as_ref();
+    let node_modules = node_modules.as_ref();
+
+    for (source, target) in [(PAGED_JS_SOURCE, PAGED_JS_PATH), (ICONS_FONT_SOURCE, ICONS_FONT_PATH)] {
+        let source = node_modules.join(source);
+        let target = site_root.join(target);
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::copy(&source, &target).await.map_err(|err| {
+            io::Error::new(err.kind(), format!("could not copy {}: {err}", source.display()))
+        })?;
+    }
+
+    tokio::fs::write(site_root.join(ICONS_CSS_PATH), ICONS_CSS).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_install_assets() {
+        let dir = std::env::temp_dir().join(format!("nova-forms-test-{}", Uuid::new_v4()));
+        let node_modules = dir.join("node_modules");
+        let site_root = dir.join("site");
+
+        // Fails with a helpful error if the npm packages are missing.
+        let err = install_assets(&site_root, &node_modules).await.unwrap_err();
+        assert!(err.to_string().contains("paged.polyfill.js"));
+
+        for source in [PAGED_JS_SOURCE, ICONS_FONT_SOURCE] {
+            let source = node_modules.join(source);
+            std::fs::create_dir_all(source.parent().unwrap()).unwrap();
+            std::fs::write(source, "asset").unwrap();
+        }
+        install_assets(&site_root, &node_modules).await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(site_root.join(PAGED_JS_PATH)).unwrap(), "asset");
+        assert_eq!(std::fs::read_to_string(site_root.join(ICONS_FONT_PATH)).unwrap(), "asset");
+        assert!(std::fs::read_to_string(site_root.join(ICONS_CSS_PATH)).unwrap().contains("material-symbols-rounded.woff2"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
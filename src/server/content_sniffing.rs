@@ -0,0 +1,140 @@
+use crate::UploadError;
+
+/// Magic bytes of the file types that can be recognized by their contents.
+/// Each entry consists of the offset of the signature, the signature and the MIME type.
+const SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xFF\xD8\xFF", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (8, b"WEBP", "image/webp"),
+    (0, b"II*\x00", "image/tiff"),
+    (0, b"MM\x00*", "image/tiff"),
+    (4, b"ftypheic", "image/heic"),
+    (4, b"ftypheix", "image/heic"),
+    (4, b"ftypmif1", "image/heif"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1", "application/x-ole-storage"),
+];
+
+/// Types that are stored in a container format and can therefore only be recognized as the container.
+const CONTAINERS: &[(&str, &str)] = &[
+    ("application/zip", "application/vnd.openxmlformats-officedocument."),
+    ("application/zip", "application/vnd.oasis.opendocument."),
+    ("application/zip", "application/epub+zip"),
+    ("application/zip", "application/x-zip-compressed"),
+    ("application/x-ole-storage", "application/msword"),
+    ("application/x-ole-storage", "application/vnd.ms-"),
+];
+
+/// Returns the MIME type of the data based on its magic bytes, if it can be recognized.
+pub(crate) fn sniff_content_type(data: &[u8]) -> Option<&'static str> {
+    SIGNATURES
+        .iter()
+        .find(|(offset, signature, _)| data.get(*offset..offset + signature.len()) == Some(signature))
+        .map(|(_, _, content_type)| *content_type)
+        .or_else(|| is_bmp(data).then_some("image/bmp"))
+}
+
+/// Bitmaps only start with `BM`, which text files may start with as well,
+/// so the reserved fields and the size of the DIB header that follow are checked too.
+fn is_bmp(data: &[u8]) -> bool {
+    const DIB_HEADER_SIZES: &[u32] = &[12, 40, 52, 56, 64, 108, 124];
+
+    let Some(header) = data.get(..18) else {
+        return false;
+    };
+    let dib_header_size = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+    header.starts_with(b"BM") && header[6..10] == [0; 4] && DIB_HEADER_SIZES.contains(&dib_header_size)
+}
+
+/// Returns `true` if files of this content type can be recognized by their magic bytes.
+fn is_sniffable(content_type: &str) -> bool {
+    SIGNATURES.iter().any(|(_, _, t)| *t == content_type)
+        || content_type == "image/bmp"
+        || CONTAINERS.iter().any(|(_, t)| content_type.starts_with(t))
+}
+
+fn normalize(content_type: &str) -> String {
+    let content_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+
+    match content_type.as_str() {
+        "image/jpg" | "image/pjpeg" => "image/jpeg".to_owned(),
+        "application/x-pdf" => "application/pdf".to_owned(),
+        _ => content_type,
+    }
+}
+
+/// Determines the content type of an uploaded file from its contents.
+/// The content type declared by the browser is only used if the contents cannot be recognized
+/// and the declared type is not one that could have been recognized.
+pub(crate) fn verify_content_type(file_name: &str, declared: Option<&str>, data: &[u8]) -> Result<Option<String>, UploadError> {
+    let declared = declared
+        .map(normalize)
+        .filter(|t| !t.is_empty() && t != "application/octet-stream");
+    let sniffed = sniff_content_type(data);
+
+    match (declared, sniffed) {
+        (Some(declared), Some(sniffed)) if declared == sniffed => Ok(Some(declared)),
+        (Some(declared), Some(sniffed)) => {
+            if CONTAINERS.iter().any(|(container, t)| *container == sniffed && declared.starts_with(t)) {
+                Ok(Some(declared))
+            } else {
+                Err(UploadError::ContentMismatch { file_name: file_name.to_owned() })
+            }
+        }
+        (None, Some(sniffed)) => Ok(Some(sniffed.to_owned())),
+        (Some(declared), None) if is_sniffable(&declared) => {
+            Err(UploadError::ContentMismatch { file_name: file_name.to_owned() })
+        }
+        (declared, None) => Ok(declared),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_content_type() {
+        assert_eq!(sniff_content_type(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n\0\0"), Some("image/png"));
+        assert_eq!(sniff_content_type(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_content_type(b"BM\x36\0\0\0\0\0\0\0\x36\0\0\0\x28\0\0\0"), Some("image/bmp"));
+        assert_eq!(sniff_content_type(b"hello world"), None);
+        assert_eq!(sniff_content_type(b""), None);
+    }
+
+    #[test]
+    fn test_verify_content_type() {
+        assert_eq!(verify_content_type("a.pdf", Some("application/pdf"), b"%PDF-1.7"), Ok(Some("application/pdf".to_owned())));
+        assert_eq!(verify_content_type("a.jpg", Some("image/jpg"), b"\xFF\xD8\xFF\xE0"), Ok(Some("image/jpeg".to_owned())));
+        assert_eq!(verify_content_type("a.pdf", None, b"%PDF-1.7"), Ok(Some("application/pdf".to_owned())));
+        assert_eq!(verify_content_type("a.txt", Some("text/plain"), b"hello"), Ok(Some("text/plain".to_owned())));
+        // Text that starts like a bitmap is not mistaken for one.
+        assert_eq!(verify_content_type("cars.csv", Some("text/csv"), b"BMW,320i,2019\nAudi,A4,2020\n"), Ok(Some("text/csv".to_owned())));
+        assert_eq!(verify_content_type("bmi.txt", Some("text/plain"), b"BMI"), Ok(Some("text/plain".to_owned())));
+        assert_eq!(
+            verify_content_type(
+                "a.docx",
+                Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+                b"PK\x03\x04"
+            ),
+            Ok(Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document".to_owned()))
+        );
+        assert_eq!(
+            verify_content_type("a.pdf", Some("application/pdf"), b"MZ\x90\x00"),
+            Err(UploadError::ContentMismatch { file_name: "a.pdf".to_owned() })
+        );
+        assert_eq!(
+            verify_content_type("a.png", Some("image/png"), b"%PDF-1.7"),
+            Err(UploadError::ContentMismatch { file_name: "a.png".to_owned() })
+        );
+    }
+}
@@ -1,75 +1,871 @@
+mod sqlite_storage;
+mod local_storage;
+mod s3_storage;
+mod migrations;
+mod retention;
+mod access;
+mod integrity;
+mod encryption;
+mod scanner;
+mod cached_documents;
+
+pub use sqlite_storage::*;
+pub use local_storage::*;
+pub use s3_storage::*;
+pub use retention::*;
+pub use access::*;
+pub use integrity::*;
+pub use encryption::*;
+pub use scanner::*;
+
 use crate::{FileId, FileInfo};
-use sqlx::{sqlite::SqliteConnectOptions, Error, SqlitePool};
-use std::str::FromStr;
+use futures::{future::BoxFuture, stream::BoxStream, StreamExt};
+use sha2::{Digest, Sha256};
+use sqlx::{sqlite::{SqliteConnectOptions, SqlitePoolOptions}, SqlitePool};
+use std::{str::FromStr, sync::Arc};
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// A backend that stores the contents of files.
+/// The metadata of the files is always kept in the SQLite database of the `FileStore`,
+/// the backend only has to store and retrieve the raw bytes under a given key.
+pub trait FileStorage: Send + Sync + 'static {
+    /// Stores the data under the given key, overwriting any existing data.
+    fn put<'a>(&'a self, key: &'a str, data: Vec<u8>) -> BoxFuture<'a, Result<(), FileStoreError>>;
+
+    /// Loads the data stored under the given key.
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, FileStoreError>>;
+
+    /// Loads the data stored under the given key in chunks, so that large files are not held in memory.
+    /// The default implementation loads all data at once using `get`.
+    fn get_stream<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<FileStream>, FileStoreError>> {
+        Box::pin(async move { Ok(self.get(key).await?.map(single_chunk)) })
+    }
+
+    /// Deletes the data stored under the given key.
+    /// Deleting a key that does not exist is not an error.
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), FileStoreError>>;
+}
+
+/// The contents of a file, loaded in chunks.
+pub type FileStream = BoxStream<'static, Result<Vec<u8>, FileStoreError>>;
+
+/// A stream that yields all data in a single chunk.
+pub(crate) fn single_chunk(data: Vec<u8>) -> FileStream {
+    futures::stream::once(async move { Ok(data) }).boxed()
+}
 
 /// A database storage for files.
+/// The file metadata is stored in SQLite, the file contents are stored in a `FileStorage` backend.
 #[derive(Clone)]
 pub struct FileStore {
     pool: SqlitePool,
+    table: String,
+    keys_table: String,
+    stored_blobs_table: String,
+    cached_documents_table: String,
+    storage: Arc<dyn FileStorage>,
+    retention: RetentionPolicy,
+    access: Arc<dyn FileAccess>,
+    encryption: Option<Keyring>,
+    scanner: Option<Arc<dyn FileScanner>>,
+    blob_locks: Arc<BlobLocks>,
+}
+
+/// Serializes writing and deleting blobs with the same key,
+/// so that a blob that is released by one file is never deleted while another file writes it again.
+/// The locks only cover a single process, so `FileStore`s sharing a storage backend must not delete concurrently.
+#[derive(Default)]
+struct BlobLocks(std::sync::Mutex<std::collections::HashMap<String, std::sync::Weak<tokio::sync::Mutex<()>>>>);
+
+impl BlobLocks {
+    async fn lock(&self, blob_key: &str) -> tokio::sync::OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.0.lock().unwrap();
+            // Forget the locks that are not held anymore.
+            locks.retain(|_, lock| lock.strong_count() > 0);
+            match locks.get(blob_key).and_then(std::sync::Weak::upgrade) {
+                Some(lock) => lock,
+                None => {
+                    let lock = Arc::new(tokio::sync::Mutex::new(()));
+                    locks.insert(blob_key.to_owned(), Arc::downgrade(&lock));
+                    lock
+                }
+            }
+        };
+        lock.lock_owned().await
+    }
 }
 
 impl FileStore {
-    /// Tries to create a new `FileStore`.
-    pub async fn new() -> Result<Self, Error> {
-        let mut path = std::env::current_dir().unwrap();
-        path.push("data.db");
+    /// Tries to create a new `FileStore` with the default settings.
+    /// This stores metadata and file contents in `data.db` in the current working directory.
+    /// Use `FileStore::builder` to configure the database and storage backend.
+    pub async fn new() -> Result<Self, FileStoreError> {
+        Self::builder().build().await
+    }
+
+    /// Creates a builder to configure a new `FileStore`.
+    pub fn builder() -> FileStoreBuilder {
+        FileStoreBuilder::default()
+    }
+
+    /// Moves the inline file contents of a legacy `files` table into the storage backend.
+    /// Earlier versions stored the file contents in a `data` column of the metadata table.
+    async fn upgrade_legacy_table(&self) -> Result<(), FileStoreError> {
+        let table = &self.table;
+
+        let legacy: Option<(String,)> = sqlx::query_as(
+            r#"
+            SELECT name FROM pragma_table_info($1) WHERE name = 'data'
+        "#,
+        )
+        .bind(table)
+        .fetch_optional(&self.pool)
+        .await?;
 
-        println!("creating database under {}", path.display());
+        if legacy.is_none() {
+            return Ok(());
+        }
 
-        let options = SqliteConnectOptions::from_str(&format!("sqlite://{}", path.display()))?
-            .create_if_missing(true);
+        let records: Vec<(String, Vec<u8>)> = sqlx::query_as(&format!(
+            r#"
+            SELECT uuid, data
+            FROM {table}
+        "#
+        ))
+        .fetch_all(&self.pool)
+        .await?;
 
-        let pool = SqlitePool::connect_with(options).await?;
+        for (uuid, data) in records {
+            self.storage.put(&uuid, data).await?;
+        }
 
-        sqlx::query(
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::raw_sql(&format!(
             r#"
-            CREATE TABLE IF NOT EXISTS files (
+            CREATE TABLE {table}_upgraded (
                 uuid TEXT PRIMARY KEY,
                 file_name TEXT NOT NULL,
-                content_type TEXT,
-                data BLOB NOT NULL
-            )
-        "#,
-        )
-        .execute(&pool)
+                content_type TEXT
+            );
+            INSERT INTO {table}_upgraded (uuid, file_name, content_type)
+            SELECT uuid, file_name, content_type FROM {table};
+            DROP TABLE {table};
+            ALTER TABLE {table}_upgraded RENAME TO {table};
+        "#
+        ))
+        .execute(&mut *tx)
         .await?;
 
-        Ok(Self { pool })
+        tx.commit().await?;
+
+        Ok(())
     }
 
     /// Insert a file into the database, returns a corresponding `FileId`.
-    pub async fn insert(&self, file_info: FileInfo, data: Vec<u8>) -> Result<FileId, sqlx::Error> {
+    /// The file is orphaned until it is committed using `FileStore::commit`.
+    pub async fn insert(&self, file_info: FileInfo, data: Vec<u8>) -> Result<FileId, FileStoreError> {
+        self.insert_owned(file_info, data, None).await
+    }
+
+    /// Insert a file into the database and records its owner, see `FileAccess`.
+    /// Files with identical contents share the same blob in the storage backend.
+    /// If a `FileScanner` is configured and rejects the file, the file is kept in quarantine
+    /// and `FileStoreError::Quarantined` is returned.
+    pub async fn insert_owned(&self, file_info: FileInfo, data: Vec<u8>, owner: Option<String>) -> Result<FileId, FileStoreError> {
+        self.insert_upload(file_info, data, owner, None).await
+    }
+
+    /// Insert a file that was uploaded for a form field and records its owner, see `FileStore::insert_owned`.
+    /// The files of an owner and field are counted by `FileStore::pending_uploads`.
+    pub async fn insert_upload(
+        &self,
+        file_info: FileInfo,
+        data: Vec<u8>,
+        owner: Option<String>,
+        field: Option<String>,
+    ) -> Result<FileId, FileStoreError> {
         let id = FileId::new();
+        let size = data.len() as i64;
+        let hash = hex::encode(Sha256::digest(&data));
+        let blob_key = blob_key(&hash);
 
-        sqlx::query(
+        let scan_result = match &self.scanner {
+            Some(scanner) => scanner.scan(file_info.file_name(), &data).await?,
+            None => ScanResult::Clean,
+        };
+        let state = match scan_result {
+            ScanResult::Clean => FileState::Orphaned,
+            ScanResult::Rejected(_) => FileState::Quarantined,
+        };
+
+        // The row is inserted first, so that a concurrent delete of another file with the same contents
+        // sees this reference and keeps the blob.
+        sqlx::query(&format!(
             r#"
-            INSERT INTO files (uuid, file_name, content_type, data)
-            VALUES ($1, $2, $3, $4)
+            INSERT INTO {} (uuid, file_name, content_type, size, hash, created_at, state, owner, blob_key, field)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
         "#,
-        )
-        .bind(&id.to_string())
+            self.table
+        ))
+        .bind(id.to_string())
         .bind(file_info.file_name())
         .bind(file_info.content_type())
-        .bind(&data)
+        .bind(size)
+        .bind(hash)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .bind(state.to_string())
+        .bind(owner)
+        .bind(&blob_key)
+        .bind(field)
         .execute(&self.pool)
         .await?;
 
-        Ok(id)
+        // The blob is written by every upload until one write completed, so that concurrent uploads
+        // of identical contents never rely on a write that may still fail.
+        let _lock = self.blob_locks.lock(&blob_key).await;
+        if !self.is_blob_stored(&blob_key).await? {
+            if let Err(err) = self.write_blob(&blob_key, data).await {
+                self.delete_record(id).await?;
+                return Err(err);
+            }
+            self.mark_blob_stored(&blob_key).await?;
+        }
+
+        match scan_result {
+            ScanResult::Clean => Ok(id),
+            ScanResult::Rejected(reason) => Err(FileStoreError::Quarantined { id, reason }),
+        }
     }
 
-    /// Get a file from the database by its `FileId`.
-    pub async fn get(&self, id: FileId) -> Result<Option<(FileInfo, Vec<u8>)>, Error> {
-        let record: Option<(String, Option<String>, Vec<u8>)> = sqlx::query_as(
+    /// Returns the number and total size in bytes of the files that the owner uploaded for a field
+    /// and that were not committed yet.
+    pub async fn pending_uploads(&self, owner: &str, field: &str) -> Result<(usize, u64), FileStoreError> {
+        let (count, size): (i64, i64) = sqlx::query_as(&format!(
+            r#"
+            SELECT COUNT(*), COALESCE(SUM(size), 0)
+            FROM {}
+            WHERE owner = $1 AND field = $2 AND state = $3
+        "#,
+            self.table
+        ))
+        .bind(owner)
+        .bind(field)
+        .bind(FileState::Orphaned.to_string())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((count as usize, size as u64))
+    }
+
+    /// Returns whether the blob with the given key was completely written to the storage backend.
+    async fn is_blob_stored(&self, blob_key: &str) -> Result<bool, FileStoreError> {
+        let record: Option<(String,)> = sqlx::query_as(&format!(
             r#"
-            SELECT file_name, content_type, data
-            FROM files
+            SELECT blob_key
+            FROM {}
+            WHERE blob_key = $1
+        "#,
+            self.stored_blobs_table
+        ))
+        .bind(blob_key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.is_some())
+    }
+
+    async fn mark_blob_stored(&self, blob_key: &str) -> Result<(), FileStoreError> {
+        sqlx::query(&format!(
+            r#"
+            INSERT OR IGNORE INTO {} (blob_key)
+            VALUES ($1)
+        "#,
+            self.stored_blobs_table
+        ))
+        .bind(blob_key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Marks the blob with the given key as not stored if no file references it anymore.
+    /// Both is checked in a single statement, so that a concurrent upload of identical contents
+    /// either keeps the blob or writes it again.
+    /// Returns `true` if the blob can be deleted.
+    async fn release_blob(&self, blob_key: &str) -> Result<bool, FileStoreError> {
+        let result = sqlx::query(&format!(
+            r#"
+            DELETE FROM {}
+            WHERE blob_key = $1 AND NOT EXISTS (SELECT 1 FROM {} WHERE blob_key = $1)
+        "#,
+            self.stored_blobs_table, self.table
+        ))
+        .bind(blob_key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns the key under which the contents of a file are stored.
+    async fn blob_key_of(&self, id: FileId) -> Result<Option<String>, FileStoreError> {
+        let record: Option<(String,)> = sqlx::query_as(&format!(
+            r#"
+            SELECT blob_key
+            FROM {}
             WHERE uuid = $1
         "#,
-        )
+            self.table
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|(blob_key,)| blob_key))
+    }
+
+    async fn delete_record(&self, id: FileId) -> Result<bool, FileStoreError> {
+        let result = sqlx::query(&format!(
+            r#"
+            DELETE FROM {}
+            WHERE uuid = $1
+        "#,
+            self.table
+        ))
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The policy that decides who may download files.
+    pub fn access(&self) -> &dyn FileAccess {
+        self.access.as_ref()
+    }
+
+    /// Get a file from the database by its `FileId`.
+    /// Files in quarantine are never returned.
+    pub async fn get(&self, id: FileId) -> Result<Option<(FileInfo, Vec<u8>)>, FileStoreError> {
+        let record: Option<(String, Option<String>, String)> = sqlx::query_as(&format!(
+            r#"
+            SELECT file_name, content_type, blob_key
+            FROM {}
+            WHERE uuid = $1 AND state != $2
+        "#,
+            self.table
+        ))
+        .bind(id.to_string())
+        .bind(FileState::Quarantined.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some((file_name, content_type, blob_key)) = record else {
+            return Ok(None);
+        };
+
+        let data = self.read_blob(&blob_key).await?;
+
+        Ok(data.map(|data| {
+            let file_info = FileInfo::new(file_name, content_type).with_size(data.len() as u64);
+            (file_info, data)
+        }))
+    }
+
+    /// Get a file from the database by its `FileId` and load its contents in chunks, see `FileStorage::get_stream`.
+    /// Encrypted files are decrypted as a whole, as they are sealed in one piece.
+    /// Files in quarantine are never returned.
+    pub async fn get_stream(&self, id: FileId) -> Result<Option<(FileInfo, FileStream)>, FileStoreError> {
+        let record: Option<(String, Option<String>, Option<i64>, String)> = sqlx::query_as(&format!(
+            r#"
+            SELECT file_name, content_type, size, blob_key
+            FROM {}
+            WHERE uuid = $1 AND state != $2
+        "#,
+            self.table
+        ))
         .bind(id.to_string())
+        .bind(FileState::Quarantined.to_string())
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(record.map(|r| (FileInfo::new(r.0, r.1), r.2)))
+        let Some((file_name, content_type, size, blob_key)) = record else {
+            return Ok(None);
+        };
+
+        let stream = self.read_blob_stream(&blob_key).await?;
+
+        Ok(stream.map(|stream| {
+            let file_info = FileInfo::new(file_name, content_type);
+            let file_info = match size {
+                Some(size) => file_info.with_size(size as u64),
+                None => file_info,
+            };
+            (file_info, stream)
+        }))
+    }
+
+    /// Get the metadata of a file by its `FileId`, without loading its contents.
+    pub async fn info(&self, id: FileId) -> Result<Option<FileInfo>, FileStoreError> {
+        let record: Option<(String, Option<String>, Option<i64>)> = sqlx::query_as(&format!(
+            r#"
+            SELECT file_name, content_type, size
+            FROM {}
+            WHERE uuid = $1 AND state != $2
+        "#,
+            self.table
+        ))
+        .bind(id.to_string())
+        .bind(FileState::Quarantined.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|(file_name, content_type, size)| {
+            let file_info = FileInfo::new(file_name, content_type);
+            match size {
+                Some(size) => file_info.with_size(size as u64),
+                None => file_info,
+            }
+        }))
+    }
+
+    /// Deletes a file and its contents.
+    /// The contents are kept as long as other files with identical contents exist.
+    /// Returns `false` if the file did not exist.
+    pub async fn delete(&self, id: FileId) -> Result<bool, FileStoreError> {
+        let Some(blob_key) = self.blob_key_of(id).await? else {
+            return Ok(false);
+        };
+
+        // The record is deleted first, so that a failure leaves an unreferenced blob behind
+        // instead of a file without contents.
+        let deleted = self.delete_record(id).await?;
+
+        // A concurrent upload of identical contents writes the blob again only after it was deleted.
+        let _lock = self.blob_locks.lock(&blob_key).await;
+        if self.release_blob(&blob_key).await? {
+            self.delete_blob(&blob_key).await?;
+        }
+
+        Ok(deleted)
+    }
+}
+
+/// Blobs are addressed by the SHA-256 hash of their contents, so that identical contents are stored only once.
+fn blob_key(hash: &str) -> String {
+    format!("sha256-{hash}")
+}
+
+enum Database {
+    Url(String),
+    Pool(SqlitePool),
+}
+
+/// A builder to configure a `FileStore`.
+/// By default, the metadata and the file contents are stored in `data.db` in the current working directory.
+pub struct FileStoreBuilder {
+    database: Database,
+    pool_options: SqlitePoolOptions,
+    table_prefix: String,
+    storage: Option<Arc<dyn FileStorage>>,
+    retention: RetentionPolicy,
+    access: Arc<dyn FileAccess>,
+    encryption: Option<Keyring>,
+    scanner: Option<Arc<dyn FileScanner>>,
+}
+
+impl Default for FileStoreBuilder {
+    fn default() -> Self {
+        Self {
+            database: Database::Url("sqlite://data.db".to_owned()),
+            pool_options: SqlitePoolOptions::new(),
+            table_prefix: String::new(),
+            storage: None,
+            retention: RetentionPolicy::default(),
+            access: Arc::new(DenyAll),
+            encryption: None,
+            scanner: None,
+        }
+    }
+}
+
+impl FileStoreBuilder {
+    /// Connects to the database with the given URL, for example `sqlite:///var/lib/forms/files.db`.
+    /// The database is created if it does not exist yet.
+    pub fn database_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.database = Database::Url(url.into());
+        self
+    }
+
+    /// Uses an existing connection pool instead of connecting to a database URL.
+    pub fn pool(mut self, pool: SqlitePool) -> Self {
+        self.database = Database::Pool(pool);
+        self
+    }
+
+    /// The options used to create the connection pool when connecting to a database URL.
+    pub fn pool_options(mut self, pool_options: SqlitePoolOptions) -> Self {
+        self.pool_options = pool_options;
+        self
+    }
+
+    /// A prefix that is added to the names of all tables created by the `FileStore`.
+    /// This is useful when sharing a database with other parts of an application.
+    /// The prefix may only contain ASCII letters, digits and underscores.
+    pub fn table_prefix<S: Into<String>>(mut self, table_prefix: S) -> Self {
+        self.table_prefix = table_prefix.into();
+        self
+    }
+
+    /// The backend in which the file contents are stored.
+    /// By default, file contents are stored in the same database as the metadata.
+    pub fn storage<S: FileStorage>(mut self, storage: S) -> Self {
+        self.storage = Some(Arc::new(storage));
+        self
+    }
+
+    /// Defines how long uploaded files are kept, see `FileStore::sweep`.
+    pub fn retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Decides who may download files, see `FileStore::download`.
+    /// By default, files cannot be downloaded at all.
+    pub fn access<A: FileAccess>(mut self, access: A) -> Self {
+        self.access = Arc::new(access);
+        self
+    }
+
+    /// Encrypts the contents of new files with a random data key, which is in turn encrypted with
+    /// the current master key of the keyring.
+    /// Files with identical contents are stored once and share their data key, see `Keyring`.
+    /// Files that were stored before encryption was enabled can be encrypted using `FileStore::rotate_keys`.
+    pub fn encryption(mut self, keyring: Keyring) -> Self {
+        self.encryption = Some(keyring);
+        self
+    }
+
+    /// Scans all files before they are stored, see `FileScanner`.
+    pub fn scanner<S: FileScanner>(mut self, scanner: S) -> Self {
+        self.scanner = Some(Arc::new(scanner));
+        self
+    }
+
+    /// Connects to the database, applies pending schema migrations and creates the `FileStore`.
+    pub async fn build(self) -> Result<FileStore, FileStoreError> {
+        let prefix = self.table_prefix;
+        if !prefix.is_empty() {
+            validate_identifier(&prefix)?;
+        }
+
+        let pool = match self.database {
+            Database::Url(url) => {
+                let options = SqliteConnectOptions::from_str(&url)?.create_if_missing(true);
+                self.pool_options.connect_with(options).await?
+            }
+            Database::Pool(pool) => pool,
+        };
+
+        let storage = match self.storage {
+            Some(storage) => storage,
+            None => Arc::new(SqliteStorage::with_table(pool.clone(), format!("{prefix}file_blobs")).await?),
+        };
+
+        let file_store = FileStore {
+            pool,
+            table: format!("{prefix}files"),
+            keys_table: format!("{prefix}file_keys"),
+            stored_blobs_table: format!("{prefix}stored_blobs"),
+            cached_documents_table: format!("{prefix}cached_documents"),
+            storage,
+            retention: self.retention,
+            access: self.access,
+            encryption: self.encryption,
+            scanner: self.scanner,
+            blob_locks: Default::default(),
+        };
+
+        file_store.upgrade_legacy_table().await?;
+        migrations::migrate(&file_store.pool, &prefix).await?;
+
+        Ok(file_store)
+    }
+}
+
+/// Table names are interpolated into queries, so only allow a safe subset of characters.
+pub(crate) fn validate_identifier(identifier: &str) -> Result<(), FileStoreError> {
+    if identifier.is_empty() || !identifier.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(FileStoreError::StorageError(format!("invalid table name {identifier}")));
+    }
+    Ok(())
+}
+
+/// Possible errors that can occur when storing or loading files.
+#[derive(Error, Debug)]
+pub enum FileStoreError {
+    #[error("Database Error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("HTTP Error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("Storage Error: {0}")]
+    StorageError(String),
+    #[error("Encryption Error: {0}")]
+    EncryptionError(String),
+    #[error("Decryption Error: the file was altered or encrypted with a different key")]
+    DecryptionError,
+    #[error("Scan Error: {0}")]
+    ScanError(String),
+    #[error("the file {id} was quarantined: {reason}")]
+    Quarantined { id: FileId, reason: String },
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) async fn memory_pool() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    pub(crate) async fn memory_file_store() -> FileStore {
+        FileStore::builder().pool(memory_pool().await).build().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_get() {
+        let file_store = memory_file_store().await;
+
+        let file_info = FileInfo::new("test.txt".to_owned(), Some("text/plain".to_owned()));
+        let id = file_store.insert(file_info, b"hello".to_vec()).await.unwrap();
+
+        let (file_info, data) = file_store.get(id).await.unwrap().unwrap();
+        assert_eq!(file_info.file_name(), "test.txt");
+        assert_eq!(file_info.content_type(), Some("text/plain"));
+        assert_eq!(data, b"hello");
+    }
+
+    /// A storage that fails the first write, to simulate an interrupted upload.
+    struct FlakyStorage {
+        inner: SqliteStorage,
+        failed: std::sync::atomic::AtomicBool,
+    }
+
+    impl FileStorage for FlakyStorage {
+        fn put<'a>(&'a self, key: &'a str, data: Vec<u8>) -> BoxFuture<'a, Result<(), FileStoreError>> {
+            Box::pin(async move {
+                if !self.failed.swap(true, std::sync::atomic::Ordering::SeqCst) {
+                    return Err(FileStoreError::StorageError("interrupted".to_owned()));
+                }
+                self.inner.put(key, data).await
+            })
+        }
+
+        fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, FileStoreError>> {
+            self.inner.get(key)
+        }
+
+        fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), FileStoreError>> {
+            self.inner.delete(key)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_insert() {
+        let file_store = memory_file_store().await;
+
+        let inserts = (0..4).map(|i| {
+            let file_info = FileInfo::new(format!("{i}.txt"), None);
+            file_store.insert(file_info, b"hello".to_vec())
+        });
+        let ids = futures::future::try_join_all(inserts).await.unwrap();
+
+        for id in ids {
+            assert_eq!(file_store.get(id).await.unwrap().unwrap().1, b"hello");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_insert_with_failed_write() {
+        let pool = memory_pool().await;
+        let storage = FlakyStorage {
+            inner: SqliteStorage::new(pool.clone()).await.unwrap(),
+            failed: Default::default(),
+        };
+        let file_store = FileStore::builder().pool(pool).storage(storage).build().await.unwrap();
+
+        let (first, second) = futures::join!(
+            file_store.insert(FileInfo::new("a.txt".to_owned(), None), b"hello".to_vec()),
+            file_store.insert(FileInfo::new("b.txt".to_owned(), None), b"hello".to_vec()),
+        );
+
+        // The failed upload is rejected, the other one still stores the contents itself.
+        assert!(first.is_err());
+        assert_eq!(file_store.get(second.unwrap()).await.unwrap().unwrap().1, b"hello");
+    }
+
+    /// A storage that pauses deletions until they are resumed.
+    struct PausedStorage {
+        inner: SqliteStorage,
+        deleting: Arc<tokio::sync::Notify>,
+        resume: Arc<tokio::sync::Notify>,
+    }
+
+    impl FileStorage for PausedStorage {
+        fn put<'a>(&'a self, key: &'a str, data: Vec<u8>) -> BoxFuture<'a, Result<(), FileStoreError>> {
+            self.inner.put(key, data)
+        }
+
+        fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, FileStoreError>> {
+            self.inner.get(key)
+        }
+
+        fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), FileStoreError>> {
+            Box::pin(async move {
+                self.deleting.notify_one();
+                self.resume.notified().await;
+                self.inner.delete(key).await
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_while_deleting() {
+        let pool = memory_pool().await;
+        let (deleting, resume) = (Arc::new(tokio::sync::Notify::new()), Arc::new(tokio::sync::Notify::new()));
+        let storage = PausedStorage {
+            inner: SqliteStorage::new(pool.clone()).await.unwrap(),
+            deleting: deleting.clone(),
+            resume: resume.clone(),
+        };
+        let file_store = FileStore::builder().pool(pool).storage(storage).build().await.unwrap();
+        let first = file_store.insert(FileInfo::new("a.txt".to_owned(), None), b"hello".to_vec()).await.unwrap();
+
+        // The identical contents are uploaded again while the blob of the first file is being deleted.
+        let delete = tokio::spawn({
+            let file_store = file_store.clone();
+            async move { file_store.delete(first).await }
+        });
+        deleting.notified().await;
+        let insert = tokio::spawn({
+            let file_store = file_store.clone();
+            async move { file_store.insert(FileInfo::new("b.txt".to_owned(), None), b"hello".to_vec()).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        resume.notify_one();
+
+        assert!(delete.await.unwrap().unwrap());
+        let second = insert.await.unwrap().unwrap();
+        assert_eq!(file_store.get(second).await.unwrap().unwrap().1, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_pending_uploads() {
+        let file_store = memory_file_store().await;
+        let upload = |field: &str, data: &[u8]| {
+            let file_info = FileInfo::new("test.txt".to_owned(), None);
+            file_store.insert_upload(file_info, data.to_vec(), Some("abc".to_owned()), Some(field.to_owned()))
+        };
+
+        let first = upload("documents", b"hello").await.unwrap();
+        upload("documents", b"world!").await.unwrap();
+        upload("photos", b"hello").await.unwrap();
+        assert_eq!(file_store.pending_uploads("abc", "documents").await.unwrap(), (2, 11));
+        assert_eq!(file_store.pending_uploads("xyz", "documents").await.unwrap(), (0, 0));
+
+        // Submitted files do not count towards the limits of the next upload.
+        file_store.commit(&[first]).await.unwrap();
+        assert_eq!(file_store.pending_uploads("abc", "documents").await.unwrap(), (1, 6));
+    }
+
+    #[tokio::test]
+    async fn test_get_missing() {
+        let file_store = memory_file_store().await;
+
+        assert!(file_store.get(FileId::new()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_table_prefix() {
+        let pool = memory_pool().await;
+        let file_store = FileStore::builder()
+            .pool(pool.clone())
+            .table_prefix("forms_")
+            .build()
+            .await
+            .unwrap();
+
+        let file_info = FileInfo::new("test.txt".to_owned(), None);
+        let id = file_store.insert(file_info, b"hello".to_vec()).await.unwrap();
+
+        let (size, hash): (i64, String) = sqlx::query_as("SELECT size, hash FROM forms_files WHERE uuid = $1")
+            .bind(id.to_string())
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(size, 5);
+        assert_eq!(hash, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM forms_file_blobs")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_table_prefix() {
+        let result = FileStore::builder()
+            .pool(memory_pool().await)
+            .table_prefix("files; DROP TABLE users; --")
+            .build()
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_legacy_table() {
+        let pool = memory_pool().await;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE files (
+                uuid TEXT PRIMARY KEY,
+                file_name TEXT NOT NULL,
+                content_type TEXT,
+                data BLOB NOT NULL
+            )
+        "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let id = FileId::new();
+        sqlx::query("INSERT INTO files (uuid, file_name, content_type, data) VALUES ($1, $2, $3, $4)")
+            .bind(id.to_string())
+            .bind("legacy.txt")
+            .bind(Option::<String>::None)
+            .bind(b"legacy".to_vec())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let file_store = FileStore::builder().pool(pool).build().await.unwrap();
+
+        let (file_info, data) = file_store.get(id).await.unwrap().unwrap();
+        assert_eq!(file_info.file_name(), "legacy.txt");
+        assert_eq!(data, b"legacy");
     }
 }
@@ -0,0 +1,351 @@
+use futures::future::BoxFuture;
+use http::{header, request::Parts, Response, StatusCode};
+use std::sync::Arc;
+
+use super::{single_chunk, FileState, FileStore, FileStoreError, FileStream};
+use crate::FileId;
+
+/// Decides who may download files from the `FileStore`.
+pub trait FileAccess: Send + Sync + 'static {
+    /// Identifies the owner of the files uploaded with this request, for example by a session cookie.
+    /// The owner is stored with the file and passed to `can_read`.
+    fn owner(&self, request: &Parts) -> Option<String>;
+
+    /// Returns whether this request may read the file with the given id and owner.
+    fn can_read<'a>(&'a self, request: &'a Parts, id: FileId, owner: Option<&'a str>) -> BoxFuture<'a, bool>;
+}
+
+/// Denies access to all files.
+/// This is the default, so that files are never served without an explicit access policy.
+pub struct DenyAll;
+
+impl FileAccess for DenyAll {
+    fn owner(&self, _request: &Parts) -> Option<String> {
+        None
+    }
+
+    fn can_read<'a>(&'a self, _request: &'a Parts, _id: FileId, _owner: Option<&'a str>) -> BoxFuture<'a, bool> {
+        Box::pin(async { false })
+    }
+}
+
+type IsAdmin = Arc<dyn Fn(&Parts) -> bool + Send + Sync>;
+
+/// Grants access to files uploaded with the same session cookie, and to admins.
+pub struct SessionAccess {
+    cookie: String,
+    is_admin: Option<IsAdmin>,
+}
+
+impl SessionAccess {
+    /// Identifies the uploader by the value of the cookie with the given name.
+    pub fn new<S: Into<String>>(cookie: S) -> Self {
+        Self {
+            cookie: cookie.into(),
+            is_admin: None,
+        }
+    }
+
+    /// Additionally grants access to all files if the given function returns `true` for a request.
+    pub fn admin<F: Fn(&Parts) -> bool + Send + Sync + 'static>(mut self, is_admin: F) -> Self {
+        self.is_admin = Some(Arc::new(is_admin));
+        self
+    }
+
+    fn session(&self, request: &Parts) -> Option<String> {
+        request
+            .headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(name, _)| *name == self.cookie)
+            .map(|(_, value)| value.to_owned())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+impl FileAccess for SessionAccess {
+    fn owner(&self, request: &Parts) -> Option<String> {
+        self.session(request)
+    }
+
+    fn can_read<'a>(&'a self, request: &'a Parts, _id: FileId, owner: Option<&'a str>) -> BoxFuture<'a, bool> {
+        Box::pin(async move {
+            let is_owner = owner.is_some() && self.session(request).as_deref() == owner;
+            is_owner || self.is_admin.as_ref().is_some_and(|is_admin| is_admin(request))
+        })
+    }
+}
+
+/// Content types that are safe to display in the browser instead of downloading them.
+const INLINE_TYPES: &[&str] = &["application/pdf", "image/png", "image/jpeg", "image/gif", "image/webp", "image/bmp"];
+
+impl FileStore {
+    /// Returns whether the request may read the file, according to the `FileAccess` policy of this `FileStore`.
+    pub async fn can_read(&self, request: &Parts, id: FileId) -> Result<bool, FileStoreError> {
+        match self.owner_of(id).await? {
+            Some(owner) => Ok(self.access.can_read(request, id, owner.as_deref()).await),
+            None => Ok(false),
+        }
+    }
+
+    /// Returns whether the request was made by the owner of the file, as identified by the `FileAccess` policy.
+    /// Files that were stored without an owner are not owned by any request.
+    pub async fn is_owner(&self, request: &Parts, id: FileId) -> Result<bool, FileStoreError> {
+        let owner = self.owner_of(id).await?.flatten();
+        Ok(owner.is_some() && owner == self.access.owner(request))
+    }
+
+    /// Returns the owner of the file, or `None` if the file does not exist or is in quarantine.
+    async fn owner_of(&self, id: FileId) -> Result<Option<Option<String>>, FileStoreError> {
+        let record: Option<(Option<String>,)> = sqlx::query_as(&format!(
+            r#"
+            SELECT owner
+            FROM {}
+            WHERE uuid = $1 AND state != $2
+        "#,
+            self.table
+        ))
+        .bind(id.to_string())
+        .bind(FileState::Quarantined.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|(owner,)| owner))
+    }
+
+    /// Handles a request to download a file and creates the response.
+    /// Images and PDFs are shown in the browser if the query string contains `inline`,
+    /// all other files are always downloaded.
+    /// Missing files and files the request may not read both result in `404 Not Found`,
+    /// so that the existence of a file is not revealed.
+    /// The contents are streamed from the `FileStorage`, see `FileStorage::get_stream`.
+    pub async fn download(&self, request: &Parts, id: FileId) -> Result<Response<FileStream>, FileStoreError> {
+        let file = if self.can_read(request, id).await? {
+            self.get_stream(id).await?
+        } else {
+            None
+        };
+
+        let Some((file_info, stream)) = file else {
+            return Ok(status_response(StatusCode::NOT_FOUND));
+        };
+
+        let content_type = file_info.content_type().unwrap_or("application/octet-stream");
+        let inline = request
+            .uri
+            .query()
+            .is_some_and(|query| query.split('&').any(|param| param == "inline"))
+            && INLINE_TYPES.contains(&content_type);
+
+        let mut response = Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CONTENT_DISPOSITION, content_disposition(file_info.file_name(), inline))
+            .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+            .header(header::CACHE_CONTROL, "private, no-store");
+        if let Some(size) = file_info.size() {
+            response = response.header(header::CONTENT_LENGTH, size);
+        }
+
+        response
+            .body(stream)
+            .map_err(|err| FileStoreError::StorageError(err.to_string()))
+    }
+
+    /// Handles a request to a route such as `/files/:id`, where the last segment of the path is the `FileId`,
+    /// see `FileStore::download`.
+    /// Errors are logged and answered with `500 Internal Server Error`, so this can be used as a route handler directly,
+    /// for example with axum:
+    ///
+    /// ```ignore
+    /// let app = Router::new()
+    ///     .route("/files/:id", get(|State(file_store): State<FileStore>, request: Request| async move {
+    ///         let (parts, _) = request.into_parts();
+    ///         file_store.serve_file(&parts).await.map(Body::from_stream)
+    ///     }))
+    ///     .with_state(file_store);
+    /// ```
+    pub async fn serve_file(&self, request: &Parts) -> Response<FileStream> {
+        let id = request
+            .uri
+            .path()
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .and_then(|segment| segment.parse::<FileId>().ok());
+
+        let Some(id) = id else {
+            return status_response(StatusCode::NOT_FOUND);
+        };
+
+        match self.download(request, id).await {
+            Ok(response) => response,
+            Err(err) => {
+                leptos::logging::error!("could not serve file {id}: {err}");
+                status_response(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
+
+/// Creates an empty response with the given status.
+fn status_response(status: StatusCode) -> Response<FileStream> {
+    Response::builder()
+        .status(status)
+        .body(single_chunk(Vec::new()))
+        .expect("response is valid")
+}
+
+/// Creates a `Content-Disposition` header value with an ASCII fallback and the UTF-8 encoded file name.
+fn content_disposition(file_name: &str, inline: bool) -> String {
+    let fallback = file_name
+        .chars()
+        .map(|c| if (c.is_ascii_graphic() || c == ' ') && c != '"' && c != '\\' { c } else { '_' })
+        .collect::<String>();
+    let encoded = percent_encoding::utf8_percent_encode(file_name, percent_encoding::NON_ALPHANUMERIC);
+
+    format!(
+        "{}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}",
+        if inline { "inline" } else { "attachment" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileInfo, server::file_store::tests::memory_pool};
+    use futures::TryStreamExt;
+    use http::Request;
+
+    fn request(uri: &str, cookie: Option<&str>) -> Parts {
+        let mut request = Request::builder().uri(uri);
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    async fn body(response: Response<FileStream>) -> Vec<u8> {
+        response.into_body().try_concat().await.unwrap()
+    }
+
+    async fn file_store() -> FileStore {
+        FileStore::builder()
+            .pool(memory_pool().await)
+            .access(SessionAccess::new("session").admin(|request| request.headers.contains_key("x-admin")))
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_session_access() {
+        let file_store = file_store().await;
+        let uploader = request("/", Some("theme=dark; session=abc"));
+        let file_info = FileInfo::new("test.txt".to_owned(), None);
+        let id = file_store
+            .insert_owned(file_info, b"hello".to_vec(), file_store.access.owner(&uploader))
+            .await
+            .unwrap();
+
+        assert!(file_store.can_read(&uploader, id).await.unwrap());
+        assert!(!file_store.can_read(&request("/", Some("session=xyz")), id).await.unwrap());
+        assert!(!file_store.can_read(&request("/", None), id).await.unwrap());
+
+        let mut admin = request("/", None);
+        admin.headers.insert("x-admin", "1".parse().unwrap());
+        assert!(file_store.can_read(&admin, id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_is_owner() {
+        let file_store = file_store().await;
+        let uploader = request("/", Some("session=abc"));
+        let file_info = FileInfo::new("test.txt".to_owned(), None);
+        let id = file_store
+            .insert_owned(file_info.clone(), b"hello".to_vec(), file_store.access.owner(&uploader))
+            .await
+            .unwrap();
+
+        assert!(file_store.is_owner(&uploader, id).await.unwrap());
+        assert!(!file_store.is_owner(&request("/", Some("session=xyz")), id).await.unwrap());
+        assert!(!file_store.is_owner(&uploader, FileId::new()).await.unwrap());
+
+        // Files without owner cannot be claimed by requests without session.
+        let id = file_store.insert(file_info, b"hello".to_vec()).await.unwrap();
+        assert!(!file_store.is_owner(&request("/", None), id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_download() {
+        let file_store = file_store().await;
+        let uploader = request("/files/1?inline", Some("session=abc"));
+        let file_info = FileInfo::new("Rechnung März.pdf".to_owned(), Some("application/pdf".to_owned()));
+        let id = file_store
+            .insert_owned(file_info, b"%PDF-1.7".to_vec(), file_store.access.owner(&uploader))
+            .await
+            .unwrap();
+
+        let response = file_store.download(&uploader, id).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/pdf");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "8");
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "inline; filename=\"Rechnung M_rz.pdf\"; filename*=UTF-8''Rechnung%20M%C3%A4rz%2Epdf"
+        );
+        assert_eq!(body(response).await, b"%PDF-1.7");
+
+        let response = file_store.download(&request("/files/1", Some("session=xyz")), id).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_serve_file() {
+        let file_store = file_store().await;
+        let uploader = request("/", Some("session=abc"));
+        let file_info = FileInfo::new("test.txt".to_owned(), None);
+        let id = file_store
+            .insert_owned(file_info, b"hello".to_vec(), file_store.access.owner(&uploader))
+            .await
+            .unwrap();
+
+        let response = file_store.serve_file(&request(&format!("/files/{id}"), Some("session=abc"))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).await, b"hello");
+
+        let response = file_store.serve_file(&request("/files/not-a-file", Some("session=abc"))).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_quarantined_files_cannot_be_read() {
+        let file_store = file_store().await;
+        let uploader = request("/", Some("session=abc"));
+        let file_info = FileInfo::new("test.txt".to_owned(), None);
+        let id = file_store
+            .insert_owned(file_info, b"hello".to_vec(), file_store.access.owner(&uploader))
+            .await
+            .unwrap();
+
+        sqlx::query("UPDATE files SET state = $1 WHERE uuid = $2")
+            .bind(FileState::Quarantined.to_string())
+            .bind(id.to_string())
+            .execute(&file_store.pool)
+            .await
+            .unwrap();
+
+        assert!(!file_store.can_read(&uploader, id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_deny_all_by_default() {
+        let file_store = FileStore::builder().pool(memory_pool().await).build().await.unwrap();
+        let file_info = FileInfo::new("test.txt".to_owned(), None);
+        let id = file_store.insert(file_info, b"hello".to_vec()).await.unwrap();
+
+        assert!(!file_store.can_read(&request("/", Some("session=abc")), id).await.unwrap());
+    }
+}
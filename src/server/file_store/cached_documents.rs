@@ -0,0 +1,116 @@
+use time::OffsetDateTime;
+
+use super::{FileStore, FileStoreError};
+
+impl FileStore {
+    /// Loads a cached document, such as a PDF cached by `PdfCache`.
+    pub(crate) async fn cached_document(&self, key: &str) -> Result<Option<Vec<u8>>, FileStoreError> {
+        let record: Option<(String,)> = sqlx::query_as(&format!(
+            r#"
+            SELECT key
+            FROM {}
+            WHERE key = $1
+        "#,
+            self.cached_documents_table
+        ))
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match record {
+            Some(_) => self.read_blob(key).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Caches a document under the given key, encrypted like files if a `Keyring` is configured.
+    /// Cached documents are purged by `FileStore::sweep` once the `RetentionPolicy::retention_period` expired.
+    pub(crate) async fn cache_document(&self, key: &str, data: Vec<u8>) -> Result<(), FileStoreError> {
+        // The blob is written first, so that a cached document never exists without its contents.
+        self.write_blob(key, data).await?;
+
+        sqlx::query(&format!(
+            r#"
+            INSERT OR REPLACE INTO {} (key, created_at)
+            VALUES ($1, $2)
+        "#,
+            self.cached_documents_table
+        ))
+        .bind(key)
+        .bind(OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Deletes a cached document, if any.
+    pub(crate) async fn delete_cached_document(&self, key: &str) -> Result<(), FileStoreError> {
+        sqlx::query(&format!(
+            r#"
+            DELETE FROM {}
+            WHERE key = $1
+        "#,
+            self.cached_documents_table
+        ))
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+
+        self.delete_blob(key).await
+    }
+
+    /// Deletes all cached documents that were cached before the given unix timestamp.
+    /// Returns the number of deleted documents.
+    pub(super) async fn sweep_cached_documents(&self, before: i64) -> Result<usize, FileStoreError> {
+        let records: Vec<(String,)> = sqlx::query_as(&format!(
+            r#"
+            SELECT key
+            FROM {}
+            WHERE created_at < $1
+        "#,
+            self.cached_documents_table
+        ))
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await?;
+
+        for (key,) in &records {
+            self.delete_cached_document(key).await?;
+        }
+
+        Ok(records.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{server::file_store::tests::memory_pool, Keyring, MasterKey};
+
+    #[tokio::test]
+    async fn test_cached_documents() {
+        let pool = memory_pool().await;
+        let file_store = FileStore::builder()
+            .pool(pool.clone())
+            .encryption(Keyring::new(MasterKey::new("1", [1; 32])))
+            .build()
+            .await
+            .unwrap();
+
+        file_store.cache_document("pdf-1", b"%PDF-1.7".to_vec()).await.unwrap();
+        assert_eq!(file_store.cached_document("pdf-1").await.unwrap().unwrap(), b"%PDF-1.7");
+        assert!(file_store.cached_document("pdf-2").await.unwrap().is_none());
+
+        // Cached documents are encrypted like files.
+        let (data,): (Vec<u8>,) = sqlx::query_as("SELECT data FROM file_blobs WHERE key = 'pdf-1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(!data.windows(8).any(|w| w == b"%PDF-1.7"));
+
+        assert_eq!(file_store.sweep_cached_documents(0).await.unwrap(), 0);
+        assert_eq!(file_store.sweep_cached_documents(i64::MAX).await.unwrap(), 1);
+        assert!(file_store.cached_document("pdf-1").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,348 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::fmt::Debug;
+
+use super::{single_chunk, FileStore, FileStoreError, FileStream};
+
+const NONCE_SIZE: usize = 12;
+
+/// A 256 bit key that encrypts the data keys of the files in the `FileStore`.
+#[derive(Clone)]
+pub struct MasterKey {
+    id: String,
+    key: Key<Aes256Gcm>,
+}
+
+impl MasterKey {
+    /// Creates a master key with the given id.
+    /// The id is stored with each encrypted data key, so that the master key can be found again after a rotation.
+    pub fn new<S: Into<String>>(id: S, key: [u8; 32]) -> Self {
+        Self {
+            id: id.into(),
+            key: key.into(),
+        }
+    }
+
+    /// Creates a master key from a base64 encoded key, for example read from an environment variable.
+    pub fn from_base64<S: Into<String>>(id: S, key: &str) -> Result<Self, FileStoreError> {
+        let key: [u8; 32] = STANDARD
+            .decode(key.trim())
+            .ok()
+            .and_then(|key| key.try_into().ok())
+            .ok_or_else(|| FileStoreError::EncryptionError("master key must be 32 base64 encoded bytes".to_owned()))?;
+
+        Ok(Self::new(id, key))
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Debug for MasterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MasterKey").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+/// The master keys used to encrypt the files in the `FileStore`.
+/// New files are encrypted with the current key, previous keys are only used to decrypt files
+/// until they were rotated using `FileStore::rotate_keys`.
+///
+/// Files with identical contents are stored only once, so the data key belongs to the stored contents rather than to a single file:
+/// all files with the same contents share one data key, and the key is deleted once the last of these files is deleted.
+/// Deleting a single file therefore does not crypto-shred its contents while other files with the same contents exist.
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    current: MasterKey,
+    previous: Vec<MasterKey>,
+}
+
+impl Keyring {
+    /// Creates a keyring that encrypts new files with the given master key.
+    pub fn new(current: MasterKey) -> Self {
+        Self {
+            current,
+            previous: Vec::new(),
+        }
+    }
+
+    /// Adds a master key that was used before, so that files encrypted with it can still be read.
+    pub fn previous(mut self, key: MasterKey) -> Self {
+        self.previous.push(key);
+        self
+    }
+
+    fn get(&self, id: &str) -> Option<&MasterKey> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|key| key.id == id)
+    }
+}
+
+/// Encrypts the plaintext and prepends the random nonce.
+fn seal(key: &Key<Aes256Gcm>, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, FileStoreError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(key)
+        .encrypt(&nonce, Payload { msg: plaintext, aad })
+        .map_err(|_| FileStoreError::EncryptionError("could not encrypt data".to_owned()))?;
+
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+/// Decrypts data that was encrypted with `seal`.
+fn open(key: &Key<Aes256Gcm>, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, FileStoreError> {
+    if sealed.len() < NONCE_SIZE {
+        return Err(FileStoreError::DecryptionError);
+    }
+
+    let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+    Aes256Gcm::new(key)
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| FileStoreError::DecryptionError)
+}
+
+impl FileStore {
+    /// Stores a blob, encrypted with a new data key if a `Keyring` is configured.
+    /// The data key is shared by all files with the same contents, see `Keyring`.
+    pub(super) async fn write_blob(&self, blob_key: &str, data: Vec<u8>) -> Result<(), FileStoreError> {
+        let Some(keyring) = &self.encryption else {
+            return self.storage.put(blob_key, data).await;
+        };
+
+        // The blob key is authenticated along with the data, so that blobs cannot be swapped.
+        let data_key = Aes256Gcm::generate_key(&mut OsRng);
+        let wrapped_key = seal(&keyring.current.key, &data_key, blob_key.as_bytes())?;
+
+        // The data key is stored first, so that an encrypted blob never exists without its key.
+        // Concurrent writes of the same blob all use the data key that was stored first,
+        // so the blob can be decrypted no matter which write completes last.
+        sqlx::query(&format!(
+            r#"
+            INSERT OR IGNORE INTO {} (blob_key, key_id, wrapped_key)
+            VALUES ($1, $2, $3)
+        "#,
+            self.keys_table
+        ))
+        .bind(blob_key)
+        .bind(&keyring.current.id)
+        .bind(wrapped_key)
+        .execute(&self.pool)
+        .await?;
+
+        let (key_id, wrapped_key) = self
+            .wrapped_key(blob_key)
+            .await?
+            .ok_or_else(|| FileStoreError::EncryptionError(format!("missing data key of {blob_key}")))?;
+        let data_key = open(&self.master_key(&key_id)?.key, &wrapped_key, blob_key.as_bytes())?;
+        let ciphertext = seal(Key::<Aes256Gcm>::from_slice(&data_key), &data, blob_key.as_bytes())?;
+
+        self.storage.put(blob_key, ciphertext).await
+    }
+
+    /// Loads a blob and decrypts it if it was stored encrypted.
+    pub(super) async fn read_blob(&self, blob_key: &str) -> Result<Option<Vec<u8>>, FileStoreError> {
+        let Some(data) = self.storage.get(blob_key).await? else {
+            return Ok(None);
+        };
+
+        let Some((key_id, wrapped_key)) = self.wrapped_key(blob_key).await? else {
+            // The blob was stored before encryption was enabled.
+            return Ok(Some(data));
+        };
+
+        let master_key = self.master_key(&key_id)?;
+        let data_key = open(&master_key.key, &wrapped_key, blob_key.as_bytes())?;
+        let data = open(Key::<Aes256Gcm>::from_slice(&data_key), &data, blob_key.as_bytes())?;
+
+        Ok(Some(data))
+    }
+
+    /// Loads a blob in chunks, unless it is encrypted.
+    pub(super) async fn read_blob_stream(&self, blob_key: &str) -> Result<Option<FileStream>, FileStoreError> {
+        if self.wrapped_key(blob_key).await?.is_some() {
+            return Ok(self.read_blob(blob_key).await?.map(single_chunk));
+        }
+
+        self.storage.get_stream(blob_key).await
+    }
+
+    /// Deletes a blob and its data key.
+    pub(super) async fn delete_blob(&self, blob_key: &str) -> Result<(), FileStoreError> {
+        self.storage.delete(blob_key).await?;
+
+        sqlx::query(&format!(
+            r#"
+            DELETE FROM {}
+            WHERE blob_key = $1
+        "#,
+            self.keys_table
+        ))
+        .bind(blob_key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn wrapped_key(&self, blob_key: &str) -> Result<Option<(String, Vec<u8>)>, FileStoreError> {
+        let record = sqlx::query_as(&format!(
+            r#"
+            SELECT key_id, wrapped_key
+            FROM {}
+            WHERE blob_key = $1
+        "#,
+            self.keys_table
+        ))
+        .bind(blob_key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    fn master_key(&self, id: &str) -> Result<&MasterKey, FileStoreError> {
+        self.encryption
+            .as_ref()
+            .ok_or_else(|| FileStoreError::EncryptionError("files are encrypted, but no keyring is configured".to_owned()))?
+            .get(id)
+            .ok_or_else(|| FileStoreError::EncryptionError(format!("unknown master key {id}")))
+    }
+
+    /// Re-encrypts all data keys that were encrypted with a previous master key using the current master key,
+    /// and encrypts all blobs that were stored before encryption was enabled.
+    /// Once this completed, previous master keys can be removed from the `Keyring`.
+    /// Returns the number of blobs that were updated.
+    pub async fn rotate_keys(&self) -> Result<usize, FileStoreError> {
+        let Some(keyring) = &self.encryption else {
+            return Err(FileStoreError::EncryptionError("no keyring is configured".to_owned()));
+        };
+        let mut rotated = 0;
+
+        let outdated: Vec<(String, String, Vec<u8>)> = sqlx::query_as(&format!(
+            r#"
+            SELECT blob_key, key_id, wrapped_key
+            FROM {}
+            WHERE key_id != $1
+        "#,
+            self.keys_table
+        ))
+        .bind(&keyring.current.id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        for (blob_key, key_id, wrapped_key) in outdated {
+            let data_key = open(&self.master_key(&key_id)?.key, &wrapped_key, blob_key.as_bytes())?;
+            let wrapped_key = seal(&keyring.current.key, &data_key, blob_key.as_bytes())?;
+
+            sqlx::query(&format!(
+                r#"
+                UPDATE {}
+                SET key_id = $1, wrapped_key = $2
+                WHERE blob_key = $3
+            "#,
+                self.keys_table
+            ))
+            .bind(&keyring.current.id)
+            .bind(wrapped_key)
+            .bind(&blob_key)
+            .execute(&self.pool)
+            .await?;
+
+            rotated += 1;
+        }
+
+        let plaintext: Vec<(String,)> = sqlx::query_as(&format!(
+            r#"
+            SELECT DISTINCT blob_key
+            FROM {}
+            WHERE blob_key NOT IN (SELECT blob_key FROM {})
+        "#,
+            self.table, self.keys_table
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        for (blob_key,) in plaintext {
+            if let Some(data) = self.storage.get(&blob_key).await? {
+                self.write_blob(&blob_key, data).await?;
+                rotated += 1;
+            }
+        }
+
+        Ok(rotated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileInfo, server::file_store::tests::memory_pool};
+    use sqlx::SqlitePool;
+
+    async fn build_file_store(pool: SqlitePool, keyring: Option<Keyring>) -> FileStore {
+        let builder = FileStore::builder().pool(pool);
+        match keyring {
+            Some(keyring) => builder.encryption(keyring),
+            None => builder,
+        }
+        .build()
+        .await
+        .unwrap()
+    }
+
+    async fn stored_blob(pool: &SqlitePool) -> Vec<u8> {
+        let (data,): (Vec<u8>,) = sqlx::query_as("SELECT data FROM file_blobs").fetch_one(pool).await.unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn test_encryption() {
+        let pool = memory_pool().await;
+        let file_store = build_file_store(pool.clone(), Some(Keyring::new(MasterKey::new("1", [1; 32])))).await;
+
+        let file_info = FileInfo::new("passport.pdf".to_owned(), None);
+        let id = file_store.insert(file_info, b"secret".to_vec()).await.unwrap();
+
+        assert!(!stored_blob(&pool).await.windows(6).any(|w| w == b"secret"));
+        assert_eq!(file_store.get(id).await.unwrap().unwrap().1, b"secret");
+
+        // Without the right master key, the file cannot be read.
+        let other = build_file_store(pool.clone(), Some(Keyring::new(MasterKey::new("2", [2; 32])))).await;
+        assert!(other.get(id).await.is_err());
+        let unencrypted = build_file_store(pool, None).await;
+        assert!(unencrypted.get(id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_keys() {
+        let pool = memory_pool().await;
+
+        let plaintext = build_file_store(pool.clone(), None).await;
+        let legacy = plaintext.insert(FileInfo::new("a.txt".to_owned(), None), b"legacy".to_vec()).await.unwrap();
+
+        let old = build_file_store(pool.clone(), Some(Keyring::new(MasterKey::new("old", [1; 32])))).await;
+        let id = old.insert(FileInfo::new("b.txt".to_owned(), None), b"hello".to_vec()).await.unwrap();
+        assert_eq!(old.get(legacy).await.unwrap().unwrap().1, b"legacy");
+
+        let keyring = Keyring::new(MasterKey::new("new", [2; 32])).previous(MasterKey::new("old", [1; 32]));
+        let rotated = build_file_store(pool.clone(), Some(keyring)).await;
+        assert_eq!(rotated.rotate_keys().await.unwrap(), 2);
+        assert_eq!(rotated.rotate_keys().await.unwrap(), 0);
+
+        // After the rotation, the previous key is not needed anymore.
+        let new = build_file_store(pool, Some(Keyring::new(MasterKey::new("new", [2; 32])))).await;
+        assert_eq!(new.get(id).await.unwrap().unwrap().1, b"hello");
+        assert_eq!(new.get(legacy).await.unwrap().unwrap().1, b"legacy");
+    }
+
+    #[test]
+    fn test_master_key_from_base64() {
+        assert!(MasterKey::from_base64("1", &STANDARD.encode([0; 32])).is_ok());
+        assert!(MasterKey::from_base64("1", &STANDARD.encode([0; 16])).is_err());
+        assert!(MasterKey::from_base64("1", "not base64").is_err());
+    }
+}
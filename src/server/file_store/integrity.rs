@@ -0,0 +1,165 @@
+use sha2::{Digest, Sha256};
+
+use super::{FileStore, FileStoreError};
+use crate::FileId;
+
+/// The result of checking the contents of a stored file against its hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integrity {
+    /// The contents match the hash recorded when the file was uploaded.
+    Intact,
+    /// The contents do not match the hash recorded when the file was uploaded.
+    Modified,
+    /// The contents are missing from the storage backend.
+    Missing,
+    /// No hash was recorded, because the file was uploaded by an earlier version.
+    Unknown,
+}
+
+impl FileStore {
+    /// Returns the hex encoded SHA-256 hash of the contents of a file, as recorded when it was uploaded.
+    pub async fn hash(&self, id: FileId) -> Result<Option<String>, FileStoreError> {
+        let record: Option<(Option<String>,)> = sqlx::query_as(&format!(
+            r#"
+            SELECT hash
+            FROM {}
+            WHERE uuid = $1
+        "#,
+            self.table
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.and_then(|(hash,)| hash))
+    }
+
+    /// Checks the stored contents of a file against the hash recorded when it was uploaded.
+    /// Returns `None` if the file does not exist.
+    pub async fn verify(&self, id: FileId) -> Result<Option<Integrity>, FileStoreError> {
+        let record: Option<(Option<String>, String)> = sqlx::query_as(&format!(
+            r#"
+            SELECT hash, blob_key
+            FROM {}
+            WHERE uuid = $1
+        "#,
+            self.table
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some((hash, blob_key)) = record else {
+            return Ok(None);
+        };
+
+        let data = match self.read_blob(&blob_key).await {
+            // Encrypted contents that fail to authenticate were altered.
+            Err(FileStoreError::DecryptionError) => return Ok(Some(Integrity::Modified)),
+            data => data?,
+        };
+
+        let integrity = match (hash, data) {
+            (_, None) => Integrity::Missing,
+            (None, Some(_)) => Integrity::Unknown,
+            (Some(hash), Some(data)) if hex::encode(Sha256::digest(&data)) == hash => Integrity::Intact,
+            (Some(_), Some(_)) => Integrity::Modified,
+        };
+
+        Ok(Some(integrity))
+    }
+
+    /// Checks all files of the `FileStore`, see `FileStore::verify`.
+    /// Returns the files whose integrity could not be confirmed.
+    pub async fn verify_all(&self) -> Result<Vec<(FileId, Integrity)>, FileStoreError> {
+        let records: Vec<(String,)> = sqlx::query_as(&format!(
+            r#"
+            SELECT uuid
+            FROM {}
+        "#,
+            self.table
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut failed = Vec::new();
+        for (uuid,) in records {
+            let id = uuid
+                .parse()
+                .map_err(|_| FileStoreError::StorageError(format!("invalid file id {uuid}")))?;
+            match self.verify(id).await? {
+                Some(Integrity::Intact) | None => {}
+                Some(integrity) => failed.push((id, integrity)),
+            }
+        }
+
+        Ok(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileInfo, server::file_store::tests::{memory_file_store, memory_pool}};
+
+    async fn blob_count(file_store: &FileStore) -> i64 {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM file_blobs")
+            .fetch_one(&file_store.pool)
+            .await
+            .unwrap();
+        count
+    }
+
+    #[tokio::test]
+    async fn test_deduplication() {
+        let file_store = memory_file_store().await;
+
+        let first = file_store.insert(FileInfo::new("a.txt".to_owned(), None), b"hello".to_vec()).await.unwrap();
+        let second = file_store.insert(FileInfo::new("b.txt".to_owned(), None), b"hello".to_vec()).await.unwrap();
+        file_store.insert(FileInfo::new("c.txt".to_owned(), None), b"world".to_vec()).await.unwrap();
+        assert_eq!(blob_count(&file_store).await, 2);
+        assert_eq!(file_store.hash(first).await.unwrap(), file_store.hash(second).await.unwrap());
+
+        assert!(file_store.delete(first).await.unwrap());
+        assert_eq!(file_store.get(second).await.unwrap().unwrap().1, b"hello");
+        assert_eq!(blob_count(&file_store).await, 2);
+
+        assert!(file_store.delete(second).await.unwrap());
+        assert_eq!(blob_count(&file_store).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_verify() {
+        let file_store = memory_file_store().await;
+        let intact = file_store.insert(FileInfo::new("a.txt".to_owned(), None), b"hello".to_vec()).await.unwrap();
+        let modified = file_store.insert(FileInfo::new("b.txt".to_owned(), None), b"world".to_vec()).await.unwrap();
+
+        let hash = file_store.hash(modified).await.unwrap().unwrap();
+        sqlx::query("UPDATE file_blobs SET data = $1 WHERE key = $2")
+            .bind(b"altered".to_vec())
+            .bind(format!("sha256-{hash}"))
+            .execute(&file_store.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(file_store.verify(intact).await.unwrap(), Some(Integrity::Intact));
+        assert_eq!(file_store.verify(modified).await.unwrap(), Some(Integrity::Modified));
+        assert_eq!(file_store.verify(FileId::new()).await.unwrap(), None);
+        assert_eq!(file_store.verify_all().await.unwrap(), vec![(modified, Integrity::Modified)]);
+    }
+
+    #[tokio::test]
+    async fn test_verify_legacy_file() {
+        let pool = memory_pool().await;
+        let file_store = FileStore::builder().pool(pool).build().await.unwrap();
+        let id = file_store.insert(FileInfo::new("a.txt".to_owned(), None), b"hello".to_vec()).await.unwrap();
+
+        sqlx::query("UPDATE files SET hash = NULL WHERE uuid = $1")
+            .bind(id.to_string())
+            .execute(&file_store.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(file_store.verify(id).await.unwrap(), Some(Integrity::Unknown));
+    }
+}
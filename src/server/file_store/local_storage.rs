@@ -0,0 +1,150 @@
+use futures::{future::BoxFuture, StreamExt};
+use std::{io::ErrorKind, path::PathBuf};
+use tokio::{fs, io::AsyncReadExt};
+
+use super::{FileStorage, FileStoreError, FileStream};
+
+/// The size of the chunks in which files are streamed.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Stores file contents as individual files in a directory on the local filesystem.
+#[derive(Clone)]
+pub struct LocalStorage {
+    directory: PathBuf,
+}
+
+impl LocalStorage {
+    /// Tries to create a new `LocalStorage` that stores files in the given directory.
+    /// The directory is created if it does not exist yet.
+    pub async fn new<P: Into<PathBuf>>(directory: P) -> Result<Self, FileStoreError> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory).await?;
+
+        Ok(Self { directory })
+    }
+
+    /// The directory in which the files are stored.
+    pub fn directory(&self) -> &PathBuf {
+        &self.directory
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, FileStoreError> {
+        if key.is_empty() || key.starts_with('.') || key.contains(['/', '\\']) {
+            return Err(FileStoreError::StorageError(format!("invalid key {key}")));
+        }
+
+        Ok(self.directory.join(key))
+    }
+}
+
+impl FileStorage for LocalStorage {
+    fn put<'a>(&'a self, key: &'a str, data: Vec<u8>) -> BoxFuture<'a, Result<(), FileStoreError>> {
+        Box::pin(async move {
+            let path = self.path(key)?;
+
+            // Write to a temporary file first so that readers never see partially written files.
+            // Each write uses its own temporary file, as identical contents may be stored concurrently.
+            let tmp_path = self.directory.join(format!(".{key}.{}.tmp", uuid::Uuid::new_v4()));
+            fs::write(&tmp_path, data).await?;
+            fs::rename(&tmp_path, &path).await?;
+
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, FileStoreError>> {
+        Box::pin(async move {
+            match fs::read(self.path(key)?).await {
+                Ok(data) => Ok(Some(data)),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    fn get_stream<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<FileStream>, FileStoreError>> {
+        Box::pin(async move {
+            let file = match fs::File::open(self.path(key)?).await {
+                Ok(file) => file,
+                Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+
+            let stream = futures::stream::try_unfold(file, |mut file| async move {
+                let mut chunk = vec![0; CHUNK_SIZE];
+                let read = file.read(&mut chunk).await?;
+                if read == 0 {
+                    return Ok(None);
+                }
+                chunk.truncate(read);
+                Ok(Some((chunk, file)))
+            });
+
+            Ok(Some(stream.boxed()))
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), FileStoreError>> {
+        Box::pin(async move {
+            match fs::remove_file(self.path(key)?).await {
+                Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+    use crate::{FileInfo, FileStore};
+    use crate::server::file_store::tests::memory_pool;
+
+    #[tokio::test]
+    async fn test_local_storage() {
+        let directory = std::env::temp_dir().join(format!("nova-forms-test-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&directory).await.unwrap();
+        let file_store = FileStore::builder()
+            .pool(memory_pool().await)
+            .storage(storage)
+            .build()
+            .await
+            .unwrap();
+
+        let file_info = FileInfo::new("test.txt".to_owned(), None);
+        let id = file_store.insert(file_info, b"hello".to_vec()).await.unwrap();
+
+        let hash = file_store.hash(id).await.unwrap().unwrap();
+        assert_eq!(std::fs::read(directory.join(format!("sha256-{hash}"))).unwrap(), b"hello");
+        assert_eq!(file_store.get(id).await.unwrap().unwrap().1, b"hello");
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_stream() {
+        let directory = std::env::temp_dir().join(format!("nova-forms-test-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&directory).await.unwrap();
+        let data = (0..CHUNK_SIZE * 2 + 1).map(|i| i as u8).collect::<Vec<_>>();
+        storage.put("large", data.clone()).await.unwrap();
+
+        let chunks: Vec<Vec<u8>> = storage.get_stream("large").await.unwrap().unwrap().try_collect().await.unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.concat(), data);
+        assert!(storage.get_stream("missing").await.unwrap().is_none());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_invalid_key() {
+        let directory = std::env::temp_dir().join(format!("nova-forms-test-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&directory).await.unwrap();
+
+        assert!(storage.put("../escape", Vec::new()).await.is_err());
+        assert!(storage.get("nested/key").await.is_err());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
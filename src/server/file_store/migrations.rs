@@ -0,0 +1,135 @@
+use sqlx::SqlitePool;
+
+use super::FileStoreError;
+
+/// The schema migrations of the `FileStore`, in the order in which they are applied.
+/// Occurrences of `{prefix}` are replaced with the configured table prefix.
+/// Released migrations must never be changed, add a new migration instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: The metadata table, as created by earlier versions without migrations.
+    r#"
+    CREATE TABLE IF NOT EXISTS {prefix}files (
+        uuid TEXT PRIMARY KEY,
+        file_name TEXT NOT NULL,
+        content_type TEXT
+    );
+    "#,
+    // 2: Size, SHA-256 hash and creation time (unix timestamp) of each file.
+    r#"
+    ALTER TABLE {prefix}files ADD COLUMN size INTEGER;
+    ALTER TABLE {prefix}files ADD COLUMN hash TEXT;
+    ALTER TABLE {prefix}files ADD COLUMN created_at INTEGER;
+    "#,
+    // 3: Lifecycle state of each file. Files that existed before are considered committed,
+    // as they may be referenced by forms that have already been submitted.
+    r#"
+    ALTER TABLE {prefix}files ADD COLUMN state TEXT NOT NULL DEFAULT 'committed';
+    ALTER TABLE {prefix}files ADD COLUMN committed_at INTEGER;
+    UPDATE {prefix}files SET committed_at = COALESCE(created_at, CAST(strftime('%s', 'now') AS INTEGER));
+    "#,
+    // 4: The owner of each file, as identified by the `FileAccess` policy.
+    r#"
+    ALTER TABLE {prefix}files ADD COLUMN owner TEXT;
+    "#,
+    // 5: The key of the blob that holds the contents of each file.
+    // Earlier versions stored the contents of each file under its id.
+    r#"
+    ALTER TABLE {prefix}files ADD COLUMN blob_key TEXT;
+    UPDATE {prefix}files SET blob_key = uuid;
+    CREATE INDEX {prefix}files_blob_key ON {prefix}files (blob_key);
+    "#,
+    // 6: The encrypted data keys of encrypted blobs.
+    r#"
+    CREATE TABLE {prefix}file_keys (
+        blob_key TEXT PRIMARY KEY,
+        key_id TEXT NOT NULL,
+        wrapped_key BLOB NOT NULL
+    );
+    "#,
+    // 7: The blobs that were completely written to the storage backend.
+    // Blobs of existing files were written before the file was inserted.
+    r#"
+    CREATE TABLE {prefix}stored_blobs (
+        blob_key TEXT PRIMARY KEY
+    );
+    INSERT OR IGNORE INTO {prefix}stored_blobs (blob_key)
+    SELECT blob_key FROM {prefix}files;
+    "#,
+    // 8: The form field for which each file was uploaded.
+    r#"
+    ALTER TABLE {prefix}files ADD COLUMN field TEXT;
+    CREATE INDEX {prefix}files_owner_field ON {prefix}files (owner, field);
+    "#,
+    // 9: Documents generated from submitted forms, such as cached PDFs.
+    r#"
+    CREATE TABLE {prefix}cached_documents (
+        key TEXT PRIMARY KEY,
+        created_at INTEGER NOT NULL
+    );
+    "#,
+];
+
+/// Applies all migrations that have not been applied yet.
+/// Returns the schema version after migrating.
+pub(super) async fn migrate(pool: &SqlitePool, prefix: &str) -> Result<i64, FileStoreError> {
+    sqlx::raw_sql(&format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {prefix}file_store_migrations (
+            version INTEGER PRIMARY KEY,
+            applied_at INTEGER NOT NULL
+        )
+    "#
+    ))
+    .execute(pool)
+    .await?;
+
+    let (mut version,): (i64,) = sqlx::query_as(&format!(
+        "SELECT COALESCE(MAX(version), 0) FROM {prefix}file_store_migrations"
+    ))
+    .fetch_one(pool)
+    .await?;
+
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        version += 1;
+
+        let mut tx = pool.begin().await?;
+
+        sqlx::raw_sql(&migration.replace("{prefix}", prefix))
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(&format!(
+            "INSERT INTO {prefix}file_store_migrations (version, applied_at) VALUES ($1, $2)"
+        ))
+        .bind(version)
+        .bind(time::OffsetDateTime::now_utc().unix_timestamp())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+    }
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::file_store::tests::memory_pool;
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let pool = memory_pool().await;
+
+        assert_eq!(migrate(&pool, "test_").await.unwrap(), MIGRATIONS.len() as i64);
+        // Migrating again must be a no-op.
+        assert_eq!(migrate(&pool, "test_").await.unwrap(), MIGRATIONS.len() as i64);
+
+        let columns: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_info('test_files')")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        let columns = columns.into_iter().map(|c| c.0).collect::<Vec<_>>();
+        assert_eq!(columns, ["uuid", "file_name", "content_type", "size", "hash", "created_at", "state", "committed_at", "owner", "blob_key", "field"]);
+    }
+}
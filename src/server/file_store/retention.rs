@@ -0,0 +1,287 @@
+use std::time::Duration;
+use strum::{Display, EnumString};
+use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+
+use super::{FileStore, FileStoreError};
+use crate::FileId;
+
+/// The lifecycle state of a stored file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum FileState {
+    /// The file was uploaded, but is not referenced by a submitted form (yet).
+    /// Orphaned files are purged once they are older than `RetentionPolicy::orphan_max_age`.
+    Orphaned,
+    /// The file is referenced by a submitted form.
+    /// Committed files are purged once they were committed longer than `RetentionPolicy::retention_period` ago.
+    Committed,
+    /// The file was rejected by the `FileScanner` and is kept for inspection only.
+    /// Quarantined files are purged once they are older than `RetentionPolicy::quarantine_max_age`.
+    Quarantined,
+}
+
+/// Defines how long files are kept in the `FileStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// The maximum age of files that were uploaded but never committed.
+    pub orphan_max_age: Duration,
+    /// How long committed files are kept.
+    /// If `None`, committed files are kept forever.
+    pub retention_period: Option<Duration>,
+    /// The maximum age of files that were rejected by the `FileScanner`.
+    pub quarantine_max_age: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            orphan_max_age: Duration::from_secs(24 * 60 * 60),
+            retention_period: None,
+            quarantine_max_age: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// The result of a sweep of the `FileStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SweepReport {
+    /// The number of orphaned files that were purged.
+    pub orphaned: usize,
+    /// The number of committed files that were purged because their retention period expired.
+    pub expired: usize,
+    /// The number of quarantined files that were purged.
+    pub quarantined: usize,
+    /// The number of cached documents, such as PDFs, that were purged because their retention period expired.
+    pub cached: usize,
+}
+
+impl FileStore {
+    /// Marks the given files as committed, i.e. referenced by a submitted form.
+    /// Call this from the server function that handles the form submission.
+    pub async fn commit(&self, ids: &[FileId]) -> Result<(), FileStoreError> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut tx = self.pool.begin().await?;
+
+        for id in ids {
+            sqlx::query(&format!(
+                r#"
+                UPDATE {}
+                SET state = $1, committed_at = $2
+                WHERE uuid = $3 AND state = $4
+            "#,
+                self.table
+            ))
+            .bind(FileState::Committed.to_string())
+            .bind(now)
+            .bind(id.to_string())
+            .bind(FileState::Orphaned.to_string())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Deletes a file that is orphaned, e.g. because the user removed it from the form before submitting.
+    /// Committed files are not affected, as they are referenced by a submitted form.
+    /// Returns `false` if no orphaned file with this id exists.
+    pub async fn discard(&self, id: FileId) -> Result<bool, FileStoreError> {
+        if self.state(id).await? != Some(FileState::Orphaned) {
+            return Ok(false);
+        }
+
+        self.delete(id).await
+    }
+
+    /// Returns the lifecycle state of a file, or `None` if the file does not exist.
+    pub async fn state(&self, id: FileId) -> Result<Option<FileState>, FileStoreError> {
+        let record: Option<(String,)> = sqlx::query_as(&format!(
+            r#"
+            SELECT state
+            FROM {}
+            WHERE uuid = $1
+        "#,
+            self.table
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        record
+            .map(|(state,)| state.parse().map_err(|_| FileStoreError::StorageError(format!("invalid file state {state}"))))
+            .transpose()
+    }
+
+    /// Purges all files that have to be removed according to the `RetentionPolicy` of this `FileStore`.
+    pub async fn sweep(&self) -> Result<SweepReport, FileStoreError> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut report = SweepReport::default();
+
+        let orphaned = self
+            .expired_files(FileState::Orphaned, "created_at", now - self.retention.orphan_max_age.as_secs() as i64)
+            .await?;
+        for id in orphaned {
+            self.delete(id).await?;
+            report.orphaned += 1;
+        }
+
+        let quarantined = self
+            .expired_files(FileState::Quarantined, "created_at", now - self.retention.quarantine_max_age.as_secs() as i64)
+            .await?;
+        for id in quarantined {
+            self.delete(id).await?;
+            report.quarantined += 1;
+        }
+
+        if let Some(retention_period) = self.retention.retention_period {
+            let expired = self
+                .expired_files(FileState::Committed, "committed_at", now - retention_period.as_secs() as i64)
+                .await?;
+            for id in expired {
+                self.delete(id).await?;
+                report.expired += 1;
+            }
+
+            // Cached documents contain the submitted data, so they are kept as long as committed files.
+            report.cached = self.sweep_cached_documents(now - retention_period.as_secs() as i64).await?;
+        }
+
+        Ok(report)
+    }
+
+    async fn expired_files(&self, state: FileState, column: &str, before: i64) -> Result<Vec<FileId>, FileStoreError> {
+        let records: Vec<(String,)> = sqlx::query_as(&format!(
+            r#"
+            SELECT uuid
+            FROM {}
+            WHERE state = $1 AND {column} < $2
+        "#,
+            self.table
+        ))
+        .bind(state.to_string())
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await?;
+
+        records
+            .into_iter()
+            .map(|(uuid,)| uuid.parse().map_err(|_| FileStoreError::StorageError(format!("invalid file id {uuid}"))))
+            .collect()
+    }
+
+    /// Spawns a background task that sweeps the `FileStore` in the given interval.
+    /// Errors are logged and the sweep is retried in the next interval.
+    pub fn spawn_sweeper(&self, interval: Duration) -> JoinHandle<()> {
+        let file_store = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                match file_store.sweep().await {
+                    Ok(report) => {
+                        leptos::logging::log!(
+                            "swept file store, purged {} orphaned, {} expired and {} quarantined files and {} cached documents",
+                            report.orphaned,
+                            report.expired,
+                            report.quarantined,
+                            report.cached
+                        );
+                    }
+                    Err(err) => {
+                        leptos::logging::error!("failed to sweep file store: {err}");
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileInfo, server::file_store::tests::{memory_file_store, memory_pool}};
+
+    async fn insert(file_store: &FileStore) -> FileId {
+        let file_info = FileInfo::new("test.txt".to_owned(), None);
+        file_store.insert(file_info, b"hello".to_vec()).await.unwrap()
+    }
+
+    async fn set_age(file_store: &FileStore, id: FileId, column: &str, seconds: i64) {
+        sqlx::query(&format!("UPDATE files SET {column} = $1 WHERE uuid = $2"))
+            .bind(OffsetDateTime::now_utc().unix_timestamp() - seconds)
+            .bind(id.to_string())
+            .execute(&file_store.pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_commit() {
+        let file_store = memory_file_store().await;
+        let id = insert(&file_store).await;
+
+        assert_eq!(file_store.state(id).await.unwrap(), Some(FileState::Orphaned));
+        file_store.commit(&[id]).await.unwrap();
+        assert_eq!(file_store.state(id).await.unwrap(), Some(FileState::Committed));
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let file_store = memory_file_store().await;
+        let id = insert(&file_store).await;
+
+        assert!(file_store.delete(id).await.unwrap());
+        assert!(!file_store.delete(id).await.unwrap());
+        assert!(file_store.get(id).await.unwrap().is_none());
+        assert_eq!(file_store.state(id).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_discard() {
+        let file_store = memory_file_store().await;
+        let orphaned = insert(&file_store).await;
+        let committed = insert(&file_store).await;
+        file_store.commit(&[committed]).await.unwrap();
+
+        assert!(file_store.discard(orphaned).await.unwrap());
+        assert!(!file_store.discard(committed).await.unwrap());
+        assert!(file_store.get(orphaned).await.unwrap().is_none());
+        assert!(file_store.get(committed).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_sweep() {
+        let file_store = FileStore::builder()
+            .pool(memory_pool().await)
+            .retention(RetentionPolicy {
+                orphan_max_age: Duration::from_secs(60),
+                retention_period: Some(Duration::from_secs(3600)),
+                ..Default::default()
+            })
+            .build()
+            .await
+            .unwrap();
+
+        let fresh_orphan = insert(&file_store).await;
+        let old_orphan = insert(&file_store).await;
+        set_age(&file_store, old_orphan, "created_at", 120).await;
+
+        let fresh_committed = insert(&file_store).await;
+        let old_committed = insert(&file_store).await;
+        file_store.commit(&[fresh_committed, old_committed]).await.unwrap();
+        set_age(&file_store, fresh_committed, "created_at", 120).await;
+        set_age(&file_store, old_committed, "committed_at", 7200).await;
+
+        let report = file_store.sweep().await.unwrap();
+        assert_eq!(report, SweepReport { orphaned: 1, expired: 1, quarantined: 0, cached: 0 });
+
+        assert!(file_store.get(fresh_orphan).await.unwrap().is_some());
+        assert!(file_store.get(old_orphan).await.unwrap().is_none());
+        assert!(file_store.get(fresh_committed).await.unwrap().is_some());
+        assert!(file_store.get(old_committed).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,318 @@
+use futures::{future::BoxFuture, StreamExt, TryStreamExt};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{Client, Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use time::{macros::format_description, OffsetDateTime};
+
+use super::{FileStorage, FileStoreError, FileStream};
+
+/// Characters that have to be percent-encoded in an S3 object key.
+const KEY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'/');
+
+/// The connection settings for an S3-compatible object storage.
+#[derive(Clone)]
+pub struct S3Config {
+    /// The endpoint of the object storage, for example `https://s3.eu-central-1.amazonaws.com` or `http://localhost:9000`.
+    pub endpoint: String,
+    /// The bucket in which the files are stored.
+    pub bucket: String,
+    /// The region of the bucket, for example `eu-central-1`.
+    pub region: String,
+    /// The access key id used to sign requests.
+    pub access_key: String,
+    /// The secret access key used to sign requests.
+    pub secret_key: String,
+}
+
+/// Stores file contents as objects in an S3-compatible object storage, such as AWS S3 or MinIO.
+/// Objects are addressed using path-style URLs, i.e. `{endpoint}/{bucket}/{key}`.
+#[derive(Clone)]
+pub struct S3Storage {
+    client: Client,
+    config: S3Config,
+}
+
+impl S3Storage {
+    /// Tries to create a new `S3Storage` using the given settings.
+    pub fn new(config: S3Config) -> Result<Self, FileStoreError> {
+        Url::parse(&config.endpoint)
+            .map_err(|err| FileStoreError::StorageError(format!("invalid endpoint: {err}")))?;
+
+        Ok(Self {
+            client: Client::new(),
+            config,
+        })
+    }
+
+    fn url(&self, key: &str) -> Result<Url, FileStoreError> {
+        let url = format!(
+            "{}/{}/{}",
+            self.config.endpoint.trim_end_matches('/'),
+            self.config.bucket,
+            utf8_percent_encode(key, KEY_ENCODE_SET)
+        );
+
+        Url::parse(&url).map_err(|err| FileStoreError::StorageError(format!("invalid url: {err}")))
+    }
+
+    /// Sends a request signed with AWS signature version 4.
+    async fn send(&self, method: Method, key: &str, body: Vec<u8>) -> Result<reqwest::Response, FileStoreError> {
+        let url = self.url(key)?;
+        let now = OffsetDateTime::now_utc();
+        let date = now
+            .format(format_description!("[year][month][day]"))
+            .expect("date can be formatted");
+        let date_time = now
+            .format(format_description!("[year][month][day]T[hour][minute][second]Z"))
+            .expect("date time can be formatted");
+
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => return Err(FileStoreError::StorageError("endpoint has no host".to_owned())),
+        };
+
+        let payload_hash = hex::encode(Sha256::digest(&body));
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{method}\n{path}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{date_time}\n\n{signed_headers}\n{payload_hash}",
+            method = method.as_str(),
+            path = url.path(),
+        );
+
+        let scope = format!("{date}/{}/s3/aws4_request", self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{date_time}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [self.config.region.as_str(), "s3", "aws4_request"]
+            .into_iter()
+            .fold(
+                hmac_sha256(format!("AWS4{}", self.config.secret_key).as_bytes(), date.as_bytes()),
+                |key, part| hmac_sha256(&key, part.as_bytes()),
+            );
+        let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.config.access_key
+        );
+
+        let response = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", date_time)
+            .header("authorization", authorization)
+            .body(body)
+            .send()
+            .await?;
+
+        Ok(response)
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+async fn error_from_response(response: reqwest::Response) -> FileStoreError {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    FileStoreError::StorageError(format!("object storage responded with {status}: {body}"))
+}
+
+impl FileStorage for S3Storage {
+    fn put<'a>(&'a self, key: &'a str, data: Vec<u8>) -> BoxFuture<'a, Result<(), FileStoreError>> {
+        Box::pin(async move {
+            let response = self.send(Method::PUT, key, data).await?;
+
+            if !response.status().is_success() {
+                return Err(error_from_response(response).await);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, FileStoreError>> {
+        Box::pin(async move {
+            let response = self.send(Method::GET, key, Vec::new()).await?;
+
+            if response.status() == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            if !response.status().is_success() {
+                return Err(error_from_response(response).await);
+            }
+
+            Ok(Some(response.bytes().await?.to_vec()))
+        })
+    }
+
+    fn get_stream<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<FileStream>, FileStoreError>> {
+        Box::pin(async move {
+            let response = self.send(Method::GET, key, Vec::new()).await?;
+
+            if response.status() == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            if !response.status().is_success() {
+                return Err(error_from_response(response).await);
+            }
+
+            let stream = response
+                .bytes_stream()
+                .map_ok(|chunk| chunk.to_vec())
+                .map_err(FileStoreError::from);
+
+            Ok(Some(stream.boxed()))
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), FileStoreError>> {
+        Box::pin(async move {
+            let response = self.send(Method::DELETE, key, Vec::new()).await?;
+
+            if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+                return Err(error_from_response(response).await);
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{FileInfo, FileStore};
+    use crate::server::file_store::tests::memory_pool;
+    use std::{collections::HashMap, sync::{Arc, Mutex}};
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}};
+
+    pub(crate) type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// Starts a minimal S3 stand-in on a random local port.
+    /// It only checks that requests are signed and keeps objects in memory.
+    pub(crate) async fn fake_s3() -> (S3Config, Objects) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let objects = Objects::default();
+
+        let objects_clone = objects.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(handle(stream, objects_clone.clone()));
+            }
+        });
+
+        let config = S3Config {
+            endpoint: format!("http://{addr}"),
+            bucket: "bucket".to_owned(),
+            region: "us-east-1".to_owned(),
+            access_key: "test".to_owned(),
+            secret_key: "secret".to_owned(),
+        };
+
+        (config, objects)
+    }
+
+    async fn handle(mut stream: TcpStream, objects: Objects) {
+        let mut buffer = Vec::new();
+        let header_end = loop {
+            let mut chunk = [0; 4096];
+            let n = stream.read(&mut chunk).await.unwrap();
+            buffer.extend_from_slice(&chunk[..n]);
+            if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+        };
+
+        let head = String::from_utf8(buffer[..header_end].to_vec()).unwrap();
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap().split(' ');
+        let (method, path) = (request_line.next().unwrap(), request_line.next().unwrap().to_owned());
+        let headers: HashMap<String, String> = lines
+            .filter_map(|line| line.split_once(": "))
+            .map(|(k, v)| (k.to_lowercase(), v.to_owned()))
+            .collect();
+
+        let content_length = headers.get("content-length").map(|v| v.parse().unwrap()).unwrap_or(0);
+        let mut body = buffer[header_end..].to_vec();
+        while body.len() < content_length {
+            let mut chunk = [0; 4096];
+            let n = stream.read(&mut chunk).await.unwrap();
+            body.extend_from_slice(&chunk[..n]);
+        }
+
+        let signed = headers
+            .get("authorization")
+            .is_some_and(|auth| auth.starts_with("AWS4-HMAC-SHA256 Credential=test/"));
+
+        let (status, response_body) = if !signed {
+            ("403 Forbidden", Vec::new())
+        } else {
+            let mut objects = objects.lock().unwrap();
+            match method {
+                "PUT" => {
+                    objects.insert(path, body);
+                    ("200 OK", Vec::new())
+                }
+                "GET" => match objects.get(&path) {
+                    Some(data) => ("200 OK", data.clone()),
+                    None => ("404 Not Found", Vec::new()),
+                },
+                "DELETE" => {
+                    objects.remove(&path);
+                    ("204 No Content", Vec::new())
+                }
+                _ => ("405 Method Not Allowed", Vec::new()),
+            }
+        };
+
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response_body.len()
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+        stream.write_all(&response_body).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_s3_storage() {
+        let (config, objects) = fake_s3().await;
+        let storage = S3Storage::new(config).unwrap();
+        let file_store = FileStore::builder()
+            .pool(memory_pool().await)
+            .storage(storage)
+            .build()
+            .await
+            .unwrap();
+
+        let file_info = FileInfo::new("test.txt".to_owned(), None);
+        let id = file_store.insert(file_info, b"hello".to_vec()).await.unwrap();
+
+        let hash = file_store.hash(id).await.unwrap().unwrap();
+        assert_eq!(objects.lock().unwrap().get(&format!("/bucket/sha256-{hash}")).unwrap(), b"hello");
+        assert_eq!(file_store.get(id).await.unwrap().unwrap().1, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_s3_storage_missing() {
+        let (config, _) = fake_s3().await;
+        let storage = S3Storage::new(config).unwrap();
+
+        assert!(storage.get("missing").await.unwrap().is_none());
+    }
+}
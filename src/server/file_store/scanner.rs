@@ -0,0 +1,226 @@
+use futures::future::BoxFuture;
+use std::{path::PathBuf, time::Duration};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::FileStoreError;
+
+/// The verdict of a `FileScanner`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanResult {
+    /// No threat was found.
+    Clean,
+    /// The file was rejected, for example because it contains malware.
+    /// Contains the reason, such as the name of the detected signature.
+    Rejected(String),
+}
+
+/// Scans uploaded files before they are accepted by the `FileStore`.
+/// Rejected files are kept in quarantine, see `FileState::Quarantined`.
+pub trait FileScanner: Send + Sync + 'static {
+    /// Scans the contents of a file when it is inserted into the `FileStore`, before it is stored.
+    /// If the file is `ScanResult::Rejected`, it is stored in quarantine, where it cannot be read or committed,
+    /// and the insert fails with `FileStoreError::Quarantined`.
+    /// Implementations may return an error if the file could not be scanned, for example because the scanner is unreachable;
+    /// the insert then fails with this error and the file is not stored, so uploads are never accepted unscanned.
+    fn scan<'a>(&'a self, file_name: &'a str, data: &'a [u8]) -> BoxFuture<'a, Result<ScanResult, FileStoreError>>;
+}
+
+/// The size of the chunks sent to ClamAV, which must be below its `StreamMaxLength`.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+enum ClamAvAddress {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// Scans files with a ClamAV daemon using the `INSTREAM` command.
+#[derive(Debug, Clone)]
+pub struct ClamAvScanner {
+    address: ClamAvAddress,
+    timeout: Duration,
+}
+
+impl ClamAvScanner {
+    /// Connects to `clamd` over TCP, for example `127.0.0.1:3310`.
+    pub fn tcp<S: Into<String>>(address: S) -> Self {
+        Self {
+            address: ClamAvAddress::Tcp(address.into()),
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Connects to `clamd` over a unix socket, for example `/run/clamav/clamd.ctl`.
+    #[cfg(unix)]
+    pub fn unix<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            address: ClamAvAddress::Unix(path.into()),
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// The maximum time a scan may take, including connecting to the daemon.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn scan_stream<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, data: &[u8]) -> Result<ScanResult, FileStoreError> {
+        stream.write_all(b"zINSTREAM\0").await?;
+        for chunk in data.chunks(CHUNK_SIZE) {
+            stream.write_all(&(chunk.len() as u32).to_be_bytes()).await?;
+            stream.write_all(chunk).await?;
+        }
+        stream.write_all(&0u32.to_be_bytes()).await?;
+        stream.flush().await?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        let response = String::from_utf8_lossy(&response);
+
+        parse_response(response.trim_end_matches(['\0', '\n']))
+    }
+}
+
+/// Parses a response such as `stream: OK` or `stream: Eicar-Test-Signature FOUND`.
+fn parse_response(response: &str) -> Result<ScanResult, FileStoreError> {
+    let result = response.strip_prefix("stream: ").unwrap_or(response);
+
+    if result == "OK" {
+        Ok(ScanResult::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(ScanResult::Rejected(signature.to_owned()))
+    } else {
+        Err(FileStoreError::ScanError(result.to_owned()))
+    }
+}
+
+impl FileScanner for ClamAvScanner {
+    fn scan<'a>(&'a self, _file_name: &'a str, data: &'a [u8]) -> BoxFuture<'a, Result<ScanResult, FileStoreError>> {
+        Box::pin(async move {
+            let scan = async {
+                match &self.address {
+                    ClamAvAddress::Tcp(address) => {
+                        Self::scan_stream(tokio::net::TcpStream::connect(address).await?, data).await
+                    }
+                    #[cfg(unix)]
+                    ClamAvAddress::Unix(path) => {
+                        Self::scan_stream(tokio::net::UnixStream::connect(path).await?, data).await
+                    }
+                }
+            };
+
+            tokio::time::timeout(self.timeout, scan)
+                .await
+                .map_err(|_| FileStoreError::ScanError("the scan timed out".to_owned()))?
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileInfo, FileState, FileStore, server::file_store::tests::memory_pool};
+    use tokio::net::TcpListener;
+
+    /// Starts a minimal ClamAV stand-in on a random local port.
+    /// It rejects all files that contain the given signature.
+    async fn fake_clamd(signature: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut command = [0; 10];
+                    stream.read_exact(&mut command).await.unwrap();
+                    assert_eq!(&command, b"zINSTREAM\0");
+
+                    let mut data = Vec::new();
+                    loop {
+                        let length = stream.read_u32().await.unwrap() as usize;
+                        if length == 0 {
+                            break;
+                        }
+                        let mut chunk = vec![0; length];
+                        stream.read_exact(&mut chunk).await.unwrap();
+                        data.extend(chunk);
+                    }
+
+                    let response: &[u8] = if data.windows(signature.len()).any(|w| w == signature) {
+                        b"stream: Test-Signature FOUND\0"
+                    } else {
+                        b"stream: OK\0"
+                    };
+                    stream.write_all(response).await.unwrap();
+                });
+            }
+        });
+
+        address
+    }
+
+    #[tokio::test]
+    async fn test_clamav_scanner() {
+        let scanner = ClamAvScanner::tcp(fake_clamd(b"EVIL").await);
+
+        assert_eq!(scanner.scan("a.txt", b"hello").await.unwrap(), ScanResult::Clean);
+        assert_eq!(
+            scanner.scan("b.txt", b"hello EVIL world").await.unwrap(),
+            ScanResult::Rejected("Test-Signature".to_owned())
+        );
+
+        // Files larger than a single chunk are streamed in multiple chunks.
+        let mut large = vec![0; 3 * CHUNK_SIZE];
+        large.extend(b"EVIL");
+        assert!(matches!(scanner.scan("c.bin", &large).await.unwrap(), ScanResult::Rejected(_)));
+    }
+
+    #[tokio::test]
+    async fn test_clamav_unavailable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        assert!(ClamAvScanner::tcp(address).scan("a.txt", b"hello").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_quarantine() {
+        let file_store = FileStore::builder()
+            .pool(memory_pool().await)
+            .scanner(ClamAvScanner::tcp(fake_clamd(b"EVIL").await))
+            .build()
+            .await
+            .unwrap();
+
+        let clean = file_store.insert(FileInfo::new("a.txt".to_owned(), None), b"hello".to_vec()).await.unwrap();
+        assert_eq!(file_store.state(clean).await.unwrap(), Some(FileState::Orphaned));
+
+        let Err(FileStoreError::Quarantined { id, reason }) =
+            file_store.insert(FileInfo::new("b.txt".to_owned(), None), b"EVIL".to_vec()).await
+        else {
+            panic!("file must be quarantined");
+        };
+        assert_eq!(reason, "Test-Signature");
+        assert_eq!(file_store.state(id).await.unwrap(), Some(FileState::Quarantined));
+        assert!(file_store.get(id).await.unwrap().is_none());
+        assert!(file_store.info(id).await.unwrap().is_none());
+
+        // Quarantined files can never be committed.
+        file_store.commit(&[id]).await.unwrap();
+        assert_eq!(file_store.state(id).await.unwrap(), Some(FileState::Quarantined));
+    }
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(parse_response("stream: OK").unwrap(), ScanResult::Clean);
+        assert_eq!(
+            parse_response("stream: Win.Test.EICAR_HDB-1 FOUND").unwrap(),
+            ScanResult::Rejected("Win.Test.EICAR_HDB-1".to_owned())
+        );
+        assert!(parse_response("INSTREAM size limit exceeded. ERROR").is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use futures::future::BoxFuture;
+use sqlx::SqlitePool;
+
+use super::{validate_identifier, FileStorage, FileStoreError};
+
+/// Stores file contents as blobs in a SQLite database.
+#[derive(Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+    table: String,
+}
+
+impl SqliteStorage {
+    /// Tries to create a new `SqliteStorage` that stores blobs in the `file_blobs` table of the given database.
+    pub async fn new(pool: SqlitePool) -> Result<Self, FileStoreError> {
+        Self::with_table(pool, "file_blobs").await
+    }
+
+    /// Tries to create a new `SqliteStorage` that stores blobs in the given table of the given database.
+    /// The table name may only contain ASCII letters, digits and underscores.
+    pub async fn with_table<S: Into<String>>(pool: SqlitePool, table: S) -> Result<Self, FileStoreError> {
+        let table = table.into();
+        validate_identifier(&table)?;
+
+        sqlx::query(&format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS {table} (
+                key TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            )
+        "#
+        ))
+        .execute(&pool)
+        .await?;
+
+        Ok(Self { pool, table })
+    }
+}
+
+impl FileStorage for SqliteStorage {
+    fn put<'a>(&'a self, key: &'a str, data: Vec<u8>) -> BoxFuture<'a, Result<(), FileStoreError>> {
+        Box::pin(async move {
+            sqlx::query(&format!(
+                r#"
+                INSERT OR REPLACE INTO {} (key, data)
+                VALUES ($1, $2)
+            "#,
+                self.table
+            ))
+            .bind(key)
+            .bind(data)
+            .execute(&self.pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, FileStoreError>> {
+        Box::pin(async move {
+            let record: Option<(Vec<u8>,)> = sqlx::query_as(&format!(
+                r#"
+                SELECT data
+                FROM {}
+                WHERE key = $1
+            "#,
+                self.table
+            ))
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            Ok(record.map(|r| r.0))
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), FileStoreError>> {
+        Box::pin(async move {
+            sqlx::query(&format!(
+                r#"
+                DELETE FROM {}
+                WHERE key = $1
+            "#,
+                self.table
+            ))
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+}
@@ -1,82 +1,312 @@
+mod attachments;
+mod cache;
+mod pagedjs;
+mod pool;
+mod pdf_a;
+#[cfg(feature = "signing")]
+mod pdf_file;
+#[cfg(feature = "signing")]
+mod signing;
+#[cfg(feature = "chrome")]
+mod chrome;
+
+pub use attachments::*;
+pub use cache::*;
+pub use pagedjs::*;
+pub use pool::*;
+pub use pdf_a::*;
+#[cfg(feature = "signing")]
+pub use signing::*;
+#[cfg(feature = "chrome")]
+pub use chrome::*;
+
+use futures::future::BoxFuture;
 use leptos::IntoView;
 use std::{
-    path::PathBuf,
-    process::{ExitStatus, Stdio},
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::ExitStatus,
     sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
 };
 use thiserror::Error;
-use tokio::{fs::File, io::AsyncWriteExt, process::Command};
+use tokio::{fs::File, io::{AsyncRead, AsyncWriteExt, ReadBuf}};
 use uuid::Uuid;
-use crate::SiteRoot;
+use crate::{DocumentMetadata, FileId, FileInfo, FileStoreError, RenderContext, RenderedAttachments, RenderedContext, SiteRoot};
+use pool::RenderPool;
+
+/// Prints rendered HTML to a PDF.
+pub trait PdfBackend: Send + Sync + 'static {
+    /// Renders the HTML file at `input` and writes the PDF to `output`.
+    /// Both files are in the working directory of the `PdfGen`, next to each other.
+    fn render<'a>(&'a self, input: &'a Path, output: &'a Path) -> BoxFuture<'a, Result<(), Error>>;
+}
 
 /// A PDF generator.
 #[derive(Clone)]
 pub struct PdfGen {
     settings: Arc<Settings>,
+    pool: Arc<RenderPool>,
     site_root: PathBuf,
 }
 
 struct Settings {
     working_dir: PathBuf,
+    backend: Arc<dyn PdfBackend>,
+    attachments: Option<Attachments>,
+    cache: Option<PdfCache>,
+    pdf_a: Option<PdfA>,
+    #[cfg(feature = "signing")]
+    signer: Option<PdfSigner>,
+    timeout: Option<Duration>,
+    max_concurrent: usize,
+    max_queued: usize,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
             working_dir: std::env::temp_dir(),
+            backend: Arc::new(PagedJsCli::default()),
+            attachments: None,
+            cache: None,
+            pdf_a: None,
+            #[cfg(feature = "signing")]
+            signer: None,
+            timeout: Some(Duration::from_secs(60)),
+            max_concurrent: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            max_queued: 32,
         }
     }
 }
 
-impl PdfGen {
-    /// Creates a new `PdfGen`.
-    pub async fn new() -> Self {
-        let conf = leptos::get_configuration(None).await.unwrap();
-        let site_root = std::env::current_dir().unwrap().join(conf.leptos_options.site_root);
-
-        Self {
-            settings: Arc::new(Settings::default()),
-            site_root
-        }
+/// Deletes a file when dropped, unless it was kept.
+struct TempFile {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempFile {
+    fn new(path: PathBuf) -> Self {
+        Self { path, keep: false }
+    }
+
+    fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        self.path.clone()
     }
+}
 
-    async fn render_html<S: AsRef<str>>(&self, html: S) -> Result<PathBuf, Error> {
-        let uuid = Uuid::new_v4();
-        let name = uuid.to_string();
-        let input_path = self
-            .settings
-            .working_dir
-            .as_path()
-            .join(format!("{name}.html"));
-        let output_path = self
-            .settings
-            .working_dir
-            .as_path()
-            .join(format!("{name}.pdf"));
-
-        let mut input_file = File::create(&input_path).await?;
-        input_file.write_all(html.as_ref().as_bytes()).await?;
-
-        let exit_status = Command::new("pagedjs-cli")
-            .stdin(Stdio::null())
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .current_dir(&self.settings.working_dir)
-            .arg(input_path.file_name().unwrap())
-            .arg("-o")
-            .arg(output_path.file_name().unwrap())
-            .status()
-            .await?;
-
-        if !exit_status.success() {
-            return Err(Error::PdfGenerationError(exit_status));
+/// Reads a generated PDF.
+/// The underlying file is deleted once the reader is dropped.
+pub struct PdfReader {
+    file: File,
+    _temp_file: TempFile,
+}
+
+impl AsyncRead for PdfReader {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_read(cx, buf)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_file(&self.path);
         }
+    }
+}
+
+impl PdfGen {
+    /// Creates a new `PdfGen` with the default settings.
+    /// The site root is read from the leptos configuration.
+    /// Fails if the leptos configuration cannot be read.
+    pub async fn new() -> Result<Self, Error> {
+        Self::builder().build().await
+    }
+
+    /// Creates a builder to configure a new `PdfGen`.
+    pub fn builder() -> PdfGenBuilder {
+        PdfGenBuilder::default()
+    }
 
-        Ok(output_path)
+    async fn render_html<S: AsRef<str>>(
+        &self,
+        html: S,
+        metadata: &DocumentMetadata,
+        attachments: &[TempFile],
+    ) -> Result<TempFile, Error> {
+        self.pool.run(self.render_html_now(html, metadata, attachments)).await
+    }
+
+    async fn render_html_now<S: AsRef<str>>(
+        &self,
+        html: S,
+        metadata: &DocumentMetadata,
+        attachments: &[TempFile],
+    ) -> Result<TempFile, Error> {
+        let settings = &self.settings;
+        let name = Uuid::new_v4().to_string();
+        let input_file = TempFile::new(settings.working_dir.join(format!("{name}.html")));
+        let output_file = TempFile::new(settings.working_dir.join(format!("{name}.pdf")));
+
+        let mut input = File::create(&input_file.path).await?;
+        input.write_all(html.as_ref().as_bytes()).await?;
+        input.flush().await?;
+
+        // Dropping the render future cancels it, which kills processes spawned by the backend.
+        let render = async {
+            settings.backend.render(&input_file.path, &output_file.path).await?;
+
+            let output_file = match &settings.attachments {
+                Some(attachment_settings) if !attachments.is_empty() => {
+                    let merged_file = TempFile::new(settings.working_dir.join(format!("{name}.merged.pdf")));
+                    attachment_settings
+                        .merge(&output_file.path, attachments, &merged_file.path)
+                        .await?;
+                    merged_file
+                }
+                _ => output_file,
+            };
+
+            let output_file = match &settings.pdf_a {
+                Some(pdf_a) => {
+                    let archive_file = TempFile::new(settings.working_dir.join(format!("{name}.pdfa.pdf")));
+                    let definitions_file = TempFile::new(settings.working_dir.join(format!("{name}.ps")));
+                    pdf_a
+                        .convert(&output_file.path, &archive_file.path, &definitions_file.path, metadata)
+                        .await?;
+                    archive_file
+                }
+                None => output_file,
+            };
+
+            #[cfg(feature = "signing")]
+            if let Some(signer) = &settings.signer {
+                let signed = signer.sign(&tokio::fs::read(&output_file.path).await?).await?;
+                let signed_file = TempFile::new(settings.working_dir.join(format!("{name}.signed.pdf")));
+                tokio::fs::write(&signed_file.path, signed).await?;
+                return Ok(signed_file);
+            }
+
+            Ok(output_file)
+        };
+
+        match settings.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, render)
+                .await
+                .map_err(|_| Error::Timeout(timeout))?,
+            None => render.await,
+        }
     }
 
     /// Renders a form as a PDF.
+    /// Returns the path to the PDF in the working directory, which has to be deleted by the caller.
+    #[deprecated(note = "use `render_form_to_bytes` or `render_form_to_reader`, which delete the PDF automatically")]
     pub async fn render_form<F, IV>(&self, form: F) -> Result<PathBuf, Error>
+    where
+        F: FnOnce() -> IV + 'static,
+        IV: IntoView + 'static,
+    {
+        Ok(self.render_form_to_file(form).await?.keep())
+    }
+
+    /// Renders a form as a PDF and returns its contents.
+    pub async fn render_form_to_bytes<F, IV>(&self, form: F) -> Result<Vec<u8>, Error>
+    where
+        F: FnOnce() -> IV + 'static,
+        IV: IntoView + 'static,
+    {
+        let output_file = self.render_form_to_file(form).await?;
+
+        Ok(tokio::fs::read(&output_file.path).await?)
+    }
+
+    /// Renders a form as a PDF and returns its contents, like `render_form_to_bytes`.
+    /// If a `PdfCache` is configured, the PDF is only printed if it was not printed from the same
+    /// `RenderContext` with the same template version before.
+    /// The form is always rendered to HTML to find its `RenderContext`, which must be provided using
+    /// `RenderContext::provide` or a `RenderContextProvider`. Forms without a `RenderContext` are not cached.
+    pub async fn render_form_to_bytes_cached<F, IV>(&self, form: F) -> Result<Vec<u8>, Error>
+    where
+        F: FnOnce() -> IV + 'static,
+        IV: IntoView + 'static,
+    {
+        let Some(cache) = &self.settings.cache else {
+            return self.render_form_to_bytes(form).await;
+        };
+
+        let (html, metadata, files, render_context) = self.render_to_html(form).await;
+        let key = render_context.map(|render_context| cache.key(&render_context)).transpose()?;
+        if let Some(key) = &key {
+            if let Some(pdf) = cache.get(key).await? {
+                return Ok(pdf);
+            }
+        }
+
+        let output_file = self.print_to_file(html, &metadata, &files).await?;
+        let pdf = tokio::fs::read(&output_file.path).await?;
+        if let Some(key) = &key {
+            cache.put(key, pdf.clone()).await?;
+        }
+        Ok(pdf)
+    }
+
+    /// Removes the cached PDF of the given render context, if any,
+    /// for example when the uploaded files of a submission were replaced.
+    pub async fn invalidate_cached(&self, render_context: &RenderContext) -> Result<(), Error> {
+        match &self.settings.cache {
+            Some(cache) => cache.delete(&cache.key(render_context)?).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Renders a form as a PDF and returns a reader for its contents,
+    /// for example to stream it in an HTTP response without loading it into memory.
+    pub async fn render_form_to_reader<F, IV>(&self, form: F) -> Result<PdfReader, Error>
+    where
+        F: FnOnce() -> IV + 'static,
+        IV: IntoView + 'static,
+    {
+        let output_file = self.render_form_to_file(form).await?;
+
+        Ok(PdfReader {
+            file: File::open(&output_file.path).await?,
+            _temp_file: output_file,
+        })
+    }
+
+    async fn render_form_to_file<F, IV>(&self, form: F) -> Result<TempFile, Error>
+    where
+        F: FnOnce() -> IV + 'static,
+        IV: IntoView + 'static,
+    {
+        let (html, metadata, files, _) = self.render_to_html(form).await;
+        self.print_to_file(html, &metadata, &files).await
+    }
+
+    async fn print_to_file(&self, html: String, metadata: &DocumentMetadata, files: &[(FileId, FileInfo)]) -> Result<TempFile, Error> {
+        let (appendix, attachments) = match &self.settings.attachments {
+            Some(attachment_settings) => {
+                let name = Uuid::new_v4().to_string();
+                attachment_settings
+                    .appendix(files, &self.settings.working_dir, &name)
+                    .await?
+            }
+            None => (String::new(), Vec::new()),
+        };
+        let html = match html.strip_suffix("</body></html>") {
+            Some(body) => format!("{body}{appendix}</body></html>"),
+            None => html,
+        };
+
+        self.render_html(html, metadata, &attachments).await
+    }
+
+    async fn render_to_html<F, IV>(&self, form: F) -> (String, DocumentMetadata, Vec<(FileId, FileInfo)>, Option<RenderContext>)
     where
         F: FnOnce() -> IV + 'static,
         IV: IntoView + 'static,
@@ -84,27 +314,172 @@ impl PdfGen {
         use leptos::*;
         use leptos_meta::*;
         use std::sync::{Arc, OnceLock};
-        
+
         let site_root = self.site_root.clone();
         let head_metadata = Arc::new(OnceLock::new());
-        
+        // Collects the uploaded files, so that they can be appended to the document.
+        let rendered_attachments = RenderedAttachments::default();
+        let rendered_attachments_clone = rendered_attachments.clone();
+        // Collects the render context of the form, so that the PDF can be cached.
+        let rendered_context = RenderedContext::default();
+        let rendered_context_clone = rendered_context.clone();
+
         let head_metadata_clone = head_metadata.clone();
-        let html = leptos::ssr::render_to_string(move || {
+        // Render asynchronously, so that resources such as uploaded files are loaded before printing.
+        let html = leptos::ssr::render_to_string_async(move || {
             provide_context(SiteRoot::from(site_root));
+            // Collects the document metadata set by the form, see `update_document_metadata`.
+            let document_metadata = create_rw_signal(DocumentMetadata::default());
+            provide_context(document_metadata);
+            provide_context(rendered_attachments_clone);
+            provide_context(rendered_context_clone);
 
-            let view = form();
+            let view = form().into_view();
 
-            head_metadata_clone.set(generate_head_metadata()).unwrap();
+            head_metadata_clone
+                .set((generate_head_metadata(), document_metadata.get_untracked()))
+                .unwrap();
 
             view
         })
-        .into_owned();
+        .await;
+
+        let (head_metadata, document_metadata) = head_metadata.get().unwrap().clone();
+        let html = format!(
+            "{}<head>{}{}{}</body></html>",
+            html_tag(&document_metadata),
+            meta_tags(&document_metadata),
+            head_metadata,
+            html
+        );
+
+        (html, document_metadata, rendered_attachments.take(), rendered_context.take())
+    }
+}
+
+/// Creates the opening `<html>` tag with the language of the document.
+fn html_tag(metadata: &DocumentMetadata) -> String {
+    use leptos::leptos_dom::ssr::escape_attr;
+
+    match &metadata.language {
+        Some(language) => format!("<!DOCTYPE html><html lang=\"{}\">", escape_attr(language)),
+        None => "<!DOCTYPE html><html>".to_owned(),
+    }
+}
+
+/// Creates the meta tags from which `pagedjs-cli` sets the metadata of the PDF.
+fn meta_tags(metadata: &DocumentMetadata) -> String {
+    use leptos::leptos_dom::ssr::escape_attr;
+
+    let keywords = (!metadata.keywords.is_empty()).then(|| metadata.keywords.join(", "));
+    [
+        ("author", metadata.author.as_deref()),
+        ("subject", metadata.subject.as_deref()),
+        ("keywords", keywords.as_deref()),
+        ("creator", Some("nova-forms")),
+    ]
+    .into_iter()
+    .filter_map(|(name, content)| Some(format!("<meta name=\"{name}\" content=\"{}\">", escape_attr(&content?))))
+    .collect()
+}
+
+/// A builder to configure a `PdfGen`.
+#[derive(Default)]
+pub struct PdfGenBuilder {
+    settings: Settings,
+    site_root: Option<PathBuf>,
+}
 
-        let html = format!("<head>{}{}</body>", head_metadata.get().unwrap(), html);
+impl PdfGenBuilder {
+    /// The directory in which intermediate files are created.
+    /// Defaults to the temporary directory of the system.
+    pub fn working_dir<P: Into<PathBuf>>(mut self, working_dir: P) -> Self {
+        self.settings.working_dir = working_dir.into();
+        self
+    }
+
+    /// The backend that prints the rendered HTML to a PDF.
+    /// Defaults to `PagedJsCli`.
+    pub fn backend<B: PdfBackend>(mut self, backend: B) -> Self {
+        self.settings.backend = Arc::new(backend);
+        self
+    }
+
+    /// Appends the files uploaded with `FileUpload`s to the generated PDFs.
+    /// Disabled by default.
+    pub fn attachments(mut self, attachments: Attachments) -> Self {
+        self.settings.attachments = Some(attachments);
+        self
+    }
+
+    /// Caches the PDFs generated with `PdfGen::render_form_to_bytes_cached`.
+    /// Disabled by default.
+    pub fn cache(mut self, cache: PdfCache) -> Self {
+        self.settings.cache = Some(cache);
+        self
+    }
+
+    /// Converts the generated PDFs to PDF/A-2b for long-term archiving.
+    /// Disabled by default.
+    pub fn pdf_a(mut self, pdf_a: PdfA) -> Self {
+        self.settings.pdf_a = Some(pdf_a);
+        self
+    }
 
-        let output_path = self.render_html(html).await?;
+    /// Signs the generated PDFs, after converting them to PDF/A if enabled.
+    /// Disabled by default, requires the `signing` feature.
+    #[cfg(feature = "signing")]
+    pub fn signer(mut self, signer: PdfSigner) -> Self {
+        self.settings.signer = Some(signer);
+        self
+    }
+
+    /// The maximum time a single render may take before it is cancelled.
+    /// Defaults to 60 seconds, `None` disables the timeout.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.settings.timeout = timeout;
+        self
+    }
 
-        Ok(output_path)
+    /// The maximum number of renders that run at the same time.
+    /// Defaults to the number of CPUs.
+    pub fn max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.settings.max_concurrent = max_concurrent.max(1);
+        self
+    }
+
+    /// The maximum number of renders that wait for a free slot.
+    /// Further renders fail with `Error::QueueFull` until the queue has room again.
+    /// Defaults to 32.
+    pub fn max_queued(mut self, max_queued: usize) -> Self {
+        self.settings.max_queued = max_queued;
+        self
+    }
+
+    /// The directory from which the assets of the site are served.
+    /// Defaults to the site root of the leptos configuration.
+    pub fn site_root<P: Into<PathBuf>>(mut self, site_root: P) -> Self {
+        self.site_root = Some(site_root.into());
+        self
+    }
+
+    /// Creates the `PdfGen`.
+    pub async fn build(self) -> Result<PdfGen, Error> {
+        let site_root = match self.site_root {
+            Some(site_root) => site_root,
+            None => {
+                let conf = leptos::get_configuration(None)
+                    .await
+                    .map_err(|err| Error::ConfigurationError(err.to_string()))?;
+                std::env::current_dir()?.join(conf.leptos_options.site_root)
+            }
+        };
+
+        Ok(PdfGen {
+            pool: Arc::new(RenderPool::new(self.settings.max_concurrent, self.settings.max_queued)),
+            settings: Arc::new(self.settings),
+            site_root,
+        })
     }
 }
 
@@ -112,6 +487,374 @@ impl PdfGen {
 pub enum Error {
     #[error("IO Error: {0}")]
     IOError(#[from] std::io::Error),
-    #[error("Generation Error: {0}")]
-    PdfGenerationError(ExitStatus),
+    #[error("Generation Error: {status}: {stderr}")]
+    PdfGenerationError { status: ExitStatus, stderr: String },
+    #[error("Timeout Error: the PDF generation took longer than {0:?}")]
+    Timeout(Duration),
+    #[error("Configuration Error: {0}")]
+    ConfigurationError(String),
+    #[error("Backend Error: {0}")]
+    BackendError(String),
+    #[error("Queue Full: too many PDFs are being generated, try again later")]
+    QueueFull,
+    #[error("Signing Error: {0}")]
+    SigningError(String),
+    #[error("File Store Error: {0}")]
+    FileStoreError(#[from] FileStoreError),
+    #[error("Serialization Error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Creates a fake `pagedjs-cli` script in a new working directory.
+    fn fake_pagedjs(script: &str) -> (PathBuf, PathBuf) {
+        let working_dir = std::env::temp_dir().join(format!("nova-forms-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&working_dir).unwrap();
+        let executable = fake_executable(&working_dir, "fake-pagedjs", script);
+
+        (working_dir, executable)
+    }
+
+    fn fake_executable(working_dir: &Path, name: &str, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let executable = working_dir.join(name);
+        std::fs::write(&executable, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&executable, std::fs::Permissions::from_mode(0o755)).unwrap();
+        executable
+    }
+
+    async fn pdf_gen(working_dir: &Path, executable: &Path, timeout: Option<Duration>) -> PdfGen {
+        PdfGen::builder()
+            .working_dir(working_dir)
+            .backend(PagedJsCli::new().executable(executable))
+            .timeout(timeout)
+            .site_root(working_dir)
+            .build()
+            .await
+            .unwrap()
+    }
+
+    fn html_files(working_dir: &Path) -> usize {
+        std::fs::read_dir(working_dir)
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "html"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_render_html() {
+        // Copies the input to the output file given after `-o`.
+        let (working_dir, executable) = fake_pagedjs(r#"cp "$1" "$3""#);
+        let pdf_gen = pdf_gen(&working_dir, &executable, None).await;
+
+        let output_file = pdf_gen.render_html("<p>hello</p>", &DocumentMetadata::default(), &[]).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&output_file.path).unwrap(), "<p>hello</p>");
+        assert_eq!(html_files(&working_dir), 0);
+
+        // The output is deleted once it is not needed anymore.
+        let output_path = output_file.path.clone();
+        drop(output_file);
+        assert!(!output_path.exists());
+
+        std::fs::remove_dir_all(working_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_render_html_error() {
+        let (working_dir, executable) = fake_pagedjs("echo 'browser crashed' >&2; exit 1");
+        let pdf_gen = pdf_gen(&working_dir, &executable, None).await;
+
+        let Err(Error::PdfGenerationError { stderr, .. }) = pdf_gen.render_html("<p>hello</p>", &DocumentMetadata::default(), &[]).await else {
+            panic!("render must fail");
+        };
+        assert_eq!(stderr, "browser crashed");
+        assert_eq!(html_files(&working_dir), 0);
+
+        std::fs::remove_dir_all(working_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_render_html_timeout() {
+        let (working_dir, executable) = fake_pagedjs("sleep 10");
+        let pdf_gen = pdf_gen(&working_dir, &executable, Some(Duration::from_millis(100))).await;
+
+        assert!(matches!(pdf_gen.render_html("<p>hello</p>", &DocumentMetadata::default(), &[]).await, Err(Error::Timeout(_))));
+        assert_eq!(html_files(&working_dir), 0);
+
+        std::fs::remove_dir_all(working_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_render_slot_outlives_timeout() {
+        /// Prints on a blocking thread that cannot be aborted, like `HeadlessChrome`.
+        struct BlockingBackend;
+
+        impl PdfBackend for BlockingBackend {
+            fn render<'a>(&'a self, _input: &'a Path, _output: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+                Box::pin(async move {
+                    let slot = take_render_slot();
+                    assert!(slot.is_some());
+                    tokio::task::spawn_blocking(move || {
+                        let _slot = slot;
+                        std::thread::sleep(Duration::from_millis(300));
+                    })
+                    .await
+                    .unwrap();
+                    Ok(())
+                })
+            }
+        }
+
+        let (working_dir, _) = fake_pagedjs("");
+        let pdf_gen = PdfGen::builder()
+            .working_dir(&working_dir)
+            .backend(BlockingBackend)
+            .timeout(Some(Duration::from_millis(50)))
+            .site_root(&working_dir)
+            .build()
+            .await
+            .unwrap();
+
+        assert!(matches!(pdf_gen.render_html("<p>hello</p>", &DocumentMetadata::default(), &[]).await, Err(Error::Timeout(_))));
+        // The slot stays occupied until the blocking print finished.
+        assert_eq!(pdf_gen.metrics().running, 1);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(pdf_gen.metrics().running, 0);
+
+        std::fs::remove_dir_all(working_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_render_queue() {
+        let (working_dir, executable) = fake_pagedjs(r#"sleep 0.2; cp "$1" "$3""#);
+        let pdf_gen = PdfGen::builder()
+            .working_dir(&working_dir)
+            .backend(PagedJsCli::new().executable(&executable))
+            .max_concurrent(1)
+            .max_queued(1)
+            .site_root(&working_dir)
+            .build()
+            .await
+            .unwrap();
+
+        // One render runs, one waits in the queue and one is rejected.
+        let metadata = DocumentMetadata::default();
+        let (first, second, third) = tokio::join!(
+            pdf_gen.render_html("<p>1</p>", &metadata, &[]),
+            pdf_gen.render_html("<p>2</p>", &metadata, &[]),
+            pdf_gen.render_html("<p>3</p>", &metadata, &[]),
+        );
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        assert!(matches!(third, Err(Error::QueueFull)));
+
+        let metrics = pdf_gen.metrics();
+        assert_eq!((metrics.completed, metrics.failed, metrics.rejected), (2, 0, 1));
+        assert_eq!((metrics.queued, metrics.running), (0, 0));
+        assert!(metrics.max_queue_time >= Duration::from_millis(100));
+        assert!(metrics.average_render_time() >= Duration::from_millis(100));
+
+        drop((first, second));
+        std::fs::remove_dir_all(working_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_render_form_to_bytes_and_reader() {
+        use leptos::*;
+        use tokio::io::AsyncReadExt;
+
+        let (working_dir, executable) = fake_pagedjs(r#"cp "$1" "$3""#);
+        let pdf_gen = pdf_gen(&working_dir, &executable, None).await;
+
+        // Rendering spawns local tasks to load resources, as it does when handling requests.
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let bytes = pdf_gen.render_form_to_bytes(|| view! { <p>"hello"</p> }).await.unwrap();
+                assert!(String::from_utf8(bytes).unwrap().contains("hello"));
+
+                let mut reader = pdf_gen.render_form_to_reader(|| view! { <p>"world"</p> }).await.unwrap();
+                let mut contents = String::new();
+                reader.read_to_string(&mut contents).await.unwrap();
+                assert!(contents.contains("world"));
+                drop(reader);
+            })
+            .await;
+
+        // Only the fake executable is left in the working directory.
+        assert_eq!(std::fs::read_dir(&working_dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(working_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_document_metadata_and_pdf_a() {
+        use crate::DocumentMeta;
+        use leptos::*;
+
+        let (working_dir, executable) = fake_pagedjs(r#"cp "$1" "$3""#);
+        // Copies the last argument to the output file and marks the result as converted.
+        let gs = fake_executable(
+            &working_dir,
+            "fake-gs",
+            r#"for arg in "$@"; do case "$arg" in -sOutputFile=*) output="${arg#-sOutputFile=}";; esac; input="$arg"; done
+cp "$input" "$output"; echo "converted" >> "$output""#,
+        );
+        let pdf_gen = PdfGen::builder()
+            .working_dir(&working_dir)
+            .backend(PagedJsCli::new().executable(&executable))
+            .pdf_a(PdfA::new().executable(&gs))
+            .site_root(&working_dir)
+            .build()
+            .await
+            .unwrap();
+
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let bytes = pdf_gen
+                    .render_form_to_bytes(|| view! {
+                        <DocumentMeta author="Jane Doe" keywords=vec!["a".to_owned(), "b".to_owned()] />
+                        <p>"hello"</p>
+                    })
+                    .await
+                    .unwrap();
+                let pdf = String::from_utf8(bytes).unwrap();
+                assert!(pdf.contains(r#"<meta name="author" content="Jane Doe">"#));
+                assert!(pdf.contains(r#"<meta name="keywords" content="a, b">"#));
+                assert!(pdf.ends_with("converted\n"));
+            })
+            .await;
+
+        // Only the fake executables are left in the working directory.
+        assert_eq!(std::fs::read_dir(&working_dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(working_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_attachments() {
+        use leptos::*;
+
+        let (working_dir, executable) = fake_pagedjs(r#"cp "$1" "$3""#);
+        // Concatenates the input files into the output file.
+        let gs = fake_executable(
+            &working_dir,
+            "fake-gs",
+            r#"for arg in "$@"; do case "$arg" in -sOutputFile=*) output="${arg#-sOutputFile=}";; -*) ;; *) cat "$arg" >> "$output";; esac; done"#,
+        );
+        let file_store = crate::server::file_store::tests::memory_file_store().await;
+        let mut files = Vec::new();
+        for (file_name, content_type, data) in [
+            ("letter.pdf", "application/pdf", b"%PDF-1.7 letter".as_slice()),
+            ("photo.png", "image/png", b"png".as_slice()),
+            ("notes.docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document", b"docx".as_slice()),
+        ] {
+            let file_info = FileInfo::new(file_name.to_owned(), Some(content_type.to_owned()));
+            let file_id = file_store.insert(file_info.clone(), data.to_vec()).await.unwrap();
+            files.push((file_id, file_info));
+        }
+
+        let pdf_gen = PdfGen::builder()
+            .working_dir(&working_dir)
+            .backend(PagedJsCli::new().executable(&executable))
+            .attachments(Attachments::new(file_store).title("Beilagen").executable(&gs))
+            .site_root(&working_dir)
+            .build()
+            .await
+            .unwrap();
+
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let bytes = pdf_gen
+                    .render_form_to_bytes(move || {
+                        // Registers the files as a `FileUpload` does, twice to check that they are not duplicated.
+                        let rendered_attachments = expect_context::<RenderedAttachments>();
+                        rendered_attachments.add(&files);
+                        rendered_attachments.add(&files[1..2]);
+                        view! { <p>"hello"</p> }
+                    })
+                    .await
+                    .unwrap();
+                let pdf = String::from_utf8(bytes).unwrap();
+                assert!(pdf.contains("<h2>Beilagen</h2>"));
+                assert!(pdf.contains("<tr><td>1</td><td>photo.png</td><td>PNG, 3 B</td></tr>"));
+                assert!(pdf.contains("<td>2</td><td>notes.docx</td>"));
+                // The PDF is listed last, as it is merged after the printed document.
+                assert!(pdf.contains("<td>3</td><td>letter.pdf</td>"));
+                assert!(!pdf.contains("<td>4</td>"));
+                assert!(pdf.contains(r#"<h2>1. photo.png</h2><img src="data:image/png;base64,cG5n">"#));
+                assert!(pdf.ends_with("</body></html>%PDF-1.7 letter"));
+            })
+            .await;
+
+        // Only the fake executables are left in the working directory.
+        assert_eq!(std::fs::read_dir(&working_dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(working_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_render_cached() {
+        use crate::{server::file_store::tests::memory_file_store, MetaData, RenderContext};
+        use leptos::*;
+
+        // Counts the renders in the working directory.
+        let (working_dir, executable) = fake_pagedjs(r#"echo render >> renders; cp "$1" "$3""#);
+        let file_store = memory_file_store().await;
+        let pdf_gen = |version: &'static str| {
+            PdfGen::builder()
+                .working_dir(&working_dir)
+                .backend(PagedJsCli::new().executable(&executable))
+                .cache(PdfCache::new(file_store.clone(), version))
+                .site_root(&working_dir)
+                .build()
+        };
+        let render_context = |name: &str| {
+            let meta_data = MetaData {
+                locale: "de".to_owned(),
+                local_utc_offset: time::UtcOffset::UTC,
+            };
+            RenderContext::new(&[("name", name)].into_iter().collect::<std::collections::BTreeMap<_, _>>(), meta_data)
+        };
+        let form = move |name: &'static str, text: &'static str| {
+            move || {
+                render_context(name).provide();
+                view! { <p>{text}</p> }
+            }
+        };
+        let renders = || std::fs::read_to_string(working_dir.join("renders")).unwrap().lines().count();
+
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let pdf_gen_v1 = pdf_gen("v1").await.unwrap();
+                let first = pdf_gen_v1.render_form_to_bytes_cached(form("Jane", "hello")).await.unwrap();
+                let second = pdf_gen_v1.render_form_to_bytes_cached(form("Jane", "hello")).await.unwrap();
+                assert_eq!(first, second);
+                assert_eq!(renders(), 1);
+
+                pdf_gen_v1.render_form_to_bytes_cached(form("John", "hello")).await.unwrap();
+                assert_eq!(renders(), 2);
+
+                // A new template version renders the PDF again.
+                let pdf_gen_v2 = pdf_gen("v2").await.unwrap();
+                let third = pdf_gen_v2.render_form_to_bytes_cached(form("Jane", "world")).await.unwrap();
+                assert_ne!(first, third);
+                assert_eq!(renders(), 3);
+
+                pdf_gen_v2.invalidate_cached(&render_context("Jane")).await.unwrap();
+                pdf_gen_v2.render_form_to_bytes_cached(form("Jane", "world")).await.unwrap();
+                assert_eq!(renders(), 4);
+
+                // Forms without a render context are never cached.
+                pdf_gen_v2.render_form_to_bytes_cached(|| view! { <p>"hello"</p> }).await.unwrap();
+                pdf_gen_v2.render_form_to_bytes_cached(|| view! { <p>"hello"</p> }).await.unwrap();
+                assert_eq!(renders(), 6);
+            })
+            .await;
+
+        std::fs::remove_dir_all(working_dir).unwrap();
+    }
 }
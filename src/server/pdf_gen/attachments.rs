@@ -0,0 +1,138 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use leptos::leptos_dom::ssr::escape_attr;
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::process::Command;
+
+use super::{Error, TempFile};
+use crate::{attachment_details, FileId, FileInfo, FileStore};
+
+/// The image types that are added as pages of the document.
+const IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// Appends the files uploaded with `FileUpload`s to the generated PDFs, after a table of attachments.
+/// Images are added as pages of the document, uploaded PDFs are merged with Ghostscript after them
+/// and are therefore listed last in the table of attachments.
+/// Other files are only listed in the table of attachments.
+#[derive(Clone)]
+pub struct Attachments {
+    file_store: FileStore,
+    title: String,
+    executable: PathBuf,
+}
+
+impl Attachments {
+    /// Creates attachments that are loaded from the given `FileStore`.
+    pub fn new(file_store: FileStore) -> Self {
+        Self {
+            file_store,
+            title: "Attachments".to_owned(),
+            executable: PathBuf::from("gs"),
+        }
+    }
+
+    /// The heading of the table of attachments.
+    /// Defaults to `Attachments`.
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// The path to the Ghostscript executable that merges the uploaded PDFs.
+    /// Defaults to `gs`, which is looked up in the `PATH`.
+    pub fn executable<P: Into<PathBuf>>(mut self, executable: P) -> Self {
+        self.executable = executable.into();
+        self
+    }
+
+    /// Loads the files and creates the HTML that is appended to the form.
+    /// Uploaded PDFs are written to the working directory, to be merged after printing.
+    pub(super) async fn appendix(
+        &self,
+        files: &[(FileId, FileInfo)],
+        working_dir: &Path,
+        name: &str,
+    ) -> Result<(String, Vec<TempFile>), Error> {
+        let mut loaded = Vec::new();
+        for (file_id, _) in files {
+            // Files that were deleted in the meantime are left out.
+            if let Some(file) = self.file_store.get(*file_id).await? {
+                loaded.push(file);
+            }
+        }
+        // The uploaded PDFs are merged after the printed document, so they are listed last to match the page order.
+        let (mut loaded, pdfs): (Vec<_>, Vec<_>) = loaded.into_iter().partition(|(file_info, data)| !is_pdf(file_info, data));
+        loaded.extend(pdfs);
+
+        let mut rows = String::new();
+        let mut pages = String::new();
+        let mut pdfs = Vec::new();
+
+        for (index, (file_info, data)) in loaded.into_iter().enumerate() {
+            let number = index + 1;
+            let file_name = escape_attr(&file_info.file_name().to_owned()).into_owned();
+            write!(
+                rows,
+                "<tr><td>{number}</td><td>{file_name}</td><td>{}</td></tr>",
+                escape_attr(&attachment_details(&file_info))
+            )
+            .unwrap();
+
+            if is_pdf(&file_info, &data) {
+                let pdf = TempFile::new(working_dir.join(format!("{name}.attachment-{}.pdf", pdfs.len())));
+                tokio::fs::write(&pdf.path, data).await?;
+                pdfs.push(pdf);
+            } else if let Some(content_type) = file_info.content_type().filter(|content_type| IMAGE_TYPES.contains(content_type)) {
+                write!(
+                    pages,
+                    r#"<section class="attachment-page"><h2>{number}. {file_name}</h2><img src="data:{content_type};base64,{}"></section>"#,
+                    STANDARD.encode(&data)
+                )
+                .unwrap();
+            }
+        }
+
+        if rows.is_empty() {
+            return Ok((String::new(), pdfs));
+        }
+
+        let html = format!(
+            r#"<section class="attachments-appendix"><h2>{}</h2><table class="attachments-table">{rows}</table></section>{pages}"#,
+            escape_attr(&self.title)
+        );
+        Ok((html, pdfs))
+    }
+
+    /// Merges the printed form at `input` with the uploaded PDFs and writes the result to `output`.
+    pub(super) async fn merge(&self, input: &Path, pdfs: &[TempFile], output: &Path) -> Result<(), Error> {
+        let child = Command::new(&self.executable)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .args(["-dBATCH", "-dNOPAUSE", "-dQUIET", "-dSAFER", "-sDEVICE=pdfwrite"])
+            .arg(format!("-sOutputFile={}", output.display()))
+            .arg(input)
+            .args(pdfs.iter().map(|pdf| &pdf.path))
+            // Kills the process if the render times out or the request is cancelled.
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let result = child.wait_with_output().await?;
+        if !result.status.success() {
+            return Err(Error::PdfGenerationError {
+                status: result.status,
+                stderr: String::from_utf8_lossy(&result.stderr).trim().to_owned(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the file is a PDF that is merged after the printed document.
+fn is_pdf(file_info: &FileInfo, data: &[u8]) -> bool {
+    file_info.content_type() == Some("application/pdf") && data.starts_with(b"%PDF-")
+}
@@ -0,0 +1,84 @@
+use sha2::{Digest, Sha256};
+
+use super::Error;
+use crate::{FileStore, RenderContext};
+
+/// Caches generated PDFs in the `FileStore`.
+/// Cached PDFs contain the submitted data, so they are encrypted like uploaded files if encryption is configured,
+/// and purged by `FileStore::sweep` once the `RetentionPolicy::retention_period` expired.
+/// The PDFs are keyed by a hash of the data and meta data of the `RenderContext` and the template version,
+/// so changing the template version invalidates all cached PDFs.
+/// The keys start with `pdf-` followed by a hash of the template version.
+#[derive(Clone)]
+pub struct PdfCache {
+    file_store: FileStore,
+    template_version: String,
+}
+
+impl PdfCache {
+    /// Creates a cache for PDFs rendered with the given version of the form template.
+    /// Change the version whenever the form or its styles change.
+    pub fn new<V: Into<String>>(file_store: FileStore, template_version: V) -> Self {
+        Self {
+            file_store,
+            template_version: template_version.into(),
+        }
+    }
+
+    /// The key of the PDF rendered with the given render context.
+    pub fn key(&self, render_context: &RenderContext) -> Result<String, Error> {
+        let version_hash = hex::encode(Sha256::digest(self.template_version.as_bytes()));
+        let data = serde_json::to_vec(&(
+            &self.template_version,
+            render_context.data().to_urlencoded(),
+            render_context.meta_data(),
+        ))?;
+        let hash = hex::encode(Sha256::digest(data));
+
+        Ok(format!("pdf-{}-{hash}", &version_hash[..16]))
+    }
+
+    pub(super) async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.file_store.cached_document(key).await?)
+    }
+
+    pub(super) async fn put(&self, key: &str, pdf: Vec<u8>) -> Result<(), Error> {
+        Ok(self.file_store.cache_document(key, pdf).await?)
+    }
+
+    pub(super) async fn delete(&self, key: &str) -> Result<(), Error> {
+        Ok(self.file_store.delete_cached_document(key).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{server::file_store::tests::memory_file_store, MetaData};
+    use time::UtcOffset;
+
+    #[tokio::test]
+    async fn test_key() {
+        let meta_data = MetaData {
+            locale: "de".to_owned(),
+            local_utc_offset: UtcOffset::UTC,
+        };
+        let file_store = memory_file_store().await;
+        let cache = PdfCache::new(file_store.clone(), "v1");
+        #[derive(serde::Serialize)]
+        struct Person {
+            name: &'static str,
+            age: u32,
+        }
+        let render_context = |age: u32| RenderContext::new(&Person { name: "Jane", age }, meta_data.clone());
+
+        let key = cache.key(&render_context(42)).unwrap();
+        assert!(key.starts_with("pdf-"));
+        assert_eq!(key, cache.key(&render_context(42)).unwrap());
+        assert_ne!(key, cache.key(&render_context(43)).unwrap());
+
+        // A new template version changes the prefix of all keys.
+        let other_key = PdfCache::new(file_store, "v2").key(&render_context(42)).unwrap();
+        assert_ne!(key[..20], other_key[..20]);
+    }
+}
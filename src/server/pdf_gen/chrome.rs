@@ -0,0 +1,220 @@
+use futures::future::BoxFuture;
+use headless_chrome::{types::PrintToPdfOptions, Browser, LaunchOptions, Tab};
+use std::{
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex},
+    time::Duration,
+};
+
+use super::{take_render_slot, Error, PdfBackend};
+
+/// The attribute that is set on the document once paged.js has laid out all pages.
+const READY_ATTRIBUTE: &str = "data-pagedjs-ready";
+
+/// Prints PDFs with a headless Chrome or Chromium.
+/// The pages are laid out by the paged.js polyfill, so that the result matches `PagedJsCli`.
+#[derive(Debug, Clone)]
+pub struct HeadlessChrome {
+    executable: Option<PathBuf>,
+    polyfill: PathBuf,
+    sandbox: bool,
+    timeout: Duration,
+}
+
+impl HeadlessChrome {
+    /// Creates a backend that loads the paged.js polyfill from the given local file,
+    /// usually the copy installed into the site root by `install_assets`, see `PAGED_JS_PATH`.
+    pub fn new<P: Into<PathBuf>>(polyfill: P) -> Self {
+        Self {
+            executable: None,
+            polyfill: polyfill.into(),
+            sandbox: true,
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// The path to the Chrome executable.
+    /// By default, an installed Chrome or Chromium is detected automatically.
+    pub fn executable<P: Into<PathBuf>>(mut self, executable: P) -> Self {
+        self.executable = Some(executable.into());
+        self
+    }
+
+    /// Whether Chrome runs in its sandbox, which is enabled by default.
+    /// Disabling it is required when running as root, for example in some containers.
+    pub fn sandbox(mut self, sandbox: bool) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// The maximum time paged.js may take to lay out the pages.
+    /// Defaults to 60 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn print(&self, input: &Path, cancellation: &Cancellation) -> Result<Vec<u8>, Error> {
+        let options = LaunchOptions::default_builder()
+            .path(self.executable.clone())
+            .sandbox(self.sandbox)
+            .build()
+            .map_err(|err| Error::ConfigurationError(err.to_string()))?;
+
+        // The browser process is killed once it is dropped.
+        let browser = Browser::new(options).map_err(backend_error)?;
+        let tab = browser.new_tab().map_err(backend_error)?;
+        cancellation.watch(tab.clone())?;
+
+        let result = self.print_tab(&tab, input, cancellation);
+        cancellation.unwatch();
+        result
+    }
+
+    fn print_tab(&self, tab: &Tab, input: &Path, cancellation: &Cancellation) -> Result<Vec<u8>, Error> {
+        tab.navigate_to(&file_url(input)).map_err(backend_error)?;
+        tab.wait_until_navigated().map_err(backend_error)?;
+        cancellation.check()?;
+        tab.wait_for_element_with_custom_timeout(&format!("html[{READY_ATTRIBUTE}]"), self.timeout)
+            .map_err(backend_error)?;
+        cancellation.check()?;
+
+        tab.print_to_pdf(Some(PrintToPdfOptions {
+            print_background: Some(true),
+            prefer_css_page_size: Some(true),
+            ..Default::default()
+        }))
+        .map_err(backend_error)
+    }
+}
+
+impl PdfBackend for HeadlessChrome {
+    fn render<'a>(&'a self, input: &'a Path, output: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let polyfill = tokio::fs::canonicalize(&self.polyfill).await?;
+            let html = tokio::fs::read_to_string(input).await?;
+            tokio::fs::write(input, inject_polyfill(&html, &polyfill)).await?;
+
+            let backend = self.clone();
+            let input = tokio::fs::canonicalize(input).await?;
+            // The blocking print cannot be aborted, so it is cancelled by closing its tab once the render is dropped,
+            // for example when it timed out. The slot of the render stays occupied until Chrome exited.
+            let cancellation = Arc::new(Cancellation::default());
+            let _cancel_on_drop = CancelOnDrop(cancellation.clone());
+            let slot = take_render_slot();
+            let pdf = tokio::task::spawn_blocking(move || {
+                let _slot = slot;
+                backend.print(&input, &cancellation)
+            })
+            .await
+            .map_err(backend_error)??;
+
+            tokio::fs::write(output, pdf).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Cancels a print that runs on a blocking thread.
+#[derive(Default)]
+struct Cancellation {
+    cancelled: AtomicBool,
+    tab: Mutex<Option<Arc<Tab>>>,
+}
+
+impl Cancellation {
+    /// Closes the tab once the print is cancelled.
+    fn watch(&self, tab: Arc<Tab>) -> Result<(), Error> {
+        *self.tab.lock().unwrap() = Some(tab);
+        // The print may have been cancelled before the tab was opened.
+        self.check()
+    }
+
+    fn unwatch(&self) {
+        self.tab.lock().unwrap().take();
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Some(tab) = self.tab.lock().unwrap().take() {
+            // Closing the tab makes the pending commands of the print fail.
+            // It waits for Chrome to respond, so it must not block the async runtime.
+            std::thread::spawn(move || {
+                let _ = tab.close(false);
+            });
+        }
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(Error::BackendError("the print was cancelled".to_owned()));
+        }
+        Ok(())
+    }
+}
+
+/// Cancels the print once the render is dropped.
+struct CancelOnDrop(Arc<Cancellation>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Adds the paged.js polyfill to the head of the document,
+/// configured to mark the document as ready once all pages are laid out.
+fn inject_polyfill(html: &str, polyfill: &Path) -> String {
+    let scripts = format!(
+        r#"<script>window.PagedConfig = {{ after: () => document.documentElement.setAttribute("{READY_ATTRIBUTE}", "") }};</script><script src="{}"></script>"#,
+        file_url(polyfill)
+    );
+
+    match html.find("<head>") {
+        Some(index) => {
+            let (before, after) = html.split_at(index + "<head>".len());
+            format!("{before}{scripts}{after}")
+        }
+        None => format!("{scripts}{html}"),
+    }
+}
+
+fn file_url(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let encoded = percent_encoding::utf8_percent_encode(&path, PATH);
+    if path.starts_with('/') {
+        format!("file://{encoded}")
+    } else {
+        format!("file:///{encoded}")
+    }
+}
+
+/// The characters that are percent-encoded in file URLs.
+const PATH: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+fn backend_error<E: ToString>(err: E) -> Error {
+    Error::BackendError(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_polyfill() {
+        let html = inject_polyfill("<head><title>Form</title></head><body></body>", Path::new("/srv/my site/paged.js"));
+
+        assert!(html.starts_with("<head><script>window.PagedConfig"));
+        assert!(html.contains(r#"<script src="file:///srv/my%20site/paged.js"></script><title>"#));
+    }
+}
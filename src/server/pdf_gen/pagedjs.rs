@@ -0,0 +1,82 @@
+use futures::future::BoxFuture;
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::process::Command;
+
+use super::{Error, PdfBackend};
+
+/// Prints PDFs with the `pagedjs-cli` command line tool,
+/// which can be installed using `npm install -g pagedjs-cli`.
+#[derive(Debug, Clone)]
+pub struct PagedJsCli {
+    executable: PathBuf,
+    args: Vec<OsString>,
+}
+
+impl Default for PagedJsCli {
+    fn default() -> Self {
+        Self {
+            executable: PathBuf::from("pagedjs-cli"),
+            args: Vec::new(),
+        }
+    }
+}
+
+impl PagedJsCli {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The path to the `pagedjs-cli` executable.
+    /// Defaults to `pagedjs-cli`, which is looked up in the `PATH`.
+    pub fn executable<P: Into<PathBuf>>(mut self, executable: P) -> Self {
+        self.executable = executable.into();
+        self
+    }
+
+    /// Adds an argument that is passed to `pagedjs-cli` before the input and output files.
+    pub fn arg<S: Into<OsString>>(mut self, arg: S) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+}
+
+impl PdfBackend for PagedJsCli {
+    fn render<'a>(&'a self, input: &'a Path, output: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let mut command = Command::new(&self.executable);
+            if let Some(working_dir) = input.parent() {
+                command.current_dir(working_dir);
+            }
+
+            let child = command
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .args(&self.args)
+                .arg(file_name(input))
+                .arg("-o")
+                .arg(file_name(output))
+                // Kills the process if the render times out or the request is cancelled.
+                .kill_on_drop(true)
+                .spawn()?;
+
+            let output = child.wait_with_output().await?;
+            if !output.status.success() {
+                return Err(Error::PdfGenerationError {
+                    status: output.status,
+                    stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+                });
+            }
+
+            Ok(())
+        })
+    }
+}
+
+fn file_name(path: &Path) -> &OsStr {
+    path.file_name().expect("temporary files have a file name")
+}
@@ -0,0 +1,175 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::process::Command;
+
+use super::Error;
+use crate::DocumentMetadata;
+
+/// Converts generated PDFs to PDF/A-2b using Ghostscript, for long-term archiving.
+/// All fonts are embedded, colors are converted to sRGB, and the metadata of the document is written
+/// to the document information dictionary, from which Ghostscript creates the XMP metadata.
+#[derive(Debug, Clone)]
+pub struct PdfA {
+    executable: PathBuf,
+    icc_profile: Option<PathBuf>,
+}
+
+impl Default for PdfA {
+    fn default() -> Self {
+        Self {
+            executable: PathBuf::from("gs"),
+            icc_profile: None,
+        }
+    }
+}
+
+impl PdfA {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The path to the Ghostscript executable.
+    /// Defaults to `gs`, which is looked up in the `PATH`.
+    pub fn executable<P: Into<PathBuf>>(mut self, executable: P) -> Self {
+        self.executable = executable.into();
+        self
+    }
+
+    /// The RGB ICC profile that is embedded as output intent.
+    /// Defaults to the sRGB profile that is bundled with Ghostscript.
+    pub fn icc_profile<P: Into<PathBuf>>(mut self, icc_profile: P) -> Self {
+        self.icc_profile = Some(icc_profile.into());
+        self
+    }
+
+    /// Converts the PDF at `input` and writes the result to `output`,
+    /// using `definitions` as temporary file for the PostScript definitions.
+    pub(super) async fn convert(
+        &self,
+        input: &Path,
+        output: &Path,
+        definitions: &Path,
+        metadata: &DocumentMetadata,
+    ) -> Result<(), Error> {
+        let icc_profile = self.icc_profile.as_deref().unwrap_or(Path::new("srgb.icc"));
+        tokio::fs::write(definitions, pdf_a_definitions(icc_profile, metadata)).await?;
+
+        let mut command = Command::new(&self.executable);
+        if let Some(icc_profile) = &self.icc_profile {
+            command.arg(format!("--permit-file-read={}", icc_profile.display()));
+        }
+
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .args([
+                "-dPDFA=2",
+                "-dPDFACompatibilityPolicy=1",
+                "-dBATCH",
+                "-dNOPAUSE",
+                "-dNOOUTERSAVE",
+                "-dQUIET",
+                "-dEmbedAllFonts=true",
+                "-dSubsetFonts=true",
+                "-sDEVICE=pdfwrite",
+                "-sColorConversionStrategy=RGB",
+            ])
+            .arg(format!("-sOutputFile={}", output.display()))
+            .arg(definitions)
+            .arg(input)
+            // Kills the process if the render times out or the request is cancelled.
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let result = child.wait_with_output().await?;
+        if !result.status.success() {
+            return Err(Error::PdfGenerationError {
+                status: result.status,
+                stderr: String::from_utf8_lossy(&result.stderr).trim().to_owned(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Creates the PostScript definitions that add the output intent and the metadata.
+fn pdf_a_definitions(icc_profile: &Path, metadata: &DocumentMetadata) -> String {
+    let mut definitions = String::from("%!\n");
+
+    let keywords = (!metadata.keywords.is_empty()).then(|| metadata.keywords.join(", "));
+    let entries = [
+        ("Title", metadata.title.as_deref()),
+        ("Author", metadata.author.as_deref()),
+        ("Subject", metadata.subject.as_deref()),
+        ("Keywords", keywords.as_deref()),
+        ("Creator", Some("nova-forms")),
+    ];
+    definitions.push('[');
+    for (key, value) in entries {
+        if let Some(value) = value {
+            write!(definitions, " /{key} {}", ps_string(value)).unwrap();
+        }
+    }
+    definitions.push_str(" /DOCINFO pdfmark\n");
+
+    if let Some(language) = &metadata.language {
+        writeln!(definitions, "[{{Catalog}} << /Lang {} >> /PUT pdfmark", ps_string(language)).unwrap();
+    }
+
+    let icc_profile = icc_profile.to_string_lossy().replace('\\', "\\\\").replace('(', "\\(").replace(')', "\\)");
+    writeln!(definitions, "/ICCProfile ({icc_profile}) def").unwrap();
+    definitions.push_str(
+        "[/_objdef {icc_PDFA} /type /stream /OBJ pdfmark\n\
+         [{icc_PDFA} << /N 3 >> /PUT pdfmark\n\
+         [{icc_PDFA} ICCProfile (r) file /PUT pdfmark\n\
+         [/_objdef {OutputIntent_PDFA} /type /dict /OBJ pdfmark\n\
+         [{OutputIntent_PDFA} << /Type /OutputIntent /S /GTS_PDFA1 /DestOutputProfile {icc_PDFA} \
+         /OutputConditionIdentifier (sRGB) >> /PUT pdfmark\n\
+         [{Catalog} << /OutputIntents [ {OutputIntent_PDFA} ] >> /PUT pdfmark\n",
+    );
+
+    definitions
+}
+
+/// Encodes a PostScript string as UTF-16BE with byte order mark,
+/// which is how PDF text strings outside of ASCII are represented.
+fn ps_string(value: &str) -> String {
+    let mut encoded = String::from("<FEFF");
+    for unit in value.encode_utf16() {
+        write!(encoded, "{unit:04X}").unwrap();
+    }
+    encoded.push('>');
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pdf_a_definitions() {
+        let metadata = DocumentMetadata {
+            title: Some("Anmeldung Ä".to_owned()),
+            keywords: vec!["a".to_owned(), "b".to_owned()],
+            language: Some("de".to_owned()),
+            ..Default::default()
+        };
+        let definitions = pdf_a_definitions(Path::new("srgb.icc"), &metadata);
+
+        assert!(definitions.contains(&format!("[ /Title {} /Keywords {} /Creator", ps_string("Anmeldung Ä"), ps_string("a, b"))));
+        assert!(definitions.contains("[{Catalog} << /Lang <FEFF00640065> >> /PUT pdfmark"));
+        assert!(!definitions.contains("/Author"));
+        assert!(definitions.contains("/ICCProfile (srgb.icc) def"));
+        assert!(definitions.contains("/OutputIntents"));
+    }
+
+    #[test]
+    fn test_ps_string() {
+        assert_eq!(ps_string("Aä"), "<FEFF004100E4>");
+    }
+}
@@ -0,0 +1,648 @@
+use flate2::read::ZlibDecoder;
+use regex::bytes::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    ops::Range,
+};
+
+use super::Error;
+
+/// The structure of an existing PDF that is needed to append an incremental update.
+/// This is not a general PDF parser, it only understands the files produced by the PDF backends,
+/// with either a cross-reference table or a cross-reference stream.
+pub(super) struct PdfFile<'a> {
+    data: &'a [u8],
+    startxref: usize,
+    xref_stream: bool,
+    size: u32,
+    root: u32,
+    id: Option<Vec<u8>>,
+    catalog: Vec<u8>,
+}
+
+/// An incremental update with a signature dictionary,
+/// whose `/Contents` still have to be filled with the signature.
+pub(super) struct SignatureUpdate {
+    pub(super) data: Vec<u8>,
+    /// The range of the hex string that holds the signature, without the angle brackets.
+    pub(super) contents: Range<usize>,
+}
+
+impl SignatureUpdate {
+    /// The parts of the document that are covered by the signature.
+    pub(super) fn signed_data(&self) -> [&[u8]; 2] {
+        [&self.data[..self.contents.start - 1], &self.data[self.contents.end + 1..]]
+    }
+}
+
+impl<'a> PdfFile<'a> {
+    pub(super) fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        // The last `startxref` of the file points to the newest cross-reference section.
+        let position = data
+            .windows(b"startxref".len())
+            .rposition(|window| window == b"startxref")
+            .ok_or_else(|| invalid("missing startxref"))?;
+        let startxref = leading_number(&data[position + b"startxref".len()..]).ok_or_else(|| invalid("invalid startxref"))?;
+
+        let mut objects = HashMap::new();
+        let mut trailer = None;
+        let mut xref_stream = false;
+        let mut visited = HashSet::new();
+        let mut section = Some(startxref);
+        // Newer sections take precedence over the sections of previous updates they point to with `/Prev`.
+        while let Some(offset) = section {
+            if !visited.insert(offset) {
+                return Err(invalid("cyclic cross-reference sections"));
+            }
+            let is_table = data.get(offset..).ok_or_else(|| invalid("invalid cross-reference offset"))?.starts_with(b"xref");
+            let dict = if is_table {
+                let dict = read_xref_table(data, offset, &mut objects)?;
+                // Hybrid files keep the objects of object streams in an additional cross-reference stream.
+                if let Some(xref_stream_offset) = capture(&dict, r"/XRefStm\s+(\d+)") {
+                    read_xref_stream(data, xref_stream_offset, &mut objects)?;
+                }
+                dict
+            } else {
+                read_xref_stream(data, offset, &mut objects)?
+            };
+            section = capture(&dict, r"/Prev\s+(\d+)");
+            if trailer.is_none() {
+                xref_stream = !is_table;
+                trailer = Some(dict);
+            }
+        }
+        let trailer = trailer.ok_or_else(|| invalid("missing trailer"))?;
+
+        let size = capture(&trailer, r"/Size\s+(\d+)").ok_or_else(|| invalid("missing /Size"))?;
+        let root = capture(&trailer, r"/Root\s+(\d+)\s+0\s+R").ok_or_else(|| invalid("missing /Root"))?;
+        let id = Regex::new(r"/ID\s*(\[\s*<[0-9A-Fa-f]*>\s*<[0-9A-Fa-f]*>\s*\])")
+            .unwrap()
+            .captures(&trailer)
+            .map(|captures| captures[1].to_vec());
+
+        let catalog = find_object(data, &objects, root)?;
+        if find(&catalog, b"/AcroForm").is_some() {
+            return Err(Error::SigningError("the PDF already contains a form".to_owned()));
+        }
+
+        Ok(Self {
+            data,
+            startxref,
+            xref_stream,
+            size,
+            root,
+            id,
+            catalog,
+        })
+    }
+
+    /// Appends an invisible signature field with a signature dictionary containing the given entries,
+    /// and reserves `capacity` bytes for the signature.
+    pub(super) fn append_signature(&self, entries: &str, capacity: usize) -> SignatureUpdate {
+        let signature = self.size;
+        let field = self.size + 1;
+
+        let mut data = self.data.to_vec();
+        if !data.ends_with(b"\n") {
+            data.push(b'\n');
+        }
+        let mut offsets = Vec::new();
+
+        // The byte range is filled in once the position of the contents is known.
+        let byte_range_placeholder = format!("[{}]", " ".repeat(40));
+        offsets.push((signature, data.len()));
+        data.extend(format!("{signature} 0 obj\n<< /Type /Sig /Filter /Adobe.PPKLite /SubFilter /ETSI.CAdES.detached {entries} /ByteRange ").as_bytes());
+        let byte_range = data.len()..data.len() + byte_range_placeholder.len();
+        data.extend(byte_range_placeholder.as_bytes());
+        data.extend(b" /Contents <");
+        let contents = data.len()..data.len() + 2 * capacity;
+        data.resize(contents.end, b'0');
+        data.extend(b"> >>\nendobj\n");
+
+        // Invisible signatures have an empty rectangle, and are printable as required by PDF/A.
+        offsets.push((field, data.len()));
+        data.extend(format!("{field} 0 obj\n<< /Type /Annot /Subtype /Widget /FT /Sig /T (Signature) /V {signature} 0 R /F 4 /Rect [0 0 0 0] >>\nendobj\n").as_bytes());
+
+        offsets.push((self.root, data.len()));
+        data.extend(format!("{} 0 obj\n", self.root).as_bytes());
+        data.extend(&self.catalog[..self.catalog.len() - 2]);
+        data.extend(format!("/AcroForm << /Fields [{field} 0 R] /SigFlags 3 >> >>\nendobj\n").as_bytes());
+
+        self.append_xref(&mut data, offsets);
+
+        // The closing bracket of the placeholder is kept, the remaining space is padding.
+        let range = format!(
+            "[0 {} {} {}",
+            contents.start - 1,
+            contents.end + 1,
+            data.len() - contents.end - 1
+        );
+        data[byte_range.start..byte_range.start + range.len()].copy_from_slice(range.as_bytes());
+
+        SignatureUpdate { data, contents }
+    }
+
+    /// Appends the cross-reference section of the update, in the same format as the original.
+    fn append_xref(&self, data: &mut Vec<u8>, mut offsets: Vec<(u32, usize)>) {
+        let id = self.id.as_ref().map(|id| format!(" /ID {}", String::from_utf8_lossy(id))).unwrap_or_default();
+        let xref = data.len();
+
+        if self.xref_stream {
+            let stream = self.size + 2;
+            offsets.push((stream, xref));
+            offsets.sort();
+
+            let index = offsets.iter().map(|(number, _)| format!("{number} 1")).collect::<Vec<_>>().join(" ");
+            let mut entries = Vec::new();
+            for (_, offset) in &offsets {
+                entries.push(1);
+                entries.extend((*offset as u32).to_be_bytes());
+                entries.extend([0, 0]);
+            }
+
+            data.extend(format!(
+                "{stream} 0 obj\n<< /Type /XRef /Size {} /W [1 4 2] /Index [{index}] /Root {} 0 R /Prev {}{id} /Length {} >>\nstream\n",
+                stream + 1,
+                self.root,
+                self.startxref,
+                entries.len()
+            ).as_bytes());
+            data.extend(entries);
+            data.extend(b"\nendstream\nendobj\n");
+        } else {
+            offsets.sort();
+
+            data.extend(b"xref\n");
+            for (number, offset) in &offsets {
+                data.extend(format!("{number} 1\n{offset:010} 00000 n \n").as_bytes());
+            }
+            data.extend(format!(
+                "trailer\n<< /Size {} /Root {} 0 R /Prev {}{id} >>\n",
+                self.size + 2,
+                self.root,
+                self.startxref
+            ).as_bytes());
+        }
+
+        data.extend(format!("startxref\n{xref}\n%%EOF\n").as_bytes());
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::SigningError(format!("unsupported PDF: {reason}"))
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|window| window == needle)
+}
+
+fn number<T: std::str::FromStr>(digits: &[u8]) -> Option<T> {
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+fn capture<T: std::str::FromStr>(data: &[u8], pattern: &str) -> Option<T> {
+    number(&Regex::new(pattern).unwrap().captures(data)?[1])
+}
+
+/// Returns the dictionary at the start of `data`, skipping leading whitespace.
+fn dictionary(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let start = data
+        .iter()
+        .position(|c| !c.is_ascii_whitespace())
+        .filter(|&start| data[start..].starts_with(b"<<"))
+        .ok_or_else(|| invalid("expected a dictionary"))?;
+    let data = &data[start..];
+
+    let mut depth = 0;
+    let mut i = 0;
+    while i < data.len() {
+        match (data[i], data.get(i + 1)) {
+            (b'<', Some(b'<')) => {
+                depth += 1;
+                i += 1;
+            }
+            (b'>', Some(b'>')) => {
+                depth -= 1;
+                i += 1;
+                if depth == 0 {
+                    return Ok(data[..=i].to_vec());
+                }
+            }
+            // Skips hex strings.
+            (b'<', _) => i += data[i..].iter().position(|&c| c == b'>').unwrap_or(data.len()),
+            // Skips literal strings, which may contain balanced or escaped parentheses.
+            (b'(', _) => {
+                let mut nesting = 0;
+                while i < data.len() {
+                    match data[i] {
+                        b'\\' => i += 1,
+                        b'(' => nesting += 1,
+                        b')' => {
+                            nesting -= 1;
+                            if nesting == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    i += 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    Err(invalid("unterminated dictionary"))
+}
+
+/// Where an object in use is stored, according to the cross-reference data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Offset(usize),
+    /// The object with the given index in an object stream.
+    Compressed { stream: u32, index: usize },
+}
+
+/// Reads a cross-reference table and returns its trailer dictionary.
+/// Objects that are already known from a newer section are kept.
+fn read_xref_table(data: &[u8], offset: usize, objects: &mut HashMap<u32, Location>) -> Result<Vec<u8>, Error> {
+    let section = &data[offset + b"xref".len()..];
+    let end = find(section, b"trailer").ok_or_else(|| invalid("missing trailer"))?;
+    let mut tokens = section[..end]
+        .split(|c| c.is_ascii_whitespace())
+        .filter(|token| !token.is_empty());
+
+    while let Some(first) = tokens.next() {
+        let first: u32 = number(first).ok_or_else(|| invalid("invalid cross-reference table"))?;
+        let count: u32 = tokens.next().and_then(number).ok_or_else(|| invalid("invalid cross-reference table"))?;
+        for object_number in first..first.saturating_add(count) {
+            let (Some(offset), Some(_generation), Some(kind)) = (tokens.next(), tokens.next(), tokens.next()) else {
+                return Err(invalid("truncated cross-reference table"));
+            };
+            if kind == b"n" {
+                let offset = number(offset).ok_or_else(|| invalid("invalid cross-reference table"))?;
+                objects.entry(object_number).or_insert(Location::Offset(offset));
+            }
+        }
+    }
+
+    dictionary(&section[end + b"trailer".len()..])
+}
+
+/// Reads a cross-reference stream and returns its dictionary, which also serves as trailer.
+/// Objects that are already known from a newer section are kept.
+fn read_xref_stream(data: &[u8], offset: usize, objects: &mut HashMap<u32, Location>) -> Result<Vec<u8>, Error> {
+    let object = object_at(data, offset, None)?;
+    let dict = dictionary(object)?;
+    if find(&dict, b"/XRef").is_none() {
+        return Err(invalid("missing cross-reference stream"));
+    }
+    // The length of cross-reference streams is always a direct object.
+    let entries = stream_contents(data, object, &dict, &HashMap::new())?;
+
+    let widths = numbers(&dict, r"/W\s*\[([^\]]*)\]").ok_or_else(|| invalid("missing /W"))?;
+    let [type_width, field_width, index_width] = widths[..] else {
+        return Err(invalid("invalid /W"));
+    };
+    if widths.iter().any(|&width| width > 8) {
+        return Err(invalid("invalid /W"));
+    }
+    let size = capture::<usize>(&dict, r"/Size\s+(\d+)").ok_or_else(|| invalid("missing /Size"))?;
+    let index = numbers(&dict, r"/Index\s*\[([^\]]*)\]").unwrap_or_else(|| vec![0, size]);
+
+    let entry_width = type_width + field_width + index_width;
+    if entry_width == 0 || !index.len().is_multiple_of(2) {
+        return Err(invalid("invalid cross-reference stream"));
+    }
+    let mut entries = entries.chunks_exact(entry_width);
+    for subsection in index.chunks(2) {
+        for object_number in subsection[0]..subsection[0].saturating_add(subsection[1]) {
+            let entry = entries.next().ok_or_else(|| invalid("truncated cross-reference stream"))?;
+            let (kind, rest) = entry.split_at(type_width);
+            let (field, index) = rest.split_at(field_width);
+            // The type defaults to 1 if its width is zero.
+            let location = match if kind.is_empty() { 1 } else { big_endian(kind) } {
+                1 => Location::Offset(big_endian(field)),
+                2 => Location::Compressed {
+                    stream: u32::try_from(big_endian(field)).map_err(|_| invalid("invalid object stream number"))?,
+                    index: big_endian(index),
+                },
+                _ => continue,
+            };
+            let object_number = u32::try_from(object_number).map_err(|_| invalid("invalid object number"))?;
+            objects.entry(object_number).or_insert(location);
+        }
+    }
+
+    Ok(dict)
+}
+
+/// Finds the dictionary of an object, which is either stored directly or in an object stream.
+fn find_object(data: &[u8], objects: &HashMap<u32, Location>, object_number: u32) -> Result<Vec<u8>, Error> {
+    match objects.get(&object_number) {
+        Some(Location::Offset(offset)) => dictionary(object_at(data, *offset, Some(object_number))?),
+        Some(Location::Compressed { stream, index }) => {
+            let Some(Location::Offset(offset)) = objects.get(stream) else {
+                return Err(invalid("missing object stream"));
+            };
+            let object = object_at(data, *offset, Some(*stream))?;
+            let dict = dictionary(object)?;
+            let stream = stream_contents(data, object, &dict, objects)?;
+
+            let first: usize = capture(&dict, r"/First\s+(\d+)").ok_or_else(|| invalid("missing /First"))?;
+            let header = stream.get(..first).ok_or_else(|| invalid("invalid /First"))?;
+            let pairs = header
+                .split(|c| c.is_ascii_whitespace())
+                .filter(|part| !part.is_empty())
+                .map(number::<usize>)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("invalid object stream"))?;
+
+            match pairs.chunks_exact(2).nth(*index) {
+                Some(&[number, offset]) if number == object_number as usize => {
+                    dictionary(stream.get(first + offset..).ok_or_else(|| invalid("invalid object stream"))?)
+                }
+                _ => Err(invalid("invalid object stream")),
+            }
+        }
+        None => Err(invalid("missing object")),
+    }
+}
+
+/// Returns the data following the `obj` keyword of the object at the given offset,
+/// after checking that it is the expected object.
+fn object_at(data: &[u8], offset: usize, object_number: Option<u32>) -> Result<&[u8], Error> {
+    let object = data.get(offset..).ok_or_else(|| invalid("invalid object offset"))?;
+    let header = Regex::new(r"^\s*(\d+)\s+\d+\s+obj").unwrap();
+    let found = header.captures(object).ok_or_else(|| invalid("invalid object offset"))?;
+    if object_number.is_some_and(|expected| number(&found[1]) != Some(expected)) {
+        return Err(invalid("invalid object offset"));
+    }
+    Ok(&object[found.get(0).unwrap().end()..])
+}
+
+/// Returns the decoded contents of the stream following the dictionary at the start of `object`.
+/// An indirect `/Length` is resolved with the cross-reference data.
+fn stream_contents(data: &[u8], object: &[u8], dict: &[u8], objects: &HashMap<u32, Location>) -> Result<Vec<u8>, Error> {
+    let rest = object.trim_ascii_start()[dict.len()..].trim_ascii_start();
+    let rest = rest.strip_prefix(b"stream").ok_or_else(|| invalid("missing stream"))?;
+    let encoded = rest
+        .strip_prefix(b"\r\n")
+        .or_else(|| rest.strip_prefix(b"\n"))
+        .ok_or_else(|| invalid("missing stream"))?;
+
+    let length = match capture::<u32>(dict, r"/Length\s+(\d+)\s+\d+\s+R") {
+        Some(length_object) => match objects.get(&length_object) {
+            Some(Location::Offset(offset)) => leading_number(object_at(data, *offset, Some(length_object))?),
+            _ => None,
+        },
+        None => capture(dict, r"/Length\s+(\d+)"),
+    };
+    let encoded = length
+        .and_then(|length| encoded.get(..length))
+        .ok_or_else(|| invalid("invalid stream length"))?;
+
+    let filters = match Regex::new(r"/Filter\s*(\[[^\]]*\]|/[A-Za-z0-9]+)").unwrap().captures(dict) {
+        Some(captures) => Regex::new(r"/([A-Za-z0-9]+)")
+            .unwrap()
+            .captures_iter(captures.get(1).unwrap().as_bytes())
+            .map(|name| name.get(1).unwrap().as_bytes())
+            .collect(),
+        None => Vec::new(),
+    };
+    match filters[..] {
+        [] => return Ok(encoded.to_vec()),
+        [b"FlateDecode"] => {}
+        _ => return Err(invalid("unsupported stream filter")),
+    }
+    let mut decoded = Vec::new();
+    ZlibDecoder::new(encoded)
+        .read_to_end(&mut decoded)
+        .map_err(|_| invalid("invalid compressed stream"))?;
+
+    match capture::<u32>(dict, r"/Predictor\s+(\d+)") {
+        None | Some(1) => Ok(decoded),
+        Some(10..=15) => png_unpredict(&decoded, capture(dict, r"/Columns\s+(\d+)").unwrap_or(1)),
+        Some(_) => Err(invalid("unsupported predictor")),
+    }
+}
+
+/// Reverses the PNG predictors of a decoded stream, where each row starts with the type of its predictor.
+fn png_unpredict(data: &[u8], columns: usize) -> Result<Vec<u8>, Error> {
+    if columns == 0 {
+        return Err(invalid("invalid /Columns"));
+    }
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; columns];
+    for row in data.chunks(columns + 1) {
+        let (&predictor, row) = row.split_first().ok_or_else(|| invalid("invalid predictor"))?;
+        let mut current = row.to_vec();
+        for i in 0..current.len() {
+            // The streams of PDFs are not split into pixels, so the left neighbour is the previous byte.
+            let left = if i > 0 { current[i - 1] } else { 0 };
+            let up = previous[i];
+            let up_left = if i > 0 { previous[i - 1] } else { 0 };
+            let prediction = match predictor {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(invalid("invalid predictor")),
+            };
+            current[i] = current[i].wrapping_add(prediction);
+        }
+        decoded.extend(&current);
+        previous[..current.len()].copy_from_slice(&current);
+    }
+    Ok(decoded)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distances = [left, up, up_left].map(|value| (estimate - value as i16).abs());
+    if distances[0] <= distances[1] && distances[0] <= distances[2] {
+        left
+    } else if distances[1] <= distances[2] {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Parses the number at the start of `data`, skipping leading whitespace.
+fn leading_number<T: std::str::FromStr>(data: &[u8]) -> Option<T> {
+    let data = data.trim_ascii_start();
+    number(&data[..data.iter().position(|c| !c.is_ascii_digit()).unwrap_or(data.len())])
+}
+
+/// Parses the numbers of an array captured by the pattern.
+fn numbers(data: &[u8], pattern: &str) -> Option<Vec<usize>> {
+    Regex::new(pattern).unwrap().captures(data)?[1]
+        .split(|c| c.is_ascii_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(number)
+        .collect()
+}
+
+fn big_endian(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |value, &byte| (value << 8) | byte as usize)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    /// Creates a minimal PDF with a cross-reference table.
+    /// Its page has a binary content stream that contains an object resembling a catalog with a form.
+    pub(in super::super) fn classic_pdf() -> Vec<u8> {
+        let content = b"\xff\x00\x9c 1 0 obj << /Type /Catalog /AcroForm << >> >> endobj \x00";
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R /Lang (de) >>".to_owned(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_owned(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] /Contents 4 0 R >>".to_owned(),
+            format!("<< /Length {} >>\nstream\n", content.len()),
+        ];
+        let mut data = b"%PDF-1.7\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(data.len());
+            data.extend(format!("{} 0 obj\n{object}", i + 1).as_bytes());
+            if i == 3 {
+                data.extend(content);
+                data.extend(b"\nendstream");
+            }
+            data.extend(b"\nendobj\n");
+        }
+        let xref = data.len();
+        data.extend(b"xref\n0 5\n0000000000 65535 f \n");
+        for offset in offsets {
+            data.extend(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+        data.extend(format!("trailer\n<< /Size 5 /Root 1 0 R /ID [<ab> <ab>] >>\nstartxref\n{xref}\n%%EOF\n").as_bytes());
+        data
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Creates a minimal PDF with the catalog in a compressed object stream
+    /// and a cross-reference stream that is compressed with the PNG up predictor.
+    fn compressed_pdf() -> Vec<u8> {
+        let objects = "1 0 2 39 << /Type /Catalog /Pages 2 0 R >>      << /Type /Pages /Kids [] /Count 0 >>";
+        let stream = compress(objects.as_bytes());
+
+        let mut data = b"%PDF-1.7\n".to_vec();
+        let object_stream = data.len();
+        data.extend(format!("3 0 obj\n<< /Type /ObjStm /N 2 /First 9 /Filter /FlateDecode /Length {} >>\nstream\n", stream.len()).as_bytes());
+        data.extend(stream);
+        data.extend(b"\nendstream\nendobj\n");
+        let xref = data.len();
+
+        // Each entry has a type, a 2 byte offset or object stream number and a 1 byte index.
+        let entries: [[u8; 4]; 5] = [
+            [0, 0, 0, 0],
+            [2, 0, 3, 0],
+            [2, 0, 3, 1],
+            [1, (object_stream >> 8) as u8, object_stream as u8, 0],
+            [1, (xref >> 8) as u8, xref as u8, 0],
+        ];
+        let mut previous = [0; 4];
+        let mut predicted = Vec::new();
+        for entry in entries {
+            predicted.push(2);
+            predicted.extend(entry.iter().zip(previous).map(|(byte, up)| byte.wrapping_sub(up)));
+            previous = entry;
+        }
+        let stream = compress(&predicted);
+        data.extend(format!(
+            "4 0 obj\n<< /Type /XRef /Size 5 /Root 1 0 R /W [1 2 1] /Filter /FlateDecode /DecodeParms << /Columns 4 /Predictor 12 >> /Length {} >>\nstream\n",
+            stream.len()
+        ).as_bytes());
+        data.extend(stream);
+        data.extend(b"\nendstream\nendobj\n");
+        data.extend(format!("startxref\n{xref}\n%%EOF\n").as_bytes());
+        data
+    }
+
+    #[test]
+    fn test_append_signature_classic() {
+        let data = classic_pdf();
+        let file = PdfFile::parse(&data).unwrap();
+        // The catalog is located with the cross-reference table instead of the content stream that resembles it.
+        assert_eq!(file.catalog, b"<< /Type /Catalog /Pages 2 0 R /Lang (de) >>");
+
+        let update = file.append_signature("/M (D:20240101000000Z)", 8);
+        let updated = String::from_utf8_lossy(&update.data).into_owned();
+
+        assert!(update.data.starts_with(&data));
+        assert!(updated.contains("1 0 obj\n<< /Type /Catalog /Pages 2 0 R /Lang (de) /AcroForm << /Fields [6 0 R] /SigFlags 3 >> >>"));
+        assert!(updated.contains("trailer\n<< /Size 7 /Root 1 0 R /Prev "));
+        assert!(updated.contains("/ID [<ab> <ab>]"));
+        assert_eq!(&update.data[update.contents.clone()], "0".repeat(16).as_bytes());
+
+        // The byte range covers everything except the contents.
+        let byte_range = format!("[0 {} {} {} ", update.contents.start - 1, update.contents.end + 1, update.data.len() - update.contents.end - 1);
+        assert!(updated.contains(&format!("/ByteRange {byte_range}")));
+        assert_eq!(update.signed_data().concat().len(), update.data.len() - 18);
+
+        // A signed PDF cannot be signed a second time.
+        assert!(matches!(PdfFile::parse(&update.data), Err(Error::SigningError(_))));
+    }
+
+    #[test]
+    fn test_append_signature_compressed() {
+        let data = compressed_pdf();
+        let file = PdfFile::parse(&data).unwrap();
+        assert!(file.xref_stream);
+        assert_eq!(file.catalog, b"<< /Type /Catalog /Pages 2 0 R >>");
+
+        let update = file.append_signature("", 8);
+        let updated = String::from_utf8_lossy(&update.data).into_owned();
+        assert!(updated.contains("/Type /XRef /Size 8 /W [1 4 2] /Index [1 1 5 1 6 1 7 1]"));
+
+        // The cross-reference stream of the update is understood as well.
+        assert!(matches!(PdfFile::parse(&update.data), Err(Error::SigningError(reason)) if reason.contains("form")));
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let mut data = classic_pdf();
+        let prev = std::str::from_utf8(&data[data.len() - 20..]).unwrap().split_whitespace().nth(1).unwrap().to_owned();
+        let catalog = data.len();
+        data.extend(b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R /Lang (en) >>\nendobj\n");
+        let xref = data.len();
+        data.extend(format!("xref\n0 1\n0000000000 65535 f \n1 1\n{catalog:010} 00000 n \ntrailer\n<< /Size 5 /Root 1 0 R /Prev {prev} >>\nstartxref\n{xref}\n%%EOF\n").as_bytes());
+
+        // The newest revision of the catalog is used.
+        let file = PdfFile::parse(&data).unwrap();
+        assert_eq!(file.catalog, b"<< /Type /Catalog /Pages 2 0 R /Lang (en) >>");
+        assert_eq!(file.startxref, xref);
+    }
+
+    #[test]
+    fn test_invalid_cross_reference() {
+        // The offset of the catalog points to the header of the file.
+        let mut data = classic_pdf();
+        let entry = find(&data, b"xref\n0 5\n").unwrap() + b"xref\n0 5\n".len() + 20;
+        data[entry..entry + 10].copy_from_slice(b"0000000000");
+        assert!(PdfFile::parse(&data).is_err());
+
+        // The previous section is the section itself.
+        assert!(PdfFile::parse(b"%PDF-1.7\nxref\n0 0\ntrailer\n<< /Size 1 /Root 1 0 R /Prev 9 >>\nstartxref\n9\n%%EOF").is_err());
+        assert!(PdfFile::parse(b"%PDF-1.7\nstartxref\n99\n%%EOF").is_err());
+    }
+
+    #[test]
+    fn test_dictionary() {
+        assert_eq!(dictionary(b" << /A (a >> b) /B <<>> /C <ff> >> rest").unwrap(), b"<< /A (a >> b) /B <<>> /C <ff> >>");
+        assert!(dictionary(b"[1 2]").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::{Error, PdfGen};
+
+/// Statistics about the renders of a `PdfGen`, see `PdfGen::metrics`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderMetrics {
+    /// The number of renders currently waiting in the queue.
+    pub queued: usize,
+    /// The number of renders currently running.
+    pub running: usize,
+    /// The number of renders that completed successfully.
+    pub completed: u64,
+    /// The number of renders that failed or timed out.
+    pub failed: u64,
+    /// The number of renders that were rejected because the queue was full.
+    pub rejected: u64,
+    /// The total time finished renders spent waiting in the queue.
+    pub total_queue_time: Duration,
+    /// The longest time a single render spent waiting in the queue.
+    pub max_queue_time: Duration,
+    /// The total time finished renders took, including failed renders.
+    pub total_render_time: Duration,
+    /// The longest time a single render took.
+    pub max_render_time: Duration,
+}
+
+impl RenderMetrics {
+    fn finished(&self) -> u64 {
+        self.completed + self.failed
+    }
+
+    /// The average time a render spent waiting in the queue.
+    pub fn average_queue_time(&self) -> Duration {
+        average(self.total_queue_time, self.finished())
+    }
+
+    /// The average time a render took.
+    pub fn average_render_time(&self) -> Duration {
+        average(self.total_render_time, self.finished())
+    }
+}
+
+fn average(total: Duration, count: u64) -> Duration {
+    if count == 0 {
+        Duration::ZERO
+    } else {
+        total.div_f64(count as f64)
+    }
+}
+
+/// The slot of a running render in the queue of a `PdfGen`, see `take_render_slot`.
+pub struct RenderSlot {
+    _permit: OwnedSemaphorePermit,
+}
+
+tokio::task_local! {
+    static RENDER_SLOT: Mutex<Option<RenderSlot>>;
+}
+
+/// Takes over the slot of the render that is currently running, so that it is only freed once the returned slot is dropped.
+/// `PdfBackend`s use this to keep the slot occupied by work that continues after the render was cancelled,
+/// for example a blocking task that prints with a browser.
+/// Returns `None` if called outside of a render or if the slot was already taken.
+pub fn take_render_slot() -> Option<RenderSlot> {
+    RENDER_SLOT.try_with(|slot| slot.lock().unwrap().take()).ok().flatten()
+}
+
+/// Limits the number of concurrent renders and the number of renders waiting for a free slot.
+pub(super) struct RenderPool {
+    slots: Arc<Semaphore>,
+    max_concurrent: usize,
+    max_queued: usize,
+    metrics: Mutex<RenderMetrics>,
+}
+
+/// Removes a render from the queue once it got a slot or was cancelled.
+struct Queued<'a>(&'a RenderPool);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.metrics.lock().unwrap().queued -= 1;
+    }
+}
+
+impl RenderPool {
+    pub(super) fn new(max_concurrent: usize, max_queued: usize) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(max_concurrent)),
+            max_concurrent,
+            max_queued,
+            metrics: Mutex::default(),
+        }
+    }
+
+    async fn acquire(&self) -> Result<RenderSlot, Error> {
+        if let Ok(permit) = self.slots.clone().try_acquire_owned() {
+            return Ok(RenderSlot { _permit: permit });
+        }
+
+        {
+            let mut metrics = self.metrics.lock().unwrap();
+            if metrics.queued >= self.max_queued {
+                metrics.rejected += 1;
+                return Err(Error::QueueFull);
+            }
+            metrics.queued += 1;
+        }
+
+        let _queued = Queued(self);
+        let permit = self.slots.clone().acquire_owned().await.expect("the semaphore is never closed");
+        Ok(RenderSlot { _permit: permit })
+    }
+
+    /// Runs the render once a slot is free, or fails with `Error::QueueFull` if too many renders are waiting.
+    pub(super) async fn run<T, F: Future<Output = Result<T, Error>>>(&self, render: F) -> Result<T, Error> {
+        let queued_at = Instant::now();
+        let slot = self.acquire().await?;
+
+        // The slot is freed once the render completed or was cancelled, unless the backend took it over.
+        let started_at = Instant::now();
+        let result = RENDER_SLOT.scope(Mutex::new(Some(slot)), render).await;
+
+        // Renders that are cancelled by the caller are not recorded.
+        let queue_time = started_at - queued_at;
+        let render_time = started_at.elapsed();
+        let mut metrics = self.metrics.lock().unwrap();
+        metrics.total_queue_time += queue_time;
+        metrics.max_queue_time = metrics.max_queue_time.max(queue_time);
+        metrics.total_render_time += render_time;
+        metrics.max_render_time = metrics.max_render_time.max(render_time);
+        if result.is_ok() {
+            metrics.completed += 1;
+        } else {
+            metrics.failed += 1;
+        }
+
+        result
+    }
+
+    fn metrics(&self) -> RenderMetrics {
+        RenderMetrics {
+            running: self.max_concurrent - self.slots.available_permits(),
+            ..self.metrics.lock().unwrap().clone()
+        }
+    }
+}
+
+impl PdfGen {
+    /// Returns statistics about the queue and the renders of this `PdfGen`,
+    /// for example to export them to a monitoring system.
+    pub fn metrics(&self) -> RenderMetrics {
+        self.pool.metrics()
+    }
+}
@@ -0,0 +1,649 @@
+use openssl::{
+    cms::{CMSOptions, CmsContentInfo},
+    hash::{hash, MessageDigest},
+    pkcs12::Pkcs12,
+    pkey::{Id, PKey, Private},
+    stack::Stack,
+    x509::X509,
+};
+use std::{fmt::Debug, time::Duration};
+
+use super::{pdf_file::PdfFile, Error};
+
+/// Applies a PAdES signature (`ETSI.CAdES.detached`) to generated PDFs,
+/// so that recipients can verify who produced a PDF and that it was not modified.
+/// The signature is created by OpenSSL, which must be version 3.0 or later to add the signing certificate as signed attribute.
+#[derive(Clone)]
+pub struct PdfSigner {
+    key: PKey<Private>,
+    certificate: X509,
+    chain: Vec<X509>,
+    reason: Option<String>,
+    location: Option<String>,
+    timestamp_url: Option<String>,
+    timeout: Duration,
+}
+
+impl PdfSigner {
+    /// Loads the signing certificate and its private key from PEM.
+    /// Any further certificates in `certificates` are embedded as certificate chain.
+    pub fn from_pem(certificates: &[u8], key: &[u8]) -> Result<Self, Error> {
+        let mut certificates = X509::stack_from_pem(certificates).map_err(signing_error)?.into_iter();
+        let certificate = certificates
+            .next()
+            .ok_or_else(|| Error::SigningError("no certificate found".to_owned()))?;
+        let key = PKey::private_key_from_pem(key).map_err(signing_error)?;
+
+        Self::new(key, certificate, certificates.collect())
+    }
+
+    /// Loads the signing certificate, its private key and the certificate chain from a PKCS#12 archive.
+    pub fn from_pkcs12(der: &[u8], password: &str) -> Result<Self, Error> {
+        let parsed = Pkcs12::from_der(der)
+            .and_then(|pkcs12| pkcs12.parse2(password))
+            .map_err(signing_error)?;
+        let (Some(key), Some(certificate)) = (parsed.pkey, parsed.cert) else {
+            return Err(Error::SigningError("the archive must contain a certificate and a private key".to_owned()));
+        };
+
+        Self::new(key, certificate, parsed.ca.map(|ca| ca.into_iter().collect()).unwrap_or_default())
+    }
+
+    fn new(key: PKey<Private>, certificate: X509, chain: Vec<X509>) -> Result<Self, Error> {
+        if !certificate.public_key().is_ok_and(|public_key| public_key.public_eq(&key)) {
+            return Err(Error::SigningError("the private key does not match the certificate".to_owned()));
+        }
+        if !matches!(key.id(), Id::RSA | Id::EC) {
+            return Err(Error::SigningError("only RSA and EC keys are supported".to_owned()));
+        }
+
+        Ok(Self {
+            key,
+            certificate,
+            chain,
+            reason: None,
+            location: None,
+            timestamp_url: None,
+            timeout: Duration::from_secs(10),
+        })
+    }
+
+    /// The reason for signing, which is shown by PDF viewers.
+    pub fn reason<S: Into<String>>(mut self, reason: S) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    /// The location of signing, which is shown by PDF viewers.
+    pub fn location<S: Into<String>>(mut self, location: S) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    /// Adds a trusted timestamp from the RFC 3161 timestamp authority at the given URL to each signature.
+    /// Without it, signatures contain no timestamp and signing works offline.
+    pub fn timestamp_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.timestamp_url = Some(url.into());
+        self
+    }
+
+    /// The maximum time a request to the timestamp authority may take.
+    /// Defaults to 10 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Signs a PDF and returns the signed PDF.
+    pub async fn sign(&self, pdf: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut entries = format!("/M {}", pdf_string(&signing_time()));
+        if let Some(reason) = &self.reason {
+            entries.push_str(&format!(" /Reason {}", pdf_string(reason)));
+        }
+        if let Some(location) = &self.location {
+            entries.push_str(&format!(" /Location {}", pdf_string(location)));
+        }
+
+        let mut update = PdfFile::parse(pdf)?.append_signature(&entries, self.capacity()?);
+        let signature = self.signed_data(&update.signed_data().concat()).await?;
+
+        let signature = hex::encode_upper(signature);
+        if signature.len() > update.contents.len() {
+            return Err(Error::SigningError("the signature is larger than the reserved space".to_owned()));
+        }
+        update.data[update.contents.start..update.contents.start + signature.len()].copy_from_slice(signature.as_bytes());
+
+        Ok(update.data)
+    }
+
+    /// The number of bytes reserved for the signature.
+    fn capacity(&self) -> Result<usize, Error> {
+        let mut capacity = 4096 + self.key.size();
+        for certificate in std::iter::once(&self.certificate).chain(&self.chain) {
+            capacity += certificate.to_der().map_err(signing_error)?.len();
+        }
+        if self.timestamp_url.is_some() {
+            capacity += 8192;
+        }
+        Ok(capacity)
+    }
+
+    /// Creates the detached CMS `SignedData` for the signed parts of the document.
+    async fn signed_data(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut chain = Stack::new().map_err(signing_error)?;
+        for certificate in &self.chain {
+            chain.push(certificate.clone()).map_err(signing_error)?;
+        }
+        // Adds the signing certificate as signed attribute, so that it cannot be substituted.
+        let cades = CMSOptions::from_bits_retain(CMS_CADES);
+        let flags = CMSOptions::DETACHED | CMSOptions::BINARY | CMSOptions::NOSMIMECAP | cades;
+        let cms = CmsContentInfo::sign(Some(&self.certificate), Some(&self.key), Some(&chain), Some(data), flags)
+            .and_then(|cms| cms.to_der())
+            .map_err(signing_error)?;
+
+        let Some(url) = &self.timestamp_url else {
+            return Ok(cms);
+        };
+        let signer_info = SignerInfo::parse(&cms).ok_or_else(|| Error::SigningError("unexpected CMS structure".to_owned()))?;
+        let token = self.timestamp(url, signer_info.signature()?).await?;
+        Ok(signer_info.with_unsigned_attribute(&attribute(OID_TIMESTAMP_TOKEN, &token)))
+    }
+
+    /// Requests a timestamp token for the signature value from a timestamp authority.
+    async fn timestamp(&self, url: &str, signature: &[u8]) -> Result<Vec<u8>, Error> {
+        let digest = hash(MessageDigest::sha256(), signature).map_err(signing_error)?;
+        let nonce: [u8; 8] = rand_bytes()?;
+        let request = sequence(&[
+            &integer(&[1]),
+            &sequence(&[&sequence(&[&oid(OID_SHA256)]), &octet_string(&digest)]),
+            &integer(&nonce),
+            // Requests the certificate of the timestamp authority, so that the token can be verified.
+            &[0x01, 0x01, 0xff],
+        ]);
+
+        let response = reqwest::Client::new()
+            .post(url)
+            .header("Content-Type", "application/timestamp-query")
+            .body(request)
+            .timeout(self.timeout)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(signing_error)?
+            .bytes()
+            .await
+            .map_err(signing_error)?;
+
+        let token = timestamp_token(&response)?;
+        verify_timestamp_token(&token, &digest, &nonce)?;
+        Ok(token)
+    }
+}
+
+impl Debug for PdfSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PdfSigner")
+            .field("certificate", &self.certificate.subject_name())
+            .field("timestamp_url", &self.timestamp_url)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Extracts the token from a `TimeStampResp`, if the timestamp was granted.
+fn timestamp_token(response: &[u8]) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::SigningError("invalid response from the timestamp authority".to_owned());
+
+    let (0x30, response, _) = read_tlv(response).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+    let (0x30, status_info, token) = read_tlv(response).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+    let (0x02, status, _) = read_tlv(status_info).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+
+    // Status 0 is granted, 1 is granted with modifications.
+    if !matches!(status, [0] | [1]) || token.is_empty() {
+        return Err(Error::SigningError("the timestamp authority rejected the request".to_owned()));
+    }
+    let (_, _, rest) = read_tlv(token).ok_or_else(invalid)?;
+    Ok(token[..token.len() - rest.len()].to_vec())
+}
+
+/// Checks that the token was signed with the certificate it contains, and that it timestamps the requested digest
+/// and answers the request with the given nonce, so that unrelated or replayed tokens are not embedded.
+/// Whether the timestamp authority is trusted is decided by the verifier of the PDF.
+fn verify_timestamp_token(token: &[u8], digest: &[u8], nonce: &[u8]) -> Result<(), Error> {
+    let mut tst_info = Vec::new();
+    CmsContentInfo::from_der(token)
+        .and_then(|mut token| {
+            token.verify(None, None, None, Some(&mut tst_info), CMSOptions::BINARY | CMSOptions::NO_SIGNER_CERT_VERIFY)
+        })
+        .map_err(|err| Error::SigningError(format!("invalid timestamp token: {err}")))?;
+
+    let mismatch = |field: &str| Error::SigningError(format!("the {field} of the timestamp does not match the request"));
+    let invalid = || Error::SigningError("invalid timestamp token".to_owned());
+
+    // TSTInfo ::= SEQUENCE { version, policy, messageImprint, serialNumber, genTime, accuracy?, ordering?, nonce?, ... }
+    let (0x30, tst_info, _) = read_tlv(&tst_info).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+    let (0x02, _, rest) = read_tlv(tst_info).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+    let (0x06, _, rest) = read_tlv(rest).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+    let (0x30, message_imprint, mut rest) = read_tlv(rest).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+    let (0x30, algorithm, hashed_message) = read_tlv(message_imprint).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+    let (0x06, algorithm, _) = read_tlv(algorithm).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+    let (0x04, hashed_message, _) = read_tlv(hashed_message).ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+    if algorithm != &oid(OID_SHA256)[2..] || hashed_message != digest {
+        return Err(mismatch("message imprint"));
+    }
+
+    // The nonce is the only integer after the serial number.
+    let mut integers = 0;
+    while let Some((tag, value, next)) = read_tlv(rest) {
+        if tag == 0x02 {
+            integers += 1;
+            if integers == 2 {
+                return if value == &integer(nonce)[2..] { Ok(()) } else { Err(mismatch("nonce")) };
+            }
+        }
+        rest = next;
+    }
+    Err(mismatch("nonce"))
+}
+
+/// The only `SignerInfo` of a CMS `ContentInfo` with `SignedData`, as created by OpenSSL.
+struct SignerInfo<'a> {
+    content_type: &'a [u8],
+    /// The elements of the `SignedData` before the `SignerInfo`s.
+    signed_data: &'a [u8],
+    /// The elements of the `SignerInfo`.
+    elements: Vec<&'a [u8]>,
+}
+
+impl<'a> SignerInfo<'a> {
+    fn parse(cms: &'a [u8]) -> Option<Self> {
+        let (0x30, content_info, _) = read_tlv(cms)? else {
+            return None;
+        };
+        let (0x06, _, rest) = read_tlv(content_info)? else {
+            return None;
+        };
+        let content_type = &content_info[..content_info.len() - rest.len()];
+        let (0xa0, explicit, _) = read_tlv(rest)? else {
+            return None;
+        };
+        let (0x30, signed_data, _) = read_tlv(explicit)? else {
+            return None;
+        };
+
+        // The signer infos are the last element of the signed data.
+        let mut rest = signed_data;
+        let mut last = None;
+        while !rest.is_empty() {
+            let (tag, value, next) = read_tlv(rest)?;
+            last = Some((tag, value, signed_data.len() - rest.len()));
+            rest = next;
+        }
+        let (0x31, signer_infos, start) = last? else {
+            return None;
+        };
+        let (0x30, signer_info, []) = read_tlv(signer_infos)? else {
+            return None;
+        };
+
+        let mut elements = Vec::new();
+        let mut rest = signer_info;
+        while !rest.is_empty() {
+            let (_, _, next) = read_tlv(rest)?;
+            elements.push(&rest[..rest.len() - next.len()]);
+            rest = next;
+        }
+
+        Some(Self {
+            content_type,
+            signed_data: &signed_data[..start],
+            elements,
+        })
+    }
+
+    /// The value of the signature, which is the last element unless unsigned attributes follow.
+    fn signature(&self) -> Result<&'a [u8], Error> {
+        match self.elements.last().and_then(|element| read_tlv(element)) {
+            Some((0x04, signature, _)) => Ok(signature),
+            _ => Err(Error::SigningError("unexpected CMS structure".to_owned())),
+        }
+    }
+
+    /// Encodes the `ContentInfo` with an unsigned attribute added to the `SignerInfo`.
+    fn with_unsigned_attribute(&self, attribute: &[u8]) -> Vec<u8> {
+        let unsigned_attributes = tlv(0xa1, attribute);
+        let signer_info = sequence(&[&self.elements.concat(), &unsigned_attributes]);
+        let signed_data = sequence(&[self.signed_data, &tlv(0x31, &signer_info)]);
+        sequence(&[self.content_type, &tlv(0xa0, &signed_data)])
+    }
+}
+
+fn signing_time() -> String {
+    let now = time::OffsetDateTime::now_utc();
+    format!(
+        "D:{:04}{:02}{:02}{:02}{:02}{:02}Z",
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    )
+}
+
+/// Encodes a PDF text string as UTF-16BE with byte order mark.
+fn pdf_string(value: &str) -> String {
+    let mut encoded = String::from("<FEFF");
+    for unit in value.encode_utf16() {
+        encoded.push_str(&format!("{unit:04X}"));
+    }
+    encoded.push('>');
+    encoded
+}
+
+fn rand_bytes<const N: usize>() -> Result<[u8; N], Error> {
+    let mut bytes = [0; N];
+    openssl::rand::rand_bytes(&mut bytes).map_err(signing_error)?;
+    Ok(bytes)
+}
+
+fn signing_error<E: ToString>(err: E) -> Error {
+    Error::SigningError(err.to_string())
+}
+
+/// The `CMS_CADES` flag of OpenSSL 3, which the `openssl` crate does not define.
+const CMS_CADES: u32 = 0x100000;
+
+const OID_TIMESTAMP_TOKEN: &[u64] = &[1, 2, 840, 113549, 1, 9, 16, 2, 14];
+const OID_SHA256: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 1];
+
+/// Encodes a DER tag, length and value.
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    if content.len() < 0x80 {
+        encoded.push(content.len() as u8);
+    } else {
+        let length = content.len().to_be_bytes();
+        let length = &length[length.iter().take_while(|&&byte| byte == 0).count()..];
+        encoded.push(0x80 | length.len() as u8);
+        encoded.extend(length);
+    }
+    encoded.extend(content);
+    encoded
+}
+
+/// Decodes a DER tag, length and value, and returns the tag, the value and the remaining data.
+fn read_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, data) = data.split_first()?;
+    let (&length, mut data) = data.split_first()?;
+
+    let length = if length < 0x80 {
+        length as usize
+    } else {
+        let (length, rest) = data.split_at_checked((length & 0x7f) as usize)?;
+        data = rest;
+        length.iter().try_fold(0usize, |acc, &byte| acc.checked_mul(256)?.checked_add(byte as usize))?
+    };
+
+    let (content, rest) = data.split_at_checked(length)?;
+    Some((tag, content, rest))
+}
+
+fn sequence(items: &[&[u8]]) -> Vec<u8> {
+    tlv(0x30, &items.concat())
+}
+
+fn octet_string(value: &[u8]) -> Vec<u8> {
+    tlv(0x04, value)
+}
+
+/// Encodes an unsigned big-endian integer.
+fn integer(value: &[u8]) -> Vec<u8> {
+    let value = &value[value.iter().take_while(|&&byte| byte == 0).count().min(value.len().saturating_sub(1))..];
+    if value.first().is_some_and(|&byte| byte & 0x80 != 0) {
+        tlv(0x02, &[&[0], value].concat())
+    } else {
+        tlv(0x02, value)
+    }
+}
+
+fn oid(arcs: &[u64]) -> Vec<u8> {
+    let mut encoded = vec![(arcs[0] * 40 + arcs[1]) as u8];
+    for &arc in &arcs[2..] {
+        let mut bytes = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            bytes.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        encoded.extend(bytes.iter().rev());
+    }
+    tlv(0x06, &encoded)
+}
+
+fn attribute(oid_arcs: &[u64], value: &[u8]) -> Vec<u8> {
+    sequence(&[&oid(oid_arcs), &tlv(0x31, value)])
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use openssl::{
+        asn1::Asn1Time,
+        bn::BigNum,
+        cms::{CMSOptions, CmsContentInfo},
+        rsa::Rsa,
+        stack::Stack,
+        x509::{store::X509StoreBuilder, X509Name},
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Creates a self-signed certificate and its private key.
+    pub(in super::super) fn test_signer() -> PdfSigner {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509Name::builder().unwrap();
+        name.append_entry_by_text("CN", "Nova Forms Test").unwrap();
+        let name = name.build();
+
+        let mut certificate = X509::builder().unwrap();
+        certificate.set_version(2).unwrap();
+        certificate.set_serial_number(&BigNum::from_u32(0x80).unwrap().to_asn1_integer().unwrap()).unwrap();
+        certificate.set_subject_name(&name).unwrap();
+        certificate.set_issuer_name(&name).unwrap();
+        certificate.set_pubkey(&key).unwrap();
+        certificate.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        certificate.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        certificate.sign(&key, MessageDigest::sha256()).unwrap();
+        let certificate = certificate.build();
+
+        PdfSigner::from_pem(&certificate.to_pem().unwrap(), &key.private_key_to_pem_pkcs8().unwrap()).unwrap()
+    }
+
+    /// Verifies the signature of a signed PDF and returns the CMS structure.
+    pub(in super::super) fn verify(signer: &PdfSigner, signed: &[u8]) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+        let text = String::from_utf8_lossy(signed);
+        let byte_range = regex::Regex::new(r"/ByteRange \[0 (\d+) (\d+) (\d+)").unwrap().captures(&text).unwrap();
+        let [first, second, length] = [1, 2, 3].map(|i| byte_range[i].parse::<usize>().unwrap());
+        assert_eq!(second + length, signed.len());
+
+        let contents = hex::decode(&signed[first + 1..second - 1]).unwrap();
+        let (_, _, padding) = read_tlv(&contents).unwrap();
+        let cms = contents[..contents.len() - padding.len()].to_vec();
+
+        let mut store = X509StoreBuilder::new().unwrap();
+        store.add_cert(signer.certificate.clone()).unwrap();
+        let data = [&signed[..first], &signed[second..]].concat();
+        let certificates: Stack<X509> = Stack::new()?;
+        CmsContentInfo::from_der(&cms)?.verify(Some(&certificates), Some(&store.build()), Some(&data), None, CMSOptions::BINARY)?;
+
+        Ok(cms)
+    }
+
+    #[tokio::test]
+    async fn test_sign() {
+        let signer = test_signer().reason("Submission");
+        let pdf = crate::server::pdf_gen::pdf_file::tests::classic_pdf();
+
+        let signed = signer.sign(&pdf).await.unwrap();
+        assert!(signed.starts_with(&pdf));
+        assert!(verify(&signer, &signed).is_ok());
+
+        // Modifications are detected.
+        let mut modified = signed.clone();
+        let position = modified.windows(5).position(|window| window == b"/Lang").unwrap();
+        modified[position + 7] = b'f';
+        assert!(verify(&signer, &modified).is_err());
+    }
+
+    /// Starts a minimal timestamp authority that answers with a token for the `TSTInfo` created from the request.
+    async fn fake_tsa(tst_info: impl FnOnce(&[u8], &[u8]) -> Vec<u8> + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let body = loop {
+                let mut chunk = vec![0; 4096];
+                let length = stream.read(&mut chunk).await.unwrap();
+                request.extend(&chunk[..length]);
+                if let Some(end) = find_subslice(&request, b"\r\n\r\n") {
+                    let body = &request[end + 4..];
+                    if read_tlv(body).is_some() {
+                        break body;
+                    }
+                }
+            };
+
+            // TimeStampReq ::= SEQUENCE { version, messageImprint, nonce, certReq }
+            let (_, request, _) = read_tlv(body).unwrap();
+            let (_, _, rest) = read_tlv(request).unwrap();
+            let message_imprint = &rest[..rest.len() - read_tlv(rest).unwrap().2.len()];
+            let (_, _, rest) = read_tlv(rest).unwrap();
+            let nonce = &rest[..rest.len() - read_tlv(rest).unwrap().2.len()];
+
+            let authority = test_signer();
+            let token = CmsContentInfo::sign(
+                Some(&authority.certificate),
+                Some(&authority.key),
+                None,
+                Some(&tst_info(message_imprint, nonce)),
+                CMSOptions::BINARY,
+            )
+            .unwrap()
+            .to_der()
+            .unwrap();
+
+            let body = sequence(&[&sequence(&[&integer(&[0])]), &token]);
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/timestamp-reply\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(&[head.as_bytes(), &body].concat()).await.unwrap();
+        });
+
+        format!("http://{address}/tsa")
+    }
+
+    fn find_subslice(data: &[u8], needle: &[u8]) -> Option<usize> {
+        data.windows(needle.len()).position(|window| window == needle)
+    }
+
+    /// Encodes a `TSTInfo` with the given message imprint and nonce.
+    fn tst_info(message_imprint: &[u8], nonce: &[u8]) -> Vec<u8> {
+        sequence(&[
+            &integer(&[1]),
+            &oid(&[1, 2, 3, 4]),
+            message_imprint,
+            &integer(&[42]),
+            &tlv(0x18, b"20240101000000Z"),
+            nonce,
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_sign_with_timestamp() {
+        let signer = test_signer().timestamp_url(fake_tsa(tst_info).await);
+        let pdf = crate::server::pdf_gen::pdf_file::tests::classic_pdf();
+
+        let cms = verify(&signer, &signer.sign(&pdf).await.unwrap()).unwrap();
+        assert!(find_subslice(&cms, &oid(OID_TIMESTAMP_TOKEN)).is_some());
+        // The signing certificate is a signed attribute.
+        assert!(find_subslice(&cms, &oid(&[1, 2, 840, 113549, 1, 9, 16, 2, 47])).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_sign_with_invalid_timestamp() {
+        let pdf = crate::server::pdf_gen::pdf_file::tests::classic_pdf();
+
+        // A token for another request is rejected.
+        let signer = test_signer().timestamp_url(fake_tsa(|message_imprint, _| tst_info(message_imprint, &integer(&[7]))).await);
+        let Err(Error::SigningError(reason)) = signer.sign(&pdf).await else {
+            panic!("signing must fail");
+        };
+        assert!(reason.contains("nonce"));
+
+        let signer = test_signer().timestamp_url(fake_tsa(|_, nonce| {
+            let other_imprint = sequence(&[&sequence(&[&oid(OID_SHA256)]), &octet_string(&[0; 32])]);
+            tst_info(&other_imprint, nonce)
+        }).await);
+        let Err(Error::SigningError(reason)) = signer.sign(&pdf).await else {
+            panic!("signing must fail");
+        };
+        assert!(reason.contains("message imprint"));
+
+        // Tokens that are not signed are rejected.
+        let token = sequence(&[&oid(&[1, 2, 840, 113549, 1, 7, 2])]);
+        assert!(verify_timestamp_token(&token, &[0; 32], &[1; 8]).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_token() {
+        let token = sequence(&[&oid(&[1, 2, 840, 113549, 1, 7, 2])]);
+        let granted = sequence(&[&sequence(&[&integer(&[0])]), &token]);
+        assert_eq!(timestamp_token(&granted).unwrap(), token);
+
+        let rejected = sequence(&[&sequence(&[&integer(&[2])])]);
+        assert!(timestamp_token(&rejected).is_err());
+    }
+
+    #[test]
+    fn test_der() {
+        assert_eq!(oid(OID_SHA256), [0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01]);
+        assert_eq!(integer(&[0, 0, 0x80]), [0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(integer(&[0]), [0x02, 0x01, 0x00]);
+        assert_eq!(&tlv(0x04, &[0; 300])[..4], [0x04, 0x82, 0x01, 0x2c]);
+        assert_eq!(read_tlv(&tlv(0x04, &[1; 300])), Some((0x04, &[1; 300][..], &[][..])));
+    }
+
+    #[test]
+    fn test_mismatched_key() {
+        let signer = test_signer();
+        let other = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        let result = PdfSigner::from_pem(&signer.certificate.to_pem().unwrap(), &other.private_key_to_pem_pkcs8().unwrap());
+        assert!(matches!(result, Err(Error::SigningError(_))));
+    }
+}